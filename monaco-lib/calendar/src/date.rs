@@ -0,0 +1,146 @@
+/// Calendar date implementation
+
+use std::fmt;
+use serde::{Serialize, Serializer};
+
+/// Number of days per year of the simulation time axis (times are ACT/365F year fractions from the valuation date)
+pub const DAYS_PER_YEAR:f64=365.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+/// Gregorian calendar date
+pub struct Date
+{
+    pub year:i32,
+    pub month:u32,
+    pub day:u32
+}
+
+/// Whether 'year' is a leap year
+pub fn is_leap_year(year:i32) -> bool
+{
+    return (year%4==0 && year%100!=0) || year%400==0;
+}
+
+/// Number of days of 'month' in 'year'
+pub fn days_in_month(year:i32,month:u32) -> u32
+{
+    return match month
+    {
+        1|3|5|7|8|10|12 =>  31,
+        4|6|9|11        =>  30,
+        2               =>  if is_leap_year(year) { 29 } else { 28 },
+        _               =>  panic!("Invalid month ({})!",month)
+    };
+}
+
+impl Date
+{
+    /// Creates a date, checking that it exists
+    pub fn new(year:i32,month:u32,day:u32) -> Result<Date,String>
+    {
+        if month<1 || month>12 || day<1 || day>days_in_month(year,month)
+        {
+            return Err(format!("Invalid date ({:04}-{:02}-{:02})",year,month,day));
+        }
+        return Ok(Date { year:year, month:month, day:day });
+    }
+
+    /// Parses an ISO date (YYYY-MM-DD)
+    pub fn parse(text:&str) -> Result<Date,String>
+    {
+        let parts:Vec<&str>=text.split('-').collect();
+        if parts.len()!=3 || parts[0].len()!=4 || parts[1].len()!=2 || parts[2].len()!=2 || !parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(format!("Invalid ISO date ({}): the format must be YYYY-MM-DD",text));
+        }
+        return Date::new(parts[0].parse().unwrap(),parts[1].parse().unwrap(),parts[2].parse().unwrap());
+    }
+
+    /// Number of days since 1970-01-01
+    ///
+    /// # Remarks
+    ///
+    /// See H. Hinnant, chrono-Compatible Low-Level Date Algorithms (days_from_civil)
+    pub fn serial(&self) -> i64
+    {
+        let y=(if self.month<=2 { self.year-1 } else { self.year }) as i64;
+        let era=if y>=0 { y } else { y-399 }/400;
+        let yoe=y-era*400;
+        let m=self.month as i64;
+        let doy=(153*(if m>2 { m-3 } else { m+9 })+2)/5+(self.day as i64)-1;
+        let doe=yoe*365+yoe/4-yoe/100+doy;
+        return era*146097+doe-719468;
+    }
+
+    /// Date of a serial number (number of days since 1970-01-01)
+    pub fn from_serial(serial:i64) -> Date
+    {
+        let z=serial+719468;
+        let era=if z>=0 { z } else { z-146096 }/146097;
+        let doe=z-era*146097;
+        let yoe=(doe-doe/1460+doe/36524-doe/146096)/365;
+        let doy=doe-(365*yoe+yoe/4-yoe/100);
+        let mp=(5*doy+2)/153;
+        let day=(doy-(153*mp+2)/5+1) as u32;
+        let month=(if mp<10 { mp+3 } else { mp-9 }) as u32;
+        let year=(yoe+era*400+if month<=2 { 1 } else { 0 }) as i32;
+        return Date { year:year, month:month, day:day };
+    }
+
+    /// ISO day of the week (1 is Monday, 7 is Sunday)
+    pub fn weekday(&self) -> u32
+    {
+        //1970-01-01 was a Thursday
+        return ((self.serial()+3).rem_euclid(7)+1) as u32;
+    }
+
+    pub fn add_days(&self,days:i64) -> Date
+    {
+        return Date::from_serial(self.serial()+days);
+    }
+
+    /// Adds a number of months (the day is capped at the end of the month, or moved to the end of the month if 'end_of_month' is set)
+    pub fn add_months(&self,months:i32,end_of_month:bool) -> Date
+    {
+        let total=self.year*12+(self.month as i32-1)+months;
+        let year=total.div_euclid(12);
+        let month=(total.rem_euclid(12)+1) as u32;
+        let last_day=days_in_month(year,month);
+        let day=if end_of_month { last_day } else { self.day.min(last_day) };
+        return Date { year:year, month:month, day:day };
+    }
+
+    pub fn is_end_of_month(&self) -> bool
+    {
+        return self.day==days_in_month(self.year,self.month);
+    }
+
+    /// Time of the date on the simulation time axis of 'valuation_date'
+    pub fn to_time(&self,valuation_date:&Date) -> f64
+    {
+        return ((self.serial()-valuation_date.serial()) as f64)/DAYS_PER_YEAR;
+    }
+
+    /// Date of a time on the simulation time axis of 'valuation_date' (rounded to the nearest day)
+    pub fn from_time(valuation_date:&Date,time:f64) -> Date
+    {
+        return valuation_date.add_days((time*DAYS_PER_YEAR).round() as i64);
+    }
+}
+
+impl fmt::Display for Date
+{
+    fn fmt(&self,f:&mut fmt::Formatter) -> fmt::Result
+    {
+        return write!(f,"{:04}-{:02}-{:02}",self.year,self.month,self.day);
+    }
+}
+
+/// Dates are serialized as ISO strings
+impl Serialize for Date
+{
+    fn serialize<S:Serializer>(&self,serializer:S) -> Result<S::Ok,S::Error>
+    {
+        return serializer.collect_str(self);
+    }
+}
@@ -0,0 +1,69 @@
+/// Day count conventions
+
+use crate::date::{Date,is_leap_year};
+
+/// Names of the supported day count conventions
+pub const DAY_COUNTS:[&str;4]=["ACT/360","ACT/365F","30/360","ACT/ACT ISDA"];
+
+/// Checks that 'day_count' is a supported convention
+pub fn validate_day_count(day_count:&str) -> Result<(),String>
+{
+    if !DAY_COUNTS.contains(&day_count)
+    {
+        return Err(format!("Invalid day count convention ({}): it must be one of {:?}",day_count,DAY_COUNTS));
+    }
+    return Ok(());
+}
+
+/// Accrual year fraction between two dates
+///
+/// # Remarks
+///
+/// 30/360 is the ISDA bond basis: a 31st start day becomes the 30th, and a 31st end day becomes the 30th if the start day is the 30th or the 31st.
+/// ACT/ACT ISDA splits the period by calendar year, dividing the days in leap years by 366 and the others by 365.
+pub fn year_fraction(start:&Date,end:&Date,day_count:&str) -> f64
+{
+    let days=(end.serial()-start.serial()) as f64;
+    return match day_count
+    {
+        "ACT/360"       =>  days/360.0,
+        "ACT/365F"      =>  days/365.0,
+        "30/360"        =>  {
+                                let d1=start.day.min(30);
+                                let d2=if end.day==31 && d1==30 { 30 } else { end.day };
+                                let days_360=360*(end.year-start.year)+30*(end.month as i32-start.month as i32)+(d2 as i32-d1 as i32);
+                                (days_360 as f64)/360.0
+                            },
+        "ACT/ACT ISDA"  =>  {
+                                if end<start
+                                {
+                                    -year_fraction(end,start,day_count)
+                                }
+                                else
+                                {
+                                    let mut fraction:f64=0.0;
+                                    let mut period_start=*start;
+                                    while period_start<*end
+                                    {
+                                        let year_end=Date { year:period_start.year+1, month:1, day:1 }.min(*end);
+                                        let year_days=if is_leap_year(period_start.year) { 366.0 } else { 365.0 };
+                                        fraction+=((year_end.serial()-period_start.serial()) as f64)/year_days;
+                                        period_start=year_end;
+                                    }
+                                    fraction
+                                }
+                            },
+        _               =>  panic!("Invalid day count convention ({}): it must be one of {:?}!",day_count,DAY_COUNTS)
+    };
+}
+
+/// Accrual periods of a schedule of times on the simulation time axis of 'valuation_date' (the period of the first time is 0)
+pub fn get_accrual_periods(times:&Vec<f64>,valuation_date:&Date,day_count:&str) -> Vec<f64>
+{
+    let mut periods:Vec<f64>=Vec::new();
+    for t in 0..times.len()
+    {
+        periods.push(if t==0 { 0.0 } else { year_fraction(&Date::from_time(valuation_date,times[t-1]),&Date::from_time(valuation_date,times[t]),day_count) });
+    }
+    return periods;
+}
@@ -0,0 +1,111 @@
+/// Holiday calendars and business day adjustment
+
+use crate::date::Date;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+/// Names of the supported business day conventions
+pub const BUSINESS_DAY_CONVENTIONS:[&str;5]=["none","following","modified-following","preceding","modified-preceding"];
+
+fn default_weekend() -> Vec<u32>
+{
+    return vec![6,7];
+}
+
+/// Holiday calendar (weekend days and holidays)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HolidayCalendar
+{
+    pub name:String,
+    /// ISO days of the week that are not business days (1 is Monday, 7 is Sunday)
+    #[serde(default="default_weekend")]
+    pub weekend:Vec<u32>,
+    /// Holidays (ISO dates)
+    #[serde(default)]
+    pub holidays:Vec<String>,
+    #[serde(skip)]
+    holiday_serials:Vec<i64>
+}
+
+/// Checks that 'convention' is a supported business day convention
+pub fn validate_business_day_convention(convention:&str) -> Result<(),String>
+{
+    if !BUSINESS_DAY_CONVENTIONS.contains(&convention)
+    {
+        return Err(format!("Invalid business day convention ({}): it must be one of {:?}",convention,BUSINESS_DAY_CONVENTIONS));
+    }
+    return Ok(());
+}
+
+impl HolidayCalendar
+{
+    /// Checks the weekend and parses the holidays (to be called when the calendar is loaded)
+    pub fn init(&mut self) -> Result<(),String>
+    {
+        if let Some(day)=self.weekend.iter().find(|d| **d<1 || **d>7)
+        {
+            return Err(format!("Calendar {} has an invalid weekend day ({}): it must be between 1 (Monday) and 7 (Sunday)",&self.name,day));
+        }
+        if (1..=7).all(|d| self.weekend.contains(&d))
+        {
+            return Err(format!("Calendar {} has no business day in the week",&self.name));
+        }
+        let mut serials:Vec<i64>=Vec::new();
+        for holiday in self.holidays.iter()
+        {
+            match Date::parse(holiday)
+            {
+                Ok(d)   =>  serials.push(d.serial()),
+                Err(m)  =>  return Err(format!("Calendar {}: {}",&self.name,m))
+            };
+        }
+        serials.sort();
+        serials.dedup();
+        self.holiday_serials=serials;
+        return Ok(());
+    }
+
+    pub fn is_business_day(&self,date:&Date) -> bool
+    {
+        return !self.weekend.contains(&date.weekday()) && self.holiday_serials.binary_search(&date.serial()).is_err();
+    }
+
+    /// Moves a date to a business day according to 'convention'
+    pub fn adjust(&self,date:&Date,convention:&str) -> Date
+    {
+        let roll=|step:i64| { let mut d=*date; while !self.is_business_day(&d) { d=d.add_days(step); } d };
+        return match convention
+        {
+            "none"                  =>  *date,
+            "following"             =>  roll(1),
+            "preceding"             =>  roll(-1),
+            "modified-following"    =>  { let d=roll(1); if d.month!=date.month { roll(-1) } else { d } },
+            "modified-preceding"    =>  { let d=roll(-1); if d.month!=date.month { roll(1) } else { d } },
+            _                       =>  panic!("Invalid business day convention ({}): it must be one of {:?}!",convention,BUSINESS_DAY_CONVENTIONS)
+        };
+    }
+
+    /// Adjusts a schedule of times on the simulation time axis of 'valuation_date'
+    pub fn adjust_times(&self,times:&Vec<f64>,valuation_date:&Date,convention:&str) -> Vec<f64>
+    {
+        return times.iter().map(|t| self.adjust(&Date::from_time(valuation_date,*t),convention).to_time(valuation_date)).collect();
+    }
+}
+
+/// Returns the calendar 'name' ('A+B' is the joint calendar of 'A' and 'B': a business day must be a business day in both)
+pub fn get_calendar(name:&str,calendars:&HashMap<String,HolidayCalendar>) -> Result<HolidayCalendar,String>
+{
+    let mut joint=HolidayCalendar { name:name.to_string(), weekend:Vec::new(), holidays:Vec::new(), holiday_serials:Vec::new() };
+    for part in name.split('+')
+    {
+        let calendar=match calendars.get(part)
+        {
+            Some(c)     =>  c,
+            None        =>  return Err(format!("Non-existent calendar ({})",part))
+        };
+        joint.weekend.extend(calendar.weekend.iter().filter(|d| !joint.weekend.contains(d)).copied().collect::<Vec<u32>>());
+        joint.holidays.extend(calendar.holidays.iter().cloned());
+    }
+    joint.init()?;
+    return Ok(joint);
+}
@@ -0,0 +1,69 @@
+pub mod date;
+pub mod day_count;
+pub mod holiday_calendar;
+pub mod schedule;
+
+#[cfg(test)]
+mod tests
+{
+    use crate::date::Date;
+    use crate::day_count::year_fraction;
+
+    #[test]
+    fn date_serials()
+    {
+        let d=Date::parse("2024-02-29").unwrap();
+        assert_eq!(Date::from_serial(d.serial()),d);
+        assert_eq!(Date::parse("1970-01-01").unwrap().serial(),0);
+        assert_eq!(d.weekday(),4);
+        assert_eq!(Date::parse("2024-01-31").unwrap().add_months(1,false),d);
+        assert!(Date::parse("2023-02-29").is_err());
+    }
+
+    #[test]
+    fn day_counts()
+    {
+        let start=Date::parse("2023-11-30").unwrap();
+        let end=Date::parse("2024-05-31").unwrap();
+        assert!((year_fraction(&start,&end,"ACT/360")-183.0/360.0).abs()<1e-12);
+        assert!((year_fraction(&start,&end,"30/360")-0.5).abs()<1e-12);
+        assert!((year_fraction(&start,&end,"ACT/ACT ISDA")-(32.0/365.0+151.0/366.0)).abs()<1e-12);
+    }
+
+    #[test]
+    fn calendar_weekends()
+    {
+        use crate::holiday_calendar::{HolidayCalendar,get_calendar};
+        let calendar=|name:&str,weekend:Vec<u32>| serde_json::from_str::<HolidayCalendar>(&format!(r#"{{"name":"{}","weekend":{:?}}}"#,name,weekend)).unwrap();
+        let mut calendars=std::collections::HashMap::new();
+        for (name,weekend) in [("A",vec![1,2,3,4]),("B",vec![5,6,7])]
+        {
+            let mut c=calendar(name,weekend);
+            assert!(c.init().is_ok());
+            calendars.insert(name.to_string(),c);
+        }
+        assert!(calendar("C",vec![6,8]).init().is_err());
+        assert!(calendar("D",vec![1,2,3,4,5,6,7]).init().is_err());
+        //The joint calendar of A and B has no business day
+        assert!(get_calendar("A+B",&calendars).is_err());
+        assert!(get_calendar("A",&calendars).is_ok());
+    }
+
+    #[test]
+    fn schedule_stubs()
+    {
+        let valuation_date=Date::parse("2025-01-15").unwrap();
+        let mut definition=crate::schedule::ScheduleDefinition { start_date:Date::parse("2025-03-10").unwrap().to_time(&valuation_date), end_date:Date::parse("2026-03-31").unwrap().to_time(&valuation_date),
+                                                                 frequency:"6M".to_string(), stub:"short-front".to_string(), roll_day:None, end_of_month:true, fixing_lag:2, payment_lag:0 };
+        let periods=definition.generate(&valuation_date,None,"none").unwrap();
+        let ends:Vec<String>=periods.iter().map(|p| p.end_date.to_string()).collect();
+        assert_eq!(ends,vec!["2025-03-31","2025-09-30","2026-03-31"]);
+        assert_eq!(periods[1].fixing_date.to_string(),"2025-03-29");
+        definition.stub="long-front".to_string();
+        assert_eq!(definition.generate(&valuation_date,None,"none").unwrap()[0].end_date.to_string(),"2025-09-30");
+        definition.stub="short-back".to_string();
+        definition.end_of_month=false;
+        let ends:Vec<String>=definition.generate(&valuation_date,None,"none").unwrap().iter().map(|p| p.end_date.to_string()).collect();
+        assert_eq!(ends,vec!["2025-09-10","2026-03-10","2026-03-31"]);
+    }
+}
@@ -0,0 +1,195 @@
+/// Schedule generator
+
+use crate::date::Date;
+use crate::holiday_calendar::{HolidayCalendar,validate_business_day_convention};
+use serde::{Serialize, Deserialize};
+
+/// Names of the supported stub rules
+pub const STUBS:[&str;4]=["short-front","long-front","short-back","long-back"];
+
+fn default_stub() -> String
+{
+    return "short-front".to_string();
+}
+
+/// Definition of a regular schedule of periods between two dates
+///
+/// # Remarks
+///
+/// The start and end dates are times on the simulation time axis (ISO dates in the instrument files are converted when they are read).
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduleDefinition
+{
+    pub start_date:f64,
+    pub end_date:f64,
+    /// Period length ('1W', '3M', '1Y', ...)
+    pub frequency:String,
+    #[serde(default="default_stub")]
+    pub stub:String,
+    /// Day of the month of the unadjusted dates (optional, by default the day of the date from which the schedule is rolled)
+    #[serde(default)]
+    pub roll_day:Option<u32>,
+    /// Whether the unadjusted dates stay on the end of the month when the date from which the schedule is rolled is an end of month
+    #[serde(default)]
+    pub end_of_month:bool,
+    /// Business days between the fixing date and the (adjusted) start of each period
+    #[serde(default)]
+    pub fixing_lag:i64,
+    /// Business days between the (adjusted) end of each period and its payment date
+    #[serde(default)]
+    pub payment_lag:i64
+}
+
+/// Period of a generated schedule
+#[derive(Serialize, Clone)]
+pub struct SchedulePeriod
+{
+    pub unadjusted_start_date:Date,
+    pub unadjusted_end_date:Date,
+    pub start_date:Date,
+    pub end_date:Date,
+    pub fixing_date:Date,
+    pub payment_date:Date,
+    /// Accrual year fraction (set by the instrument, according to its day count convention)
+    pub accrual_period:f64
+}
+
+/// Parses a period length ('nD', 'nW', 'nM' or 'nY'): returns the number of units and the unit
+pub fn parse_tenor(tenor:&str) -> Result<(i32,char),String>
+{
+    let unit=tenor.chars().last().unwrap_or(' ').to_ascii_uppercase();
+    let count=tenor[..tenor.len().saturating_sub(1)].parse::<i32>();
+    return match (count,unit)
+    {
+        (Ok(n),'D'|'W'|'M'|'Y') if n>0 =>  Ok((n,unit)),
+        _                               =>  Err(format!("Invalid frequency ({}): it must be a positive number of days, weeks, months or years (e.g. '3M')",tenor))
+    };
+}
+
+/// Moves a date by a number of business days of 'calendar' (calendar days if there is no calendar)
+fn add_business_days(date:&Date,days:i64,calendar:Option<&HolidayCalendar>) -> Date
+{
+    let calendar=match calendar
+    {
+        Some(c)     =>  c,
+        None        =>  return date.add_days(days)
+    };
+    let step=if days<0 { -1 } else { 1 };
+    let mut d=*date;
+    for _i in 0..days.abs()
+    {
+        d=d.add_days(step);
+        while !calendar.is_business_day(&d) { d=d.add_days(step); }
+    }
+    return d;
+}
+
+impl ScheduleDefinition
+{
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        parse_tenor(&self.frequency)?;
+        if !STUBS.contains(&self.stub.as_str())
+        {
+            return Err(format!("Invalid stub ({}): it must be one of {:?}",&self.stub,STUBS));
+        }
+        if self.end_date<=self.start_date
+        {
+            return Err(format!("The end date of the schedule ({}) is not after the start date ({})",self.end_date,self.start_date));
+        }
+        if self.roll_day.map_or(false,|d| d<1 || d>31)
+        {
+            return Err(format!("Invalid roll day ({})",self.roll_day.unwrap()));
+        }
+        return Ok(());
+    }
+
+    /// Date 'k' periods away from 'anchor' (negative 'k' rolls backward), according to the roll conventions
+    fn roll(&self,anchor:&Date,k:i32) -> Date
+    {
+        let (n,unit)=parse_tenor(&self.frequency).unwrap();
+        return match unit
+        {
+            'D'     =>  anchor.add_days((k*n) as i64),
+            'W'     =>  anchor.add_days((7*k*n) as i64),
+            _       =>  {
+                            let months=if unit=='Y' { 12*k*n } else { k*n };
+                            let d=anchor.add_months(months,self.end_of_month && anchor.is_end_of_month());
+                            match self.roll_day
+                            {
+                                Some(r) if !(self.end_of_month && anchor.is_end_of_month())  =>  Date { year:d.year, month:d.month, day:r.min(crate::date::days_in_month(d.year,d.month)) },
+                                _                                                               =>  d
+                            }
+                        }
+        };
+    }
+
+    /// Unadjusted dates of the schedule (start date, period ends)
+    ///
+    /// # Remarks
+    ///
+    /// Front stubs roll the dates backward from the end date, back stubs forward from the start date (each date is computed from the anchor date, not from the previous one).
+    /// A long stub is merged with the adjacent regular period.
+    fn get_unadjusted_dates(&self,start:&Date,end:&Date) -> Vec<Date>
+    {
+        let backward=self.stub.ends_with("front");
+        let (anchor,last)=if backward { (*end,*start) } else { (*start,*end) };
+        let mut dates:Vec<Date>=vec![anchor];
+        let mut k:i32=1;
+        loop
+        {
+            let d=self.roll(&anchor,if backward { -k } else { k });
+            if (backward && d<=last) || (!backward && d>=last)
+            {
+                break;
+            }
+            dates.push(d);
+            k+=1;
+        }
+        let has_stub=self.roll(&anchor,if backward { -k } else { k })!=last;
+        if has_stub && self.stub.starts_with("long") && dates.len()>1
+        {
+            dates.pop();
+        }
+        dates.push(last);
+        if backward
+        {
+            dates.reverse();
+        }
+        return dates;
+    }
+
+    /// Generates the periods of the schedule
+    ///
+    /// # Arguments
+    ///
+    /// * `valuation_date` - Date of the time 0
+    /// * `calendar` - Calendar used to adjust the dates and count the lags (no adjustment if not set)
+    /// * `business_day_convention` - Business day convention of the accrual and payment dates
+    pub fn generate(&self,valuation_date:&Date,calendar:Option<&HolidayCalendar>,business_day_convention:&str) -> Result<Vec<SchedulePeriod>,String>
+    {
+        self.validate()?;
+        validate_business_day_convention(business_day_convention)?;
+        let adjust=|d:&Date| match calendar { Some(c) => c.adjust(d,business_day_convention), None => *d };
+
+        let dates=self.get_unadjusted_dates(&Date::from_time(valuation_date,self.start_date),&Date::from_time(valuation_date,self.end_date));
+        let mut periods:Vec<SchedulePeriod>=Vec::new();
+        for p in 1..dates.len()
+        {
+            let start_date=adjust(&dates[p-1]);
+            let end_date=adjust(&dates[p]);
+            periods.push(SchedulePeriod
+            {
+                unadjusted_start_date:dates[p-1],
+                unadjusted_end_date:dates[p],
+                start_date:start_date,
+                end_date:end_date,
+                fixing_date:add_business_days(&start_date,-self.fixing_lag,calendar),
+                payment_date:add_business_days(&end_date,self.payment_lag,calendar),
+                accrual_period:0.0
+            });
+        }
+        return Ok(periods);
+    }
+}
@@ -0,0 +1,160 @@
+/// Exposure profile statistics
+
+use data_cube::data_cube::Cube;
+use serde::{Serialize, Deserialize};
+
+/// Potential future exposure at a quantile
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PfeProfile
+{
+    pub quantile:f64,
+    pub pfe:Vec<f64>,
+    pub pfe_std_error:Vec<f64>,
+    pub peak_pfe:f64,
+    pub peak_pfe_date:f64
+}
+
+/// Exposure statistics of a time series of an exposure cube (one value per date of the cube)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExposureProfile
+{
+    pub name:String,
+    pub dates:Vec<f64>,
+    pub ee:Vec<f64>,
+    pub ee_std_error:Vec<f64>,
+    pub ene:Vec<f64>,
+    pub ene_std_error:Vec<f64>,
+    pub effective_ee:Vec<f64>,
+    pub pfe:Vec<PfeProfile>,
+    pub epe:f64,
+    pub epe_std_error:f64,
+    pub effective_epe:f64,
+    pub horizon:f64
+}
+
+/// Empirical quantile of a sorted sample and its standard error
+///
+/// # Remarks
+///
+/// The standard error is estimated from the order statistics one binomial standard deviation (sqrt(N*q*(1-q)) ranks) below and above the quantile.
+fn quantile_std_error(sorted:&Vec<f64>,quantile:f64) -> (f64,f64)
+{
+    let n=sorted.len();
+    let k=((quantile*n as f64).ceil() as usize).clamp(1,n)-1;
+    let m=((n as f64)*quantile*(1.0-quantile)).sqrt().ceil() as usize;
+    let lower=sorted[k.saturating_sub(m)];
+    let upper=sorted[(k+m).min(n-1)];
+    return (sorted[k],(upper-lower)/2.0);
+}
+
+/// Weights of the dates of a time-weighted average over [0,horizon] (the value of each date covers the step that ends on it)
+fn get_time_weights(dates:&Vec<f64>,horizon:f64) -> Vec<f64>
+{
+    let mut weights:Vec<f64>=vec![0.0;dates.len()];
+    for d in 1..dates.len()
+    {
+        weights[d]=(dates[d].min(horizon)-dates[d-1].min(horizon)).max(0.0);
+    }
+    let total:f64=weights.iter().sum();
+    if total>0.0
+    {
+        return weights.iter().map(|w| w/total).collect();
+    }
+    //Only one date within the horizon
+    weights[0]=1.0;
+    return weights;
+}
+
+/// Checks the quantiles and the horizon of the exposure profiles
+pub fn validate_profile_parameters(quantiles:&Vec<f64>,horizon:f64) -> Result<(),String>
+{
+    if let Some(q)=quantiles.iter().find(|q| **q<=0.0 || **q>=1.0)
+    {
+        return Err(format!("Invalid PFE quantile ({}): it must be between 0 and 1",q));
+    }
+    if horizon<=0.0
+    {
+        return Err(format!("Invalid exposure horizon ({}): it must be positive",horizon));
+    }
+    return Ok(());
+}
+
+/// Computes the exposure profile of each time series of an exposure cube
+///
+/// # Arguments
+///
+/// * `exposures` - Cube of the values (trades, netting sets...)
+/// * `quantiles` - Quantiles of the PFE profiles
+/// * `horizon` - Horizon of the EPE and effective EPE (e.g. 1 year for Basel), capped at the last date of the cube
+///
+/// # Remarks
+///
+/// - EE(t)=E\[max(V(t),0)\], ENE(t)=E\[min(V(t),0)\], PFE(t) is the quantile of max(V(t),0)
+/// - The effective EE is non-decreasing: EEE(t_k)=max(EEE(t_k-1),EE(t_k))
+/// - EPE and effective EPE are the time-weighted averages of EE and EEE over \[0,horizon\]
+/// - The EPE standard error is the standard error of the path-wise time-weighted average of the positive exposure (the effective EPE has none, as it is not an average over the paths)
+pub fn compute_profiles(exposures:&Cube,quantiles:&Vec<f64>,horizon:f64) -> Vec<ExposureProfile>
+{
+    let num_dates=exposures.dates.len();
+    if num_dates==0 || exposures.num_scenarios==0
+    {
+        return Vec::new();
+    }
+    let horizon=horizon.min(exposures.dates[num_dates-1]);
+    let weights=get_time_weights(&exposures.dates,horizon);
+    let mut profiles:Vec<ExposureProfile>=Vec::new();
+    for series in 0..exposures.num_series
+    {
+        let mut profile=ExposureProfile
+        {
+            name:exposures.time_series_names[series].clone(),
+            dates:exposures.dates.clone(),
+            ee:Vec::new(),
+            ee_std_error:Vec::new(),
+            ene:Vec::new(),
+            ene_std_error:Vec::new(),
+            effective_ee:Vec::new(),
+            pfe:quantiles.iter().map(|q| PfeProfile { quantile:*q, pfe:Vec::new(), pfe_std_error:Vec::new(), peak_pfe:0.0, peak_pfe_date:0.0 }).collect(),
+            epe:0.0,
+            epe_std_error:0.0,
+            effective_epe:0.0,
+            horizon:horizon
+        };
+        let mut path_epe:Vec<f64>=vec![0.0;exposures.num_scenarios];
+        for d in 0..num_dates
+        {
+            let values:Vec<f64>=(0..exposures.num_scenarios).map(|s| exposures.get_item(s,series,d).unwrap()).collect();
+            let mut positive:Vec<f64>=values.iter().map(|v| v.max(0.0)).collect();
+            let negative:Vec<f64>=values.iter().map(|v| v.min(0.0)).collect();
+            for s in 0..exposures.num_scenarios
+            {
+                path_epe[s]+=weights[d]*positive[s];
+            }
+            let (ee,ee_std_error)=math::math::mean_and_standard_error(&positive);
+            let (ene,ene_std_error)=math::math::mean_and_standard_error(&negative);
+            profile.ee.push(ee);
+            profile.ee_std_error.push(ee_std_error);
+            profile.ene.push(ene);
+            profile.ene_std_error.push(ene_std_error);
+            profile.effective_ee.push(if d==0 { ee } else { ee.max(profile.effective_ee[d-1]) });
+            positive.sort_by(|a,b| a.partial_cmp(b).unwrap());
+            for pfe in profile.pfe.iter_mut()
+            {
+                let (value,std_error)=quantile_std_error(&positive,pfe.quantile);
+                pfe.pfe.push(value);
+                pfe.pfe_std_error.push(std_error);
+                if d==0 || value>pfe.peak_pfe
+                {
+                    pfe.peak_pfe=value;
+                    pfe.peak_pfe_date=exposures.dates[d];
+                }
+            }
+        }
+        let (epe,epe_std_error)=math::math::mean_and_standard_error(&path_epe);
+        profile.epe=epe;
+        profile.epe_std_error=epe_std_error;
+        profile.effective_epe=(0..num_dates).map(|d| weights[d]*profile.effective_ee[d]).sum();
+        profiles.push(profile);
+    }
+    return profiles;
+}
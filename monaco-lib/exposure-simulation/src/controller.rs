@@ -1,24 +1,44 @@
 use data_cube::data_cube::Cube;
 use model::model::Model;
 use model::live_model::LiveModel;
+use model::live_model::InnerSimulator;
 use std::collections::HashMap;
 use logger::Logger;
 
 use instrument::instrument::Instrument;
+use instrument::lsm::LsmRegression;
+use instrument::lsm::LsmBounds;
 
 pub fn create_raw_cube(models:&Vec<Box<dyn Model>>,dates:Vec<f64>,num_paths:usize, correlation_matrix:&Vec<f64>) -> Cube
+{
+    let num_of_variables=get_number_of_variables(&models);
+    let normal_variates=math::math::simulate_normal_variates(num_of_variables, num_paths*dates.len(), &correlation_matrix);
+    return make_raw_cube(&models, normal_variates, dates, num_paths);
+}
+
+/// Same as 'create_raw_cube', but the variates are generated from a seeded random number generator
+pub fn create_raw_cube_with_seed(models:&Vec<Box<dyn Model>>,dates:Vec<f64>,num_paths:usize, correlation_matrix:&Vec<f64>, seed:u64) -> Cube
+{
+    let num_of_variables=get_number_of_variables(&models);
+    let normal_variates=math::math::simulate_normal_variates_with_seed(num_of_variables, num_paths*dates.len(), &correlation_matrix, seed);
+    return make_raw_cube(&models, normal_variates, dates, num_paths);
+}
+
+fn get_number_of_variables(models:&Vec<Box<dyn Model>>) -> usize
 {
     let mut num_of_variables:usize=0;
     for i in 0..models.len()
     {
         num_of_variables+=models[i].get_number_of_variables();
     }
+    return num_of_variables;
+}
 
-    let normal_variates=math::math::simulate_normal_variates(num_of_variables, num_paths*dates.len(), &correlation_matrix);
-
-    let mut ret_cube=Cube::make_cube(normal_variates,dates.clone(),num_paths,num_of_variables);
+fn make_raw_cube(models:&Vec<Box<dyn Model>>,normal_variates:Vec<f64>,dates:Vec<f64>,num_paths:usize) -> Cube
+{
+    let mut ret_cube=Cube::make_cube(normal_variates,dates,num_paths,get_number_of_variables(&models));
 
-    num_of_variables=0;
+    let mut num_of_variables:usize=0;
     for i in 0..models.len()
     {
         for s in num_of_variables..num_of_variables+models[i].get_number_of_variables()
@@ -34,13 +54,11 @@ pub fn create_raw_cube(models:&Vec<Box<dyn Model>>,dates:Vec<f64>,num_paths:usiz
 pub fn create_data_cube_from_raw(models:&Vec<Box<dyn Model>>,raw_factors:&Cube, logger:&Logger) -> Cube
 {
     let mut num_of_outputs:usize=0;
-    let mut num_of_variables:usize=0;
 
-    logger.log("Data cube from raw - Tallying variables and outputs...","controller");
+    logger.log("Data cube from raw - Tallying outputs...","controller");
     for i in 0..models.len()
     {
         num_of_outputs+=models[i].get_number_of_outputs();
-        num_of_variables+=models[i].get_number_of_variables();
     }
 
     logger.log("Data cube from raw - Making empty cube...","controller");
@@ -51,7 +69,7 @@ pub fn create_data_cube_from_raw(models:&Vec<Box<dyn Model>>,raw_factors:&Cube,
     logger.log(format!("Data cube from raw - Results cube - Numer series : {0}",ret_cube.num_series).as_str(),"controller");
 
     num_of_outputs=0;
-    num_of_variables=0;
+    let mut num_of_variables:usize=0;
     logger.log("Data cube from raw - Populating factors...","controller"); 
     for i in 0..models.len()
     {
@@ -80,6 +98,24 @@ pub fn compute_paths(models:&Vec<Box<dyn Model>>,time_steps:&Vec<f64>,num_paths:
     //Compute random values for models' variables
     logger.log("compute_exposures - Creating raw cube...","controller");
     let raw_cube=create_raw_cube(&models, time_steps.clone(), num_paths, &correlation_matrix);
+    return compute_paths_from_raw_cube(&models, raw_cube, &logger);
+}
+
+/// Same as 'compute_paths', but the variates are generated from a seeded random number generator
+/// 
+/// # Remarks
+/// 
+/// Used to create path sets that are independent from the pricing paths (e.g. LSM training paths)
+pub fn compute_paths_with_seed(models:&Vec<Box<dyn Model>>,time_steps:&Vec<f64>,num_paths:usize, correlation_matrix:&Vec<f64>, seed:u64, logger:&Logger) -> (Cube,Cube)
+{
+    logger.log(format!("compute_paths_with_seed - Start (seed: {})",seed),"controller");
+    logger.log("compute_paths_with_seed - Creating raw cube...","controller");
+    let raw_cube=create_raw_cube_with_seed(&models, time_steps.clone(), num_paths, &correlation_matrix, seed);
+    return compute_paths_from_raw_cube(&models, raw_cube, &logger);
+}
+
+fn compute_paths_from_raw_cube(models:&Vec<Box<dyn Model>>,raw_cube:Cube, logger:&Logger) -> (Cube,Cube)
+{
 
     logger.log(format!("Raw cube - Num paths    : {0}",raw_cube.num_scenarios).as_str(),"controller");
     logger.log(format!("Raw cube - Num dates    : {0}",raw_cube.dates.len()).as_str(),"controller");
@@ -121,6 +157,81 @@ pub fn create_live_models<'a>(models:&'a Vec<Box<dyn Model>>,paths:&'a Cube,raw_
     return live_models;
 }
 
+/// Nested simulation engine
+/// 
+/// The inner paths use the same models and correlations of the outer simulation.
+/// The inner cubes contain the history of the outer path before the branching date (e.g. for coupon fixings).
+/// The variates are generated from a seed derived from 'seed', the outer scenario, and the branching date (i.e. the inner paths are reproducible).
+pub struct NestedSimulator<'a>
+{
+    pub models:&'a Vec<Box<dyn Model>>,
+    pub correlation_matrix:&'a Vec<f64>,
+    pub seed:u64
+}
+
+impl InnerSimulator for NestedSimulator<'_>
+{
+    fn with_inner_paths(&self,live_models:&HashMap<String,LiveModel>,scenario:usize,dates:&Vec<f64>,num_paths:usize,logger:&Logger,f:&mut dyn FnMut(&HashMap<String,LiveModel>) -> ()) -> ()
+    {
+        let seed=self.seed^((scenario as u64).wrapping_mul(0x9E3779B97F4A7C15))^dates[0].to_bits();
+
+        //The inner paths share the history of the outer path up to the branching date
+        let outer_dates:Vec<f64>=match live_models.values().next()
+        {
+            Some(m)     =>  m.cube.dates.iter().filter(|d| **d<dates[0]).map(|d| *d).collect(),
+            None        =>  Vec::new()
+        };
+        let branch_dt_idx=outer_dates.len();
+        let mut inner_dates=outer_dates;
+        inner_dates.extend(dates.iter());
+
+        let mut raw_cube=create_raw_cube_with_seed(&self.models, inner_dates.clone(), num_paths, &self.correlation_matrix, seed);
+
+        let mut num_of_outputs:usize=0;
+        for i in 0..self.models.len()
+        {
+            num_of_outputs+=self.models[i].get_number_of_outputs();
+        }
+        let mut paths=Cube::make_empty_cube(inner_dates, num_paths, num_of_outputs);
+
+        let mut start:usize=0;
+        let mut raw_start:usize=0;
+        for i in 0..self.models.len()
+        {
+            let outer_model=match live_models.get(&self.models[i].get_name())
+            {
+                Some(m)     =>  m,
+                None        =>  panic!("Nested simulation - model {} not found in the outer simulation!",self.models[i].get_name())
+            };
+            let branch_values=outer_model.get_variable_values(scenario, dates[0], &logger);
+            for s in 0..num_paths
+            {
+                for dt_idx in 0..branch_dt_idx
+                {
+                    for o in 0..self.models[i].get_number_of_outputs()
+                    {
+                        let _=paths.set_item(s, start+o, dt_idx, outer_model.cube.get_item(scenario, outer_model.start+o, dt_idx).unwrap());
+                    }
+                    for v in 0..self.models[i].get_number_of_variables()
+                    {
+                        let _=raw_cube.set_item(s, raw_start+v, dt_idx, outer_model.raw_cube.get_item(scenario, outer_model.raw_start+v, dt_idx).unwrap());
+                    }
+                }
+                for o in 0..branch_values.len()
+                {
+                    let _=paths.set_item(s, start+o, branch_dt_idx, branch_values[o]);
+                }
+            }
+            self.models[i].populate_factors_from(branch_dt_idx, raw_start, &raw_cube, start, &mut paths, &logger);
+            start+=self.models[i].get_number_of_outputs();
+            raw_start+=self.models[i].get_number_of_variables();
+        }
+
+        let inner_live_models=create_live_models(&self.models, &paths, &raw_cube, &logger);
+        f(&inner_live_models);
+    }
+}
+
 // pub fn populate_live_models<'a>(live_models:&'a Vec<LiveModel<'a>>,instruments:&mut Vec<Box<dyn Instrument>>,log:fn(&str)->()) -> ()
 // {
 //     log("compute_exposures - Populating live models...");
@@ -150,11 +261,12 @@ pub fn create_live_models<'a>(models:&'a Vec<Box<dyn Model>>,paths:&'a Cube,raw_
 /// * `time_steps` - List of time steps (years fractions) to be used for the simulation
 /// * `num_paths` - Number of paths to simulate
 /// * `correlation_matrix` - Correlation matrix between the models' variables
+/// * `training_live_models` - Live models on an independent set of paths, used to estimate the LSM regressions (optional)
 /// 
 /// # Remarks
 /// 
 /// The order of correlations between the factors is the same of the variables in the `models` paramters
-pub fn compute_exposures(instruments:&Vec<Box<dyn Instrument>>,mut results_cube:Cube,live_models:&HashMap<String,LiveModel>, logger:&Logger) -> (Cube,Vec<Vec<Vec<(f64,f64)>>>,Vec<Cube>)
+pub fn compute_exposures(instruments:&Vec<Box<dyn Instrument>>,mut results_cube:Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>, logger:&Logger) -> (Cube,Vec<Vec<Vec<(f64,f64)>>>,Vec<Cube>,Vec<Vec<LsmRegression>>)
 { 
     // log("compute_exposures - Making empty results cube...");
     // let results_cube=Cube::make_empty_cube(time_steps.clone(),num_paths,instruments.len());
@@ -165,18 +277,50 @@ pub fn compute_exposures(instruments:&Vec<Box<dyn Instrument>>,mut results_cube:
 
     let mut cashflows:Vec<Vec<Vec<(f64,f64)>>>=Vec::new();
     let mut exercise_cubes:Vec<Cube>=Vec::new();
+    let mut regressions:Vec<Vec<LsmRegression>>=Vec::new();
 
     //Price instruments on paths
     logger.log("compute_exposures - Generating exposures...","controller");
     for i in 0..instruments.len()
     {
         logger.log(&format!("compute_exposures - Computing values for instrument: {} ({}/{})...",instruments[i].get_name(),i,instruments.len())[..],"controller");
-        let (instrument_cashflows,instrument_exercise_cube)=instruments[i].compute_values(i,&mut results_cube,&live_models,logger);
+        let (instrument_cashflows,instrument_exercise_cube,instrument_regressions)=instruments[i].compute_values(i,&mut results_cube,&live_models,training_live_models,logger);
         results_cube.set_time_series_name(i,&instruments[i].get_name());
 
         cashflows.push(instrument_cashflows);
         exercise_cubes.push(instrument_exercise_cube);
+        regressions.push(instrument_regressions);
     }
 
-    return (results_cube,cashflows,exercise_cubes);
-}
\ No newline at end of file
+    return (results_cube,cashflows,exercise_cubes,regressions);
+}
+
+/// Computes the lower and upper (dual) bounds for the instruments with an exercise policy
+/// 
+/// #Arguments
+/// 
+/// * `instruments` - Instruments priced on the simulated paths
+/// * `dates` - Simulation dates
+/// * `live_models` - Live models of the (outer) simulation
+/// * `regressions` - Regressions returned by 'compute_exposures' (one vector for each instrument)
+/// * `simulator` - Nested simulation engine
+/// * `num_outer_paths` - Number of outer paths
+/// * `num_inner_paths` - Number of inner paths for each continuation value estimate
+pub fn compute_bounds(instruments:&Vec<Box<dyn Instrument>>,dates:&Vec<f64>,live_models:&HashMap<String,LiveModel>,regressions:&Vec<Vec<LsmRegression>>,simulator:&dyn InnerSimulator,num_outer_paths:usize,num_inner_paths:usize,logger:&Logger) -> Vec<LsmBounds>
+{
+    let mut bounds:Vec<LsmBounds>=Vec::new();
+    logger.log("compute_bounds - Computing dual bounds...","controller");
+    for i in 0..instruments.len()
+    {
+        if regressions[i].len()>0
+        {
+            logger.log(format!("compute_bounds - Computing bounds for instrument: {} ({}/{})...",instruments[i].get_name(),i,instruments.len()),"controller");
+            match instruments[i].compute_bounds(&dates,&live_models,&regressions[i],simulator,num_outer_paths,num_inner_paths,&logger)
+            {
+                Some(b) =>  bounds.push(b),
+                None    =>  ()
+            }
+        }
+    }
+    return bounds;
+}
@@ -1,4 +1,6 @@
+pub mod analytics;
 pub mod controller;
+pub mod netting;
 
 #[cfg(test)]
 mod tests {
@@ -6,4 +8,146 @@ mod tests {
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn netting_sets()
+    {
+        use crate::netting::{Counterparty,NettingSet,NettingDefinition};
+        use data_cube::data_cube::Cube;
+
+        let mut exposures=Cube::make_cube(vec![10.0,-4.0,7.0,-12.0,3.0,5.0],vec![0.0],2,3);
+        let names=vec!["A".to_string(),"B".to_string(),"C".to_string()];
+        for (i,name) in names.iter().enumerate() { exposures.set_time_series_name(i,name); }
+        let mut netting=NettingDefinition
+        {
+            counterparties:vec![Counterparty { name:"Group".to_string(), parent:String::new() },Counterparty { name:"Sub".to_string(), parent:"Group".to_string() }],
+            netting_sets:vec![NettingSet { name:"NS1".to_string(), counterparty:"Group".to_string(), trades:vec!["A".to_string(),"B".to_string()], csa:None },
+                              NettingSet { name:"NS2".to_string(), counterparty:"Sub".to_string(), trades:vec!["C".to_string()], csa:None }]
+        };
+        assert!(netting.validate(&names).is_ok());
+        let netting_set_cube=netting.compute_netting_set_cube(&exposures);
+        assert_eq!(netting_set_cube.get_item(0,0,0).unwrap(),6.0);
+        assert_eq!(netting_set_cube.get_item(1,0,0).unwrap(),-9.0);
+        let counterparty_cube=netting.compute_counterparty_cube(&netting_set_cube);
+        assert_eq!(counterparty_cube.time_series_names,vec!["Group (positive)","Group (negative)","Sub (positive)","Sub (negative)"]);
+        assert_eq!(counterparty_cube.get_item(0,0,0).unwrap(),13.0);
+        assert_eq!(counterparty_cube.get_item(1,1,0).unwrap(),-9.0);
+        assert_eq!(counterparty_cube.get_item(1,2,0).unwrap(),5.0);
+        netting.counterparties[0].parent="Sub".to_string();
+        assert!(netting.validate(&names).is_err());
+    }
+
+    #[test]
+    fn exposure_profiles()
+    {
+        use data_cube::data_cube::Cube;
+
+        //4 scenarios, dates 0, 0.5 and 2, one series
+        let exposures=Cube::make_cube(vec![1.0,4.0,2.0, -1.0,2.0,-2.0, 3.0,-2.0,6.0, 1.0,0.0,2.0],vec![0.0,0.5,2.0],4,1);
+        let profile=&crate::analytics::compute_profiles(&exposures,&vec![0.75],1.0)[0];
+        assert_eq!(profile.ee,vec![1.25,1.5,2.5]);
+        assert_eq!(profile.ene,vec![-0.25,-0.5,-0.5]);
+        assert_eq!(profile.effective_ee,vec![1.25,1.5,2.5]);
+        assert_eq!(profile.pfe[0].pfe,vec![1.0,2.0,2.0]);
+        assert_eq!((profile.pfe[0].peak_pfe,profile.pfe[0].peak_pfe_date),(2.0,0.5));
+        //Weights 0.5 (step ending at 0.5) and 0.5 (step from 0.5 to the 1 year horizon)
+        assert!((profile.epe-2.0).abs()<1e-12);
+        assert!((profile.effective_epe-2.0).abs()<1e-12);
+    }
+
+    #[test]
+    fn csa_collateral()
+    {
+        let csa=crate::netting::Csa { threshold:10.0, minimum_transfer_amount:5.0, independent_amount:2.0, rounding:1.0, call_frequency:0.0, margin_period_of_risk:0.5 };
+        let dates=vec![0.0,0.5,1.0,1.5,2.0];
+        let values=vec![12.0,30.4,33.0,-20.0,-18.0];
+        //Balances: 0 (transfer 2 below MTA), 20, 20 (transfer 3 below MTA), -10, -10
+        assert_eq!(csa.compute_collateral(&dates,&values).unwrap(),vec![2.0,2.0,22.0,22.0,-8.0]);
+        let mut netting=crate::netting::NettingDefinition { counterparties:Vec::new(), netting_sets:vec![crate::netting::NettingSet { name:"NS".to_string(), counterparty:"C".to_string(), trades:Vec::new(), csa:Some(csa) }] };
+        assert_eq!(netting.add_lookback_dates(&vec![0.0,0.25,1.0]),vec![0.0,0.25,0.5,1.0]);
+        //The lookback date 0.5 of 1 is missing (0.25 is within the margin period of risk of the first date)
+        assert!(netting.check_lookback_dates(&vec![0.0,0.25,0.5,1.0]).is_ok());
+        assert!(netting.check_lookback_dates(&vec![0.0,0.25,1.0]).is_err());
+        assert!(netting.netting_sets[0].csa.as_ref().unwrap().compute_collateral(&vec![0.0,0.25,1.0],&vec![12.0,30.4,33.0]).is_err());
+        //Calls on 0, 1 and 2 only: balances 0, 0, 23, 23, -8
+        netting.netting_sets[0].csa.as_mut().unwrap().call_frequency=1.0;
+        assert_eq!(netting.netting_sets[0].csa.as_ref().unwrap().compute_collateral(&dates,&values).unwrap(),vec![2.0,2.0,2.0,25.0,25.0]);
+    }
+
+    /// Zero bond paying 1 at 'maturity' on a Hw1f short rate (underlying of the dual bounds test)
+    struct ZeroBond
+    {
+        maturity:f64,
+        model_name:String
+    }
+
+    impl instrument::instrument::Exercisable for ZeroBond
+    {
+        fn get_maturity_date(&self) -> f64
+        {
+            return self.maturity;
+        }
+        fn get_models_variables_values(&self,date:f64,live_models:&std::collections::HashMap<String,model::live_model::LiveModel>,logger:&logger::Logger) -> Vec<f64>
+        {
+            let model=&live_models[&self.model_name];
+            return (0..model.cube.num_scenarios).flat_map(|s| model.get_variable_values(s,date,&logger)).collect();
+        }
+        fn get_value(&self,scenario:usize,date:f64,live_models:&std::collections::HashMap<String,model::live_model::LiveModel>,logger:&logger::Logger) -> f64
+        {
+            if date>=self.maturity
+            {
+                return 0.0;
+            }
+            let r=live_models[&self.model_name].get_value(scenario,date,self.maturity-date,&logger).unwrap();
+            return (-r*(self.maturity-date)).exp();
+        }
+        fn get_cashflows(&self,_scenario:usize,min_date:f64,max_date:f64,_live_models:&std::collections::HashMap<String,model::live_model::LiveModel>,_logger:&logger::Logger) -> Vec<(f64,f64)>
+        {
+            return if self.maturity>min_date && self.maturity<=max_date { vec![(self.maturity,1.0)] } else { Vec::new() };
+        }
+    }
+
+    #[test]
+    fn dual_bounds()
+    {
+        use crate::controller;
+        use instrument::callable::Callable;
+        use instrument::instrument::Instrument;
+        use model::model::{Model,OutputInterpolation};
+        use model::hw1f::Hw1f;
+        use data_cube::data_cube::Cube;
+
+        let logger=logger::Logger { log_tags:Vec::new() };
+        let mut hw1f=Hw1f { name:"EUR".to_string(), interpolation:OutputInterpolation::Linear, term_structure:vec![(0.0,0.02),(1.0,0.02),(5.0,0.025)], thetas:Vec::new(), a:vec![(0.0,0.05)], sigmas:vec![(0.0,0.015)], initial_rate:0.02 };
+        hw1f.init();
+        let models:Vec<Box<dyn Model>>=vec![Box::new(hw1f)];
+        let dates=vec![0.0,0.5,1.0,1.5,2.0];
+        let correlation_matrix=vec![1.0];
+
+        //Bermudan call (by the issuer) at 0.95 on a 2 years zero bond, with the regressions estimated on training paths
+        let callable=Callable
+        {
+            name:"Callable zero".to_string(),
+            exposure_discount_model_name:"EUR".to_string(),
+            underlying:ZeroBond { maturity:2.0, model_name:"EUR".to_string() },
+            call_dates:vec![0.5,1.0,1.5],
+            exercise_windows:Vec::new(),
+            side:"issuer".to_string(),
+            exercise_fee:0.95,
+            valuation:"exercise-right".to_string()
+        };
+        let (paths,raw_cube)=controller::compute_paths_with_seed(&models,&dates,500,&correlation_matrix,1,&logger);
+        let live_models=controller::create_live_models(&models,&paths,&raw_cube,&logger);
+        let (training_paths,training_raw_cube)=controller::compute_paths_with_seed(&models,&dates,500,&correlation_matrix,2,&logger);
+        let training_live_models=controller::create_live_models(&models,&training_paths,&training_raw_cube,&logger);
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),500,1);
+        let (_,_,regressions)=callable.compute_values(0,&mut results_cube,&live_models,Some(&training_live_models),&logger);
+
+        let simulator=controller::NestedSimulator { models:&models, correlation_matrix:&correlation_matrix, seed:3 };
+        let bounds=callable.compute_bounds(&dates,&live_models,&regressions,&simulator,200,50,&logger).unwrap();
+        assert!(bounds.lower_bound>0.0);
+        //The bounds are Monte Carlo estimates: lower<=upper within three standard errors of the gap
+        assert!(bounds.lower_bound<=bounds.upper_bound+3.0*bounds.duality_gap_std_error);
+        assert!((bounds.duality_gap-(bounds.upper_bound-bounds.lower_bound)).abs()<1e-12);
+    }
 }
@@ -0,0 +1,363 @@
+/// Netting sets and counterparty hierarchy
+
+use data_cube::data_cube::Cube;
+use serde::{Serialize, Deserialize};
+
+/// Counterparty (the netting sets of a counterparty are also aggregated in its parent, if any)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Counterparty
+{
+    pub name:String,
+    #[serde(default)]
+    pub parent:String
+}
+
+/// Credit support annex: variation margin terms of a netting set
+///
+/// # Remarks
+///
+/// Amounts are in the currency of the exposures: positive collateral is held by us, negative collateral is posted by us.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Csa
+{
+    /// Exposure (in absolute value) below which no collateral is called
+    #[serde(default)]
+    pub threshold:f64,
+    #[serde(default)]
+    pub minimum_transfer_amount:f64,
+    /// Amount posted by the counterparty (negative if posted by us) independently of the exposure
+    #[serde(default)]
+    pub independent_amount:f64,
+    /// Transfers are rounded to the nearest multiple of 'rounding' (no rounding if 0)
+    #[serde(default)]
+    pub rounding:f64,
+    /// Time between margin calls (year fraction, 0 for a call on every simulation date)
+    #[serde(default)]
+    pub call_frequency:f64,
+    /// Time between the last margin call honoured by a defaulting counterparty and the close-out (year fraction)
+    #[serde(default)]
+    pub margin_period_of_risk:f64
+}
+
+/// Set of trades with a counterparty whose values are netted
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NettingSet
+{
+    pub name:String,
+    pub counterparty:String,
+    pub trades:Vec<String>,
+    #[serde(default)]
+    pub csa:Option<Csa>
+}
+
+/// Tolerance used to compare simulation dates
+const DATE_TOLERANCE:f64=1e-9;
+
+impl Csa
+{
+    pub fn validate(&self) -> Result<(),String>
+    {
+        let amounts=[("threshold",self.threshold),("minimum_transfer_amount",self.minimum_transfer_amount),("rounding",self.rounding),("call_frequency",self.call_frequency),("margin_period_of_risk",self.margin_period_of_risk)];
+        for (name,value) in amounts.iter()
+        {
+            if *value<0.0
+            {
+                return Err(format!("Invalid CSA {} ({}): it must not be negative",name,value));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Collateral balance after the margin calls (one value per date, on a single scenario)
+    ///
+    /// # Remarks
+    ///
+    /// The balance is 0 before the first call (on the first date); a call happens on a date if 'call_frequency' has elapsed since the previous one.
+    /// The credit support amount is the part of the value beyond the threshold; the transfer to reach it is made only if it's at least the minimum transfer amount.
+    fn get_balances(&self,dates:&Vec<f64>,values:&Vec<f64>) -> Vec<f64>
+    {
+        let mut balances:Vec<f64>=Vec::new();
+        let mut balance:f64=0.0;
+        let mut last_call:Option<f64>=None;
+        for d in 0..dates.len()
+        {
+            if last_call.map_or(true,|c| dates[d]-c>=self.call_frequency-DATE_TOLERANCE)
+            {
+                let credit_support_amount=if values[d]>self.threshold { values[d]-self.threshold } else if values[d]< -self.threshold { values[d]+self.threshold } else { 0.0 };
+                let mut transfer=credit_support_amount-balance;
+                if self.rounding>0.0
+                {
+                    transfer=(transfer/self.rounding).round()*self.rounding;
+                }
+                if transfer!=0.0 && transfer.abs()>=self.minimum_transfer_amount
+                {
+                    balance+=transfer;
+                }
+                last_call=Some(dates[d]);
+            }
+            balances.push(balance);
+        }
+        return balances;
+    }
+
+    /// Collateral held on each date (one value per date, on a single scenario): the balance after the last call on or before the date minus the margin period of risk, plus the independent amount
+    ///
+    /// # Remarks
+    ///
+    /// Dates within the margin period of risk of the first date use the balance of the first date.
+    /// The other lookback dates must be simulation dates.
+    pub fn compute_collateral(&self,dates:&Vec<f64>,values:&Vec<f64>) -> Result<Vec<f64>,String>
+    {
+        let balances=self.get_balances(dates,values);
+        let mut collateral:Vec<f64>=Vec::new();
+        for d in 0..dates.len()
+        {
+            let lookback=self.get_lookback_index(dates,d)?;
+            collateral.push(balances[lookback]+self.independent_amount);
+        }
+        return Ok(collateral);
+    }
+
+    /// Index of the margin period of risk lookback date of 'dates[d]' (0 when the lookback date is not positive)
+    fn get_lookback_index(&self,dates:&Vec<f64>,d:usize) -> Result<usize,String>
+    {
+        let lookback=dates[d]-self.margin_period_of_risk;
+        if lookback<=0.0
+        {
+            return Ok(0);
+        }
+        return match dates.iter().position(|t| (t-lookback).abs()<=DATE_TOLERANCE)
+        {
+            Some(i)     =>  Ok(i),
+            None        =>  Err(format!("Lookback date {} of date {} is not a simulation date",lookback,dates[d]))
+        };
+    }
+}
+
+/// Netting sets and counterparties of a portfolio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NettingDefinition
+{
+    #[serde(default)]
+    pub counterparties:Vec<Counterparty>,
+    pub netting_sets:Vec<NettingSet>
+}
+
+impl NettingDefinition
+{
+    /// Checks the definition against the names of the trades (the series of the exposures cube)
+    pub fn validate(&self,trade_names:&Vec<String>) -> Result<(),String>
+    {
+        let mut netted_trades:Vec<&String>=Vec::new();
+        for netting_set in self.netting_sets.iter()
+        {
+            if self.netting_sets.iter().filter(|n| n.name==netting_set.name).count()>1
+            {
+                return Err(format!("Netting set {} is defined more than once",&netting_set.name));
+            }
+            for trade in netting_set.trades.iter()
+            {
+                if !trade_names.contains(trade)
+                {
+                    return Err(format!("Netting set {} refers to non-existent trade ({})",&netting_set.name,trade));
+                }
+                if netted_trades.contains(&trade)
+                {
+                    return Err(format!("Trade {} belongs to more than one netting set",trade));
+                }
+                netted_trades.push(trade);
+            }
+        }
+        self.validate_csas()?;
+        for counterparty in self.counterparties.iter()
+        {
+            if !counterparty.parent.is_empty() && !self.counterparties.iter().any(|c| c.name==counterparty.parent)
+            {
+                return Err(format!("Counterparty {} refers to non-existent parent ({})",&counterparty.name,&counterparty.parent));
+            }
+            let mut ancestor=counterparty.parent.clone();
+            for _i in 0..self.counterparties.len()
+            {
+                if ancestor==counterparty.name
+                {
+                    return Err(format!("Counterparty {} is its own ancestor",&counterparty.name));
+                }
+                ancestor=self.get_parent(&ancestor);
+            }
+        }
+        return Ok(());
+    }
+
+    /// Checks the CSAs of the netting sets
+    pub fn validate_csas(&self) -> Result<(),String>
+    {
+        for netting_set in self.netting_sets.iter()
+        {
+            if let Some(csa)=&netting_set.csa
+            {
+                if let Err(e)=csa.validate()
+                {
+                    return Err(format!("Netting set {}: {}",&netting_set.name,e));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Adds to the simulation dates the margin period of risk lookback dates of the CSAs (positive dates only)
+    pub fn add_lookback_dates(&self,dates:&Vec<f64>) -> Vec<f64>
+    {
+        let mut all_dates=dates.clone();
+        for csa in self.netting_sets.iter().filter_map(|n| n.csa.as_ref()).filter(|c| c.margin_period_of_risk>0.0)
+        {
+            for t in dates.iter()
+            {
+                let lookback=t-csa.margin_period_of_risk;
+                if lookback>0.0 && !all_dates.iter().any(|d| (d-lookback).abs()<=DATE_TOLERANCE)
+                {
+                    all_dates.push(lookback);
+                }
+            }
+        }
+        all_dates.sort_by(|a,b| a.partial_cmp(b).unwrap());
+        return all_dates;
+    }
+
+    /// Checks that the simulation dates contain the margin period of risk lookback dates of the CSAs (positive dates only)
+    pub fn check_lookback_dates(&self,dates:&Vec<f64>) -> Result<(),String>
+    {
+        for netting_set in self.netting_sets.iter()
+        {
+            if let Some(csa)=&netting_set.csa
+            {
+                for d in 0..dates.len()
+                {
+                    if let Err(e)=csa.get_lookback_index(dates,d)
+                    {
+                        return Err(format!("Netting set {}: {}",&netting_set.name,e));
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn get_parent(&self,counterparty:&String) -> String
+    {
+        return match self.counterparties.iter().find(|c| c.name==*counterparty)
+        {
+            Some(c)     =>  c.parent.clone(),
+            None        =>  String::new()
+        };
+    }
+
+    /// Names of all the counterparties (the listed ones, then the ones only referred to by the netting sets)
+    pub fn get_counterparty_names(&self) -> Vec<String>
+    {
+        let mut names:Vec<String>=self.counterparties.iter().map(|c| c.name.clone()).collect();
+        for netting_set in self.netting_sets.iter()
+        {
+            if !names.contains(&netting_set.counterparty)
+            {
+                names.push(netting_set.counterparty.clone());
+            }
+        }
+        return names;
+    }
+
+    /// Whether the netting set belongs to 'counterparty' or to one of its descendants
+    fn is_under(&self,netting_set:&NettingSet,counterparty:&String) -> bool
+    {
+        let mut c=netting_set.counterparty.clone();
+        while !c.is_empty()
+        {
+            if c==*counterparty
+            {
+                return true;
+            }
+            c=self.get_parent(&c);
+        }
+        return false;
+    }
+
+    /// Netted values: one series for each netting set (sum of the values of its trades)
+    pub fn compute_netting_set_cube(&self,exposures:&Cube) -> Cube
+    {
+        let mut cube=Cube::make_empty_cube(exposures.dates.clone(),exposures.num_scenarios,self.netting_sets.len());
+        for (n,netting_set) in self.netting_sets.iter().enumerate()
+        {
+            cube.set_time_series_name(n,&netting_set.name);
+            let series:Vec<usize>=netting_set.trades.iter().map(|t| exposures.time_series_names.iter().position(|name| name==t).unwrap()).collect();
+            for s in 0..exposures.num_scenarios
+            {
+                for t in 0..exposures.dates.len()
+                {
+                    let value:f64=series.iter().map(|i| exposures.get_item(s,*i,t).unwrap()).sum();
+                    cube.set_item(s,n,t,value).unwrap();
+                }
+            }
+        }
+        return cube;
+    }
+
+    /// Collateral held and collateralised values of the netting sets with a CSA: two cubes with one series for each of them
+    ///
+    /// # Remarks
+    ///
+    /// The collateralised value is the netted value minus the collateral held.
+    /// Fails when the dates of the cube do not contain the margin period of risk lookback dates.
+    pub fn compute_collateral_cubes(&self,netting_set_cube:&Cube) -> Result<(Cube,Cube),String>
+    {
+        let collateralised:Vec<usize>=(0..self.netting_sets.len()).filter(|n| self.netting_sets[*n].csa.is_some()).collect();
+        let mut collateral_cube=Cube::make_empty_cube(netting_set_cube.dates.clone(),netting_set_cube.num_scenarios,collateralised.len());
+        let mut collateralised_cube=Cube::make_empty_cube(netting_set_cube.dates.clone(),netting_set_cube.num_scenarios,collateralised.len());
+        for (c,n) in collateralised.iter().enumerate()
+        {
+            let csa=self.netting_sets[*n].csa.as_ref().unwrap();
+            collateral_cube.set_time_series_name(c,&self.netting_sets[*n].name);
+            collateralised_cube.set_time_series_name(c,&self.netting_sets[*n].name);
+            for s in 0..netting_set_cube.num_scenarios
+            {
+                let values:Vec<f64>=(0..netting_set_cube.dates.len()).map(|t| netting_set_cube.get_item(s,*n,t).unwrap()).collect();
+                let collateral=match csa.compute_collateral(&netting_set_cube.dates,&values)
+                {
+                    Ok(c)       =>  c,
+                    Err(e)      =>  return Err(format!("Netting set {}: {}",&self.netting_sets[*n].name,e))
+                };
+                for t in 0..netting_set_cube.dates.len()
+                {
+                    collateral_cube.set_item(s,c,t,collateral[t]).unwrap();
+                    collateralised_cube.set_item(s,c,t,values[t]-collateral[t]).unwrap();
+                }
+            }
+        }
+        return Ok((collateral_cube,collateralised_cube));
+    }
+
+    /// Counterparty exposures: two series for each counterparty, the sum of the positive and the sum of the negative values of its netting sets (and of the netting sets of its descendants)
+    ///
+    /// # Remarks
+    ///
+    /// The values of different netting sets are not netted: the series are named 'Counterparty (positive)' and 'Counterparty (negative)'.
+    pub fn compute_counterparty_cube(&self,netting_set_cube:&Cube) -> Cube
+    {
+        let names=self.get_counterparty_names();
+        let mut cube=Cube::make_empty_cube(netting_set_cube.dates.clone(),netting_set_cube.num_scenarios,2*names.len());
+        for (c,name) in names.iter().enumerate()
+        {
+            cube.set_time_series_name(2*c,&format!("{} (positive)",name));
+            cube.set_time_series_name(2*c+1,&format!("{} (negative)",name));
+            let netting_sets:Vec<usize>=(0..self.netting_sets.len()).filter(|n| self.is_under(&self.netting_sets[*n],name)).collect();
+            for s in 0..netting_set_cube.num_scenarios
+            {
+                for t in 0..netting_set_cube.dates.len()
+                {
+                    let values:Vec<f64>=netting_sets.iter().map(|n| netting_set_cube.get_item(s,*n,t).unwrap()).collect();
+                    cube.set_item(s,2*c,t,values.iter().map(|v| v.max(0.0)).sum()).unwrap();
+                    cube.set_item(s,2*c+1,t,values.iter().map(|v| v.min(0.0)).sum()).unwrap();
+                }
+            }
+        }
+        return cube;
+    }
+}
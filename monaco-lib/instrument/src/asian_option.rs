@@ -0,0 +1,123 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::path_dependent::{PathData,compute_path_dependent_values};
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+/// Asian option implementation
+/// (fixed strike option on the arithmetic or geometric average of a Black underlying over a set of averaging dates, cash settled on the expiry date)
+#[derive(Serialize, Deserialize)]
+pub struct AsianOption
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub call_or_put:String,
+    pub average_type:String,
+    pub notional:f64,
+    pub strike:f64,
+    pub averaging_dates:Vec<f64>,
+    pub expiry_date:f64,
+    pub underlying_model_name:String,
+    pub discount_model_name:String,
+    pub fx_model_name:String
+}
+
+impl AsianOption
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    fn get_omega(&self) -> f64
+    {
+        return match self.call_or_put.as_str()
+        {
+            "call"      =>  1.0,
+            "put"       =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid option type ({}): it must be 'call' or 'put'!",&self.name,&self.call_or_put)
+        };
+    }
+
+    fn is_geometric(&self) -> bool
+    {
+        return match self.average_type.as_str()
+        {
+            "geometric"     =>  true,
+            "arithmetic"    =>  false,
+            _               =>  panic!("Instrument {} has an invalid average type ({}): it must be 'arithmetic' or 'geometric'!",&self.name,&self.average_type)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.average_type!="arithmetic" && self.average_type!="geometric"
+        {
+            return Err(format!("Asian option {} has an invalid average type ({}): it must be 'arithmetic' or 'geometric'!",&self.name,&self.average_type));
+        }
+        if self.averaging_dates.is_empty()
+        {
+            return Err(format!("Asian option {} must have at least one averaging date!",&self.name));
+        }
+        if self.averaging_dates.iter().any(|d| *d>self.expiry_date)
+        {
+            return Err(format!("Asian option {} has averaging dates after the expiry date!",&self.name));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Payoff on the expiry date of a scenario; the state is the running average of the fixings up to each averaging date
+    pub(crate) fn get_path(&self,scenario:usize,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> PathData
+    {
+        let underlying_model=self.get_live_model(live_models,&self.underlying_model_name);
+        let is_geometric=self.is_geometric();
+        let mut states:Vec<(f64,Vec<f64>)>=Vec::new();
+        let mut total:f64=0.0;
+        for (i,averaging_date) in self.averaging_dates.iter().enumerate()
+        {
+            let fixing=underlying_model.get_value(scenario,*averaging_date,0.0,&logger).unwrap();
+            total+=if is_geometric { fixing.ln() } else { fixing };
+            let average=total/((i+1) as f64);
+            states.push((*averaging_date,vec![if is_geometric { average.exp() } else { average }]));
+        }
+
+        let average=states.last().unwrap().1[0];
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,self.expiry_date,0.0,&logger).unwrap();
+        let payoff=self.notional*(self.get_omega()*(average-self.strike)).max(0.0)*fx;
+        return PathData { cashflows:vec![(self.expiry_date,self.get_sign()*payoff)], states:states, exercise_values:Vec::new(), end_date:self.expiry_date };
+    }
+}
+
+impl Instrument for AsianOption
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        logger.log(format!("asian-option|compute_values -> name: {}, starting regression...",self.name),"instrument");
+        let f_path=|scenario:usize,live_models:&HashMap<String,LiveModel>| self.get_path(scenario,&live_models,&logger);
+        let f_variables=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| self.get_live_model(live_models,&self.underlying_model_name).get_variable_values(scenario,date,&logger);
+        return compute_path_dependent_values(start,result_cube,self.expiry_date,&Vec::new(),1.0,&live_models,training_live_models,&self.discount_model_name,&f_path,&f_variables,&logger);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::path_dependent::{PathData,compute_path_dependent_values};
+use model::live_model::LiveModel;
+use model::black::Black;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_brownian_bridge() -> bool
+{
+    return true;
+}
+
+fn default_bridge_seed() -> u64
+{
+    return 1;
+}
+
+/// Barrier option implementation
+/// (European option on 'notional' units of a Black underlying that is activated or cancelled when the underlying crosses a barrier)
+#[derive(Serialize, Deserialize)]
+pub struct BarrierOption
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub call_or_put:String,
+    pub knock_type:String,
+    pub notional:f64,
+    pub strike:f64,
+    #[serde(default)]
+    pub lower_barrier:Option<f64>,
+    #[serde(default)]
+    pub upper_barrier:Option<f64>,
+    pub expiry_date:f64,
+    pub underlying_model_name:String,
+    pub discount_model_name:String,
+    pub fx_model_name:String,
+    #[serde(default="default_brownian_bridge")]
+    pub brownian_bridge:bool,
+    #[serde(default="default_bridge_seed")]
+    pub bridge_seed:u64
+}
+
+impl BarrierOption
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    fn get_omega(&self) -> f64
+    {
+        return match self.call_or_put.as_str()
+        {
+            "call"      =>  1.0,
+            "put"       =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid option type ({}): it must be 'call' or 'put'!",&self.name,&self.call_or_put)
+        };
+    }
+
+    fn is_knock_out(&self) -> bool
+    {
+        return match self.knock_type.as_str()
+        {
+            "out"       =>  true,
+            "in"        =>  false,
+            _           =>  panic!("Instrument {} has an invalid knock type ({}): it must be 'in' or 'out'!",&self.name,&self.knock_type)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.knock_type!="in" && self.knock_type!="out"
+        {
+            return Err(format!("Barrier option {} has an invalid knock type ({}): it must be 'in' or 'out'!",&self.name,&self.knock_type));
+        }
+        if let (Some(lower),Some(upper))=(self.lower_barrier,self.upper_barrier)
+        {
+            if lower>=upper
+            {
+                return Err(format!("Barrier option {} has a lower barrier ({}) that is not below the upper barrier ({})!",&self.name,lower,upper));
+            }
+        }
+        else if self.lower_barrier.is_none() && self.upper_barrier.is_none()
+        {
+            return Err(format!("Barrier option {} must have a lower barrier, an upper barrier, or both!",&self.name));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Returns the Black model of the underlying (used for the volatility of the Brownian bridge)
+    fn get_black_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>) -> &'a Black
+    {
+        return match self.get_live_model(live_models,&self.underlying_model_name).model.as_any().downcast_ref::<Black>()
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {}: the model {} must be a Black model!",&self.name,&self.underlying_model_name)
+        };
+    }
+
+    /// Whether the underlying is on or beyond a barrier
+    fn is_beyond_barrier(&self,spot:f64) -> bool
+    {
+        return self.lower_barrier.map_or(false,|b| spot<=b) || self.upper_barrier.map_or(false,|b| spot>=b);
+    }
+
+    /// Probability that the underlying crosses a barrier between two monitoring dates, given the values on those dates (Brownian bridge on the log of the underlying)
+    pub fn get_crossing_probability(&self,start_spot:f64,end_spot:f64,variance:f64) -> f64
+    {
+        if variance<=0.0
+        {
+            return 0.0;
+        }
+        let mut survival:f64=1.0;
+        if let Some(b)=self.upper_barrier
+        {
+            survival*=1.0-(-2.0*(b/start_spot).ln()*(b/end_spot).ln()/variance).exp();
+        }
+        if let Some(b)=self.lower_barrier
+        {
+            survival*=1.0-(-2.0*(start_spot/b).ln()*(end_spot/b).ln()/variance).exp();
+        }
+        return 1.0-survival;
+    }
+
+    /// Payoff on the expiry date and barrier flag of a scenario; the state is 1 from the monitoring date on which the barrier is crossed
+    ///
+    /// # Remarks
+    ///
+    /// The barrier is monitored on 'monitoring_dates' and, when 'brownian_bridge' is set, between them: the crossing is drawn (from 'uniforms') with the Brownian bridge probability.
+    fn get_path(&self,scenario:usize,monitoring_dates:&Vec<f64>,uniforms:&Vec<Vec<f64>>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> PathData
+    {
+        let underlying_model=self.get_live_model(live_models,&self.underlying_model_name);
+        let black=self.get_black_model(live_models);
+        let mut knock_date:Option<f64>=None;
+        let mut prev_spot=underlying_model.get_value(scenario,monitoring_dates[0],0.0,&logger).unwrap();
+        if self.is_beyond_barrier(prev_spot)
+        {
+            knock_date=Some(monitoring_dates[0]);
+        }
+        for t in 1..monitoring_dates.len()
+        {
+            if knock_date.is_some()
+            {
+                break;
+            }
+            let spot=underlying_model.get_value(scenario,monitoring_dates[t],0.0,&logger).unwrap();
+            let crossed=self.is_beyond_barrier(spot) || (self.brownian_bridge && uniforms[scenario][t-1]<self.get_crossing_probability(prev_spot,spot,black.get_variance(monitoring_dates[t-1],monitoring_dates[t])));
+            if crossed
+            {
+                knock_date=Some(monitoring_dates[t]);
+            }
+            prev_spot=spot;
+        }
+
+        let is_active=knock_date.is_some()!=self.is_knock_out();
+        let spot=underlying_model.get_value(scenario,self.expiry_date,0.0,&logger).unwrap();
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,self.expiry_date,0.0,&logger).unwrap();
+        let payoff=if is_active { self.notional*(self.get_omega()*(spot-self.strike)).max(0.0)*fx } else { 0.0 };
+
+        //A knocked-out option is worth 0 from the knock date
+        let end_date=match knock_date
+        {
+            Some(d) if self.is_knock_out()  =>  d,
+            _                               =>  self.expiry_date
+        };
+        let states=match knock_date
+        {
+            Some(d)     =>  vec![(d,vec![1.0])],
+            None        =>  Vec::new()
+        };
+        return PathData { cashflows:vec![(self.expiry_date,self.get_sign()*payoff)], states:states, exercise_values:Vec::new(), end_date:end_date };
+    }
+}
+
+impl Instrument for BarrierOption
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let mut monitoring_dates:Vec<f64>=result_cube.dates.iter().filter(|d| **d<self.expiry_date).map(|d| *d).collect();
+        monitoring_dates.push(self.expiry_date);
+
+        //The same uniform draws are used for the pricing and the training paths
+        let num_draws=match training_live_models
+        {
+            Some(training_models)   =>  result_cube.num_scenarios.max(self.get_live_model(training_models,&self.underlying_model_name).cube.num_scenarios),
+            None                    =>  result_cube.num_scenarios
+        };
+        let num_steps=monitoring_dates.len()-1;
+        let mut uniforms:Vec<Vec<f64>>=vec![Vec::new();num_draws];
+        if num_steps>0
+        {
+            let mut identity:Vec<f64>=vec![0.0;num_steps*num_steps];
+            for i in 0..num_steps { identity[i*num_steps+i]=1.0; }
+            let normal_variates=math::math::simulate_normal_variates_with_seed(num_steps, num_draws, &identity, self.bridge_seed);
+            for s in 0..num_draws
+            {
+                uniforms[s]=(0..num_steps).map(|t| math::math::normal_cdf(normal_variates[t*num_draws+s])).collect();
+            }
+        }
+
+        logger.log(format!("barrier-option|compute_values -> name: {}, starting regression...",self.name),"instrument");
+        let f_path=|scenario:usize,live_models:&HashMap<String,LiveModel>| self.get_path(scenario,&monitoring_dates,&uniforms,&live_models,&logger);
+        let f_variables=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| self.get_live_model(live_models,&self.underlying_model_name).get_variable_values(scenario,date,&logger);
+        return compute_path_dependent_values(start,result_cube,self.expiry_date,&Vec::new(),1.0,&live_models,training_live_models,&self.discount_model_name,&f_path,&f_variables,&logger);
+    }
+}
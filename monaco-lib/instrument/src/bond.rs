@@ -0,0 +1,325 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+use crate::vanilla_swap::{find_fixing,add_fixings};
+use calendar::date::Date;
+use calendar::day_count::{validate_day_count,get_accrual_periods};
+use calendar::holiday_calendar::{HolidayCalendar,get_calendar,validate_business_day_convention};
+use calendar::schedule::{ScheduleDefinition,SchedulePeriod};
+
+/// Bond implementation
+/// (fixed, floating or zero-coupon bond with a redemption schedule, discounted with an issuer credit spread)
+#[derive(Serialize, Deserialize)]
+pub struct Bond
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub coupon_type:String,
+    pub notional:f64,
+    #[serde(default)]
+    pub coupon_rate:f64,
+    #[serde(default)]
+    pub payment_dates:Vec<f64>,
+    #[serde(default)]
+    pub redemptions:Vec<(f64,f64)>,
+    pub discount_model_name:String,
+    #[serde(default)]
+    pub projection_model_name:String,
+    #[serde(default)]
+    pub index_name:String,
+    #[serde(default)]
+    pub fixings:Vec<(f64,f64)>,
+    pub fx_model_name:String,
+    #[serde(default)]
+    pub spread_curve:Vec<(f64,f64)>,
+    #[serde(default)]
+    pub day_count:String,
+    #[serde(default)]
+    pub calendar:String,
+    #[serde(default="default_business_day_convention")]
+    pub business_day_convention:String,
+    #[serde(default)]
+    pub schedule:Option<ScheduleDefinition>,
+    #[serde(skip)]
+    accrual_periods:Vec<f64>,
+    #[serde(skip)]
+    schedule_periods:Vec<SchedulePeriod>
+}
+
+fn default_business_day_convention() -> String
+{
+    return "modified-following".to_string();
+}
+
+impl Bond
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payment_dates.len()<2 && self.coupon_type!="zero"
+        {
+            return Err(format!("Bond {} must have a start date and at least one payment date!",&self.name));
+        }
+        if self.payment_dates.is_empty()
+        {
+            return Err(format!("Bond {} has no payment dates!",&self.name));
+        }
+        match self.coupon_type.as_str()
+        {
+            "fixed"|"zero"  =>  (),
+            "floating"      =>  if self.projection_model_name.is_empty() { return Err(format!("Bond {} is floating but has no projection model!",&self.name)); },
+            _               =>  return Err(format!("Bond {} has an invalid coupon type ({}): it must be 'fixed', 'floating' or 'zero'!",&self.name,&self.coupon_type))
+        };
+        if self.coupon_type=="floating"
+        {
+            for t in (1..self.payment_dates.len()).filter(|t| self.payment_dates[*t]>0.0)
+            {
+                if let Err(m)=find_fixing(&self.fixings,self.get_index_name(),self.payment_dates[t-1])
+                {
+                    return Err(format!("Bond {}: {}!",&self.name,m));
+                }
+            }
+        }
+        let redeemed:f64=self.get_redemptions().iter().map(|r| r.1).sum();
+        if (redeemed-self.notional).abs()>0.000001*self.notional.abs().max(1.0)
+        {
+            return Err(format!("Bond {} redeems {} for a notional of {}!",&self.name,redeemed,self.notional));
+        }
+        return Ok(());
+    }
+
+    /// Name of the index of the floating rate ('index_name' or, if not set, the projection model name)
+    pub fn get_index_name(&self) -> &String
+    {
+        if self.index_name.is_empty()
+        {
+            return &self.projection_model_name;
+        }
+        return &self.index_name;
+    }
+
+    /// Adds the fixings of 'fixing_history' (index name -> list of (date,rate)) to a floating bond (the fixings defined in the bond take precedence)
+    pub fn apply_fixing_history(&mut self,fixing_history:&HashMap<String,Vec<(f64,f64)>>) -> ()
+    {
+        if self.coupon_type!="floating"
+        {
+            return;
+        }
+        if let Some(history)=fixing_history.get(self.get_index_name())
+        {
+            add_fixings(&mut self.fixings,history);
+        }
+    }
+
+    /// Generates the payment dates from 'schedule' (or adjusts them), adjusts the redemption dates to the business days of 'calendar', and computes the accrual periods with 'day_count' (when they are set)
+    pub fn apply_conventions(&mut self,valuation_date:&Option<Date>,calendars:&HashMap<String,HolidayCalendar>) -> Result<(),String>
+    {
+        if self.calendar.is_empty() && self.day_count.is_empty() && self.schedule.is_none()
+        {
+            return Ok(());
+        }
+        let valuation_date=match valuation_date
+        {
+            Some(d)     =>  d,
+            None        =>  return Err(format!("Bond {}: a valuation date is required for schedules, calendars and day count conventions!",&self.name))
+        };
+        let name=self.name.clone();
+        let with_name=|m:String| format!("Bond {}: {}!",name,m);
+        let calendar=match self.calendar.is_empty()
+        {
+            true    =>  None,
+            false   =>  {
+                            validate_business_day_convention(&self.business_day_convention).map_err(with_name)?;
+                            Some(get_calendar(&self.calendar,calendars).map_err(with_name)?)
+                        }
+        };
+        if let Some(schedule)=&self.schedule
+        {
+            if !self.payment_dates.is_empty() || schedule.fixing_lag!=0 || schedule.payment_lag!=0
+            {
+                return Err(with_name("a bond with a schedule can not have explicit payment dates, and its schedule can not have lags".to_string()));
+            }
+            let periods=schedule.generate(valuation_date,calendar.as_ref(),&self.business_day_convention).map_err(with_name)?;
+            self.payment_dates=vec![periods[0].start_date.to_time(valuation_date)];
+            self.payment_dates.extend(periods.iter().map(|p| p.end_date.to_time(valuation_date)));
+            self.schedule_periods=periods;
+        }
+        else if let Some(c)=&calendar
+        {
+            self.payment_dates=c.adjust_times(&self.payment_dates,valuation_date,&self.business_day_convention);
+        }
+        if let Some(c)=&calendar
+        {
+            let redemption_dates=c.adjust_times(&self.redemptions.iter().map(|r| r.0).collect(),valuation_date,&self.business_day_convention);
+            for (r,date) in redemption_dates.iter().enumerate()
+            {
+                self.redemptions[r].0=*date;
+            }
+        }
+        if !self.day_count.is_empty()
+        {
+            validate_day_count(&self.day_count).map_err(with_name)?;
+            self.accrual_periods=get_accrual_periods(&self.payment_dates,valuation_date,&self.day_count);
+        }
+        for p in 0..self.schedule_periods.len()
+        {
+            self.schedule_periods[p].accrual_period=if self.accrual_periods.is_empty() { self.payment_dates[p+1]-self.payment_dates[p] } else { self.accrual_periods[p+1] };
+        }
+        return Ok(());
+    }
+
+    /// Periods generated from 'schedule' (empty if the payment dates are explicit)
+    pub fn get_schedule_periods(&self) -> &Vec<SchedulePeriod>
+    {
+        return &self.schedule_periods;
+    }
+
+    /// Redemption schedule (the whole notional is redeemed on the last payment date if no schedule is given)
+    fn get_redemptions(&self) -> Vec<(f64,f64)>
+    {
+        if self.redemptions.is_empty()
+        {
+            return vec![(*self.payment_dates.last().unwrap(),self.notional)];
+        }
+        return self.redemptions.clone();
+    }
+
+    /// Notional outstanding over the period starting on 'date'
+    fn get_outstanding_notional(&self,date:f64) -> f64
+    {
+        return self.notional-self.get_redemptions().iter().filter(|r| r.0<=date).map(|r| r.1).sum::<f64>();
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Coupon (in the bond ccy) paid on 'payment_dates[t]', as known on 'date' (projected if the period has not reset yet, from the fixings if it reset on or before the evaluation date 0)
+    fn get_coupon(&self,t:usize,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let cpn_start=self.payment_dates[t-1];
+        let cpn_end=self.payment_dates[t];
+        let cpn_period=cpn_end-cpn_start;
+        let rate=match self.coupon_type.as_str()
+        {
+            "zero"      =>  0.0,
+            "floating"  =>  {
+                                let projection_model=self.get_live_model(live_models,&self.projection_model_name);
+                                let fwd=if cpn_start>date
+                                {
+                                    model::live_model::get_fwd_rate(projection_model, scenario, date, cpn_start, cpn_end,&logger)
+                                }
+                                else
+                                {
+                                    match find_fixing(&self.fixings,self.get_index_name(),cpn_start)
+                                    {
+                                        Ok(Some(r)) =>  r,
+                                        Ok(None)    =>  projection_model.get_value(scenario,cpn_start.max(0.0),cpn_period,&logger).unwrap(),
+                                        Err(m)      =>  panic!("Bond {}: {}!",&self.name,m)
+                                    }
+                                };
+                                fwd+self.coupon_rate
+                            },
+            _           =>  self.coupon_rate
+        };
+        let accrual_period=if self.accrual_periods.is_empty() { cpn_period } else { self.accrual_periods[t] };
+        return rate*accrual_period*self.get_outstanding_notional(cpn_start);
+    }
+
+    /// Coupon and redemption payments (date,amount) in the bond ccy, as known on 'date'
+    fn get_payments(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>
+    {
+        let mut payments:Vec<(f64,f64)>=Vec::new();
+        for t in 1..self.payment_dates.len()
+        {
+            if self.payment_dates[t]>date
+            {
+                let coupon=self.get_coupon(t,scenario,date,&live_models,&logger);
+                if coupon!=0.0 { payments.push((self.payment_dates[t],coupon)); }
+            }
+        }
+        for redemption in self.get_redemptions()
+        {
+            if redemption.0>date { payments.push(redemption); }
+        }
+        return payments;
+    }
+
+    /// Value at 'date' (in the base ccy) of the payments after 'date', discounted with the discount model plus the issuer spread
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let discount_model=self.get_live_model(live_models,&self.discount_model_name);
+        let mut v:f64=0.0;
+        for (payment_date,amount) in self.get_payments(scenario,date,&live_models,&logger)
+        {
+            let term=payment_date-date;
+            let spread=if self.spread_curve.is_empty() { 0.0 } else { math::math::interpolate(&self.spread_curve,term) };
+            let r=discount_model.get_value(scenario,date,term,&logger).unwrap();
+            v+=amount*(-(r+spread)*term).exp();
+        }
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+        return self.get_sign()*v*fx;
+    }
+}
+
+impl Instrument for Bond
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let sign=self.get_sign();
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            //Realised coupons and redemptions
+            let mut scenario_cashflows:Vec<(f64,f64)>=Vec::new();
+            for t in 1..self.payment_dates.len()
+            {
+                let coupon=self.get_coupon(t,s,f64::INFINITY,&live_models,&logger);
+                if coupon!=0.0 { scenario_cashflows.push((self.payment_dates[t],coupon)); }
+            }
+            for redemption in self.get_redemptions()
+            {
+                scenario_cashflows.push(redemption);
+            }
+            let mut scenario_cashflows:Vec<(f64,f64)>=scenario_cashflows.iter().map(|cf| (cf.0,sign*cf.1*fx_model.get_value(s,cf.0,0.0,&logger).unwrap())).collect();
+            scenario_cashflows.sort_by(|a, b| a.0.partial_cmp(&(b.0)).unwrap());
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=self.get_value(s,date,&live_models,&logger);
+                logger.log(format!("bond|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
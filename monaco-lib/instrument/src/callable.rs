@@ -0,0 +1,458 @@
+//use macros::debug;
+use crate::instrument::Instrument;
+use crate::instrument::Exercisable;
+use crate::lsm::*;
+use model::live_model::LiveModel;
+use model::live_model::InnerSimulator;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+
+fn default_exercise_frequency() -> f64
+{
+    return 1.0/252.0;
+}
+
+fn default_side() -> String
+{
+    return "holder".to_string();
+}
+
+fn default_valuation() -> String
+{
+    return "reversed-underlying".to_string();
+}
+
+/// Exercise window: the instrument can be called on any date between 'start_date' and 'end_date'
+/// (the dates are approximated by a grid with step 'exercise_frequency')
+///
+/// The call must be notified 'notice_period' before the call date, so the exercise decision is taken on the notice date
+/// (the cashflows up to the call date are still exchanged)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExerciseWindow
+{
+    pub start_date:f64,
+    pub end_date:f64,
+    #[serde(default)]
+    pub notice_period:f64,
+    #[serde(default="default_exercise_frequency")]
+    pub exercise_frequency:f64
+}
+
+impl ExerciseWindow
+{
+    /// Checks the consistency of the window
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.exercise_frequency<=0.0
+        {
+            return Err(format!("Exercise window ({}-{}) has a non-positive exercise frequency ({})!",self.start_date,self.end_date,self.exercise_frequency));
+        }
+        if self.end_date<self.start_date
+        {
+            return Err(format!("Exercise window ends ({}) before it starts ({})!",self.end_date,self.start_date));
+        }
+        return Ok(());
+    }
+
+    /// Returns the exercise (notice) dates of the window
+    pub fn get_exercise_dates(&self) -> Vec<f64>
+    {
+        if self.exercise_frequency<=0.0
+        {
+            panic!("Exercise window ({}-{}) has a non-positive exercise frequency ({})!",self.start_date,self.end_date,self.exercise_frequency);
+        }
+        if self.end_date<self.start_date
+        {
+            panic!("Exercise window ends ({}) before it starts ({})!",self.end_date,self.start_date);
+        }
+        //The step is adjusted so that both the start and the end dates are on the grid
+        let num_steps=((self.end_date-self.start_date)/self.exercise_frequency).ceil() as usize;
+        if num_steps==0
+        {
+            return vec![self.start_date-self.notice_period];
+        }
+        let step=(self.end_date-self.start_date)/(num_steps as f64);
+        return (0..num_steps+1).map(|i| self.start_date+(i as f64)*step-self.notice_period).collect();
+    }
+}
+
+/// Callable instrument implementation
+/// (exercisable underlying + call dates and/or exercise windows)
+///
+/// Exercising terminates the underlying on the call date: the cashflows after it are cancelled and the exercising party pays 'exercise_fee' to the other party on it.
+/// The right to exercise belongs to the holder of the position ('side' is "holder") or to the counterparty ('side' is "issuer").
+///
+/// With the "exercise-right" valuation the position is the underlying plus (holder) or minus (issuer) the exercise right.
+/// The "reversed-underlying" valuation (default) keeps the original callable swap valuation: the LSM runs on the negated cashflows of the underlying, with -V as exercise value.
+#[derive(Serialize, Deserialize)]
+pub struct Callable<I:Exercisable>
+{
+    pub name: String,
+    pub exposure_discount_model_name:String,
+    pub underlying:I,
+    #[serde(default)]
+    pub call_dates:Vec<f64>,
+    #[serde(default)]
+    pub exercise_windows:Vec<ExerciseWindow>,
+    #[serde(default="default_side")]
+    pub side:String,
+    #[serde(default)]
+    pub exercise_fee:f64,
+    #[serde(default="default_valuation")]
+    pub valuation:String
+}
+
+impl<I:Exercisable> Callable<I>
+{
+    /// Checks the side and the exercise windows (the underlying is checked separately)
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.side!="holder" && self.side!="issuer"
+        {
+            return Err(format!("Callable {} has an invalid side ({}): it must be 'holder' or 'issuer'!",&self.name,&self.side));
+        }
+        if self.valuation!="reversed-underlying" && self.valuation!="exercise-right"
+        {
+            return Err(format!("Callable {} has an invalid valuation ({}): it must be 'reversed-underlying' or 'exercise-right'!",&self.name,&self.valuation));
+        }
+        if self.valuation=="reversed-underlying" && (self.side!="holder" || self.exercise_fee!=0.0)
+        {
+            return Err(format!("Callable {}: the side and the exercise fee require the 'exercise-right' valuation!",&self.name));
+        }
+        for window in self.exercise_windows.iter()
+        {
+            if let Err(e)=window.validate()
+            {
+                return Err(format!("Callable {}: {}",&self.name,e));
+            }
+        }
+        return Ok(());
+    }
+
+    ///Add a 'rich' (date,callable_flag,termination_date) date to an vec of rich dates
+    /// If a date is already present, then its callability flag and termination date are replaced
+    fn add_rich_date(rich_dates:&mut Vec<(bool,f64,f64)>,is_call_date:bool,date:f64,termination_date:f64) -> ()
+    {
+        let tolerance:f64=0.00001;
+        for rd in 0..rich_dates.len()
+        {
+            if (rich_dates[rd].1-date).abs()<tolerance
+            {
+                rich_dates[rd].0=is_call_date;
+                rich_dates[rd].2=termination_date;
+                return;
+            }
+        }
+        rich_dates.push((is_call_date,date,termination_date));
+    }
+
+    /// Returns the dates used for the LSM calculation as (is_call_date,date,termination_date) tuples
+    /// The dates are the union of the dates in the result cube (up to maturity) + call dates + exercise window grids + maturity date
+    /// 
+    /// The termination date is the call date of an exercise (notice) date: it is the date itself, unless the date belongs to a window with a notice period
+    fn get_rich_dates(&self,result_dates:&Vec<f64>,logger:&Logger) -> Vec<(bool,f64,f64)>
+    {
+        let date_threshold=0.001;
+
+        let maturity_date=self.underlying.get_maturity_date();
+        logger.log(format!("callable|compute_values -> maturity: {}",maturity_date),"instrument");
+        //Create instrument values cube: the dates are the union of the dates in the result cube + call dates+ maturity date
+        //A rich date is a tuple (is_call_date,date,termination_date)
+        let mut rich_dates:Vec<(bool,f64,f64)>=Vec::new();
+        for dt_idx in 0..result_dates.len()
+        {
+            if (maturity_date-result_dates[dt_idx])>date_threshold
+            {
+                rich_dates.push((false,result_dates[dt_idx],result_dates[dt_idx]));
+            }
+        }
+        //The maturity date does not affect callability (i.e. the option value is always 0 at maturity)
+        Callable::<I>::add_rich_date(&mut rich_dates,false,maturity_date,maturity_date);
+
+        //Add call dates
+        for call_dt_idx in 0..self.call_dates.len()
+        {
+            Callable::<I>::add_rich_date(&mut rich_dates,true,self.call_dates[call_dt_idx],self.call_dates[call_dt_idx]);
+        }
+
+        //Add the exercise grids of the windows (only the dates between the start and the maturity are used)
+        for window in self.exercise_windows.iter()
+        {
+            let exercise_dates=window.get_exercise_dates();
+            logger.log(format!("callable|compute_values -> exercise window: {}-{} (notice: {}), exercise dates: {}",window.start_date,window.end_date,window.notice_period,exercise_dates.len()),"instrument");
+            for exercise_date in exercise_dates
+            {
+                if exercise_date>=0.0 && (maturity_date-exercise_date)>date_threshold
+                {
+                    Callable::<I>::add_rich_date(&mut rich_dates,true,exercise_date,exercise_date+window.notice_period);
+                }
+            }
+        }
+
+        rich_dates.sort_by(|a, b| a.1.partial_cmp(&(b.1)).unwrap());
+
+        for d in 0..rich_dates.len()
+        {
+            logger.log(format!("callable|compute_values -> rich_dates ({}): {}/{}/{}",d,rich_dates[d].0,rich_dates[d].1,rich_dates[d].2),"instrument");
+        }
+        return rich_dates;
+    }
+
+    /// Returns 1 if the holder of the position owns the exercise right, -1 if the issuer does
+    /// Whether the position is valued as the underlying plus or minus the exercise right (rather than with the original reversed underlying valuation)
+    fn is_exercise_right_valuation(&self) -> bool
+    {
+        return match self.valuation.as_str()
+        {
+            "exercise-right"        =>  true,
+            "reversed-underlying"   =>  false,
+            _                       =>  panic!("Instrument {} has an invalid valuation ({}): it must be 'reversed-underlying' or 'exercise-right'!",&self.name,&self.valuation)
+        };
+    }
+
+    fn get_side_sign(&self) -> f64
+    {
+        return match self.side.as_str()
+        {
+            "holder"    =>  1.0,
+            "issuer"    =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'holder' or 'issuer'!",&self.name,&self.side)
+        };
+    }
+
+    fn get_discount_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(&self.exposure_discount_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.exposure_discount_model_name)
+        };
+    }
+
+    /// Discount factor from 'end_date' to 'date'
+    fn get_discount_factor(&self,scenario:usize,date:f64,end_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        if end_date<=date
+        {
+            return 1.0;
+        }
+        let r=self.get_discount_model(live_models).get_value(scenario,date,end_date-date,&logger).unwrap();
+        return (-r*(end_date-date)).exp();
+    }
+
+    /// Value at 'date' of the cashflows of the underlying in (date,end_date]
+    fn get_cashflows_value(&self,scenario:usize,date:f64,end_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let mut v:f64=0.0;
+        for (t,cf) in self.underlying.get_cashflows(scenario,date,end_date,&live_models,&logger).iter()
+        {
+            v+=cf*self.get_discount_factor(scenario,date,*t,&live_models,&logger);
+        }
+        return v;
+    }
+
+    /// Value of exercising on 'date' for the party that owns the exercise right (the underlying terminates on 'termination_date')
+    ///
+    /// # Remarks
+    ///
+    /// The cashflows up to the termination date are still exchanged: only the cashflows after it are given up. The fee is paid on the termination date.
+    fn get_exercise_value(&self,scenario:usize,date:f64,termination_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let given_up=self.underlying.get_value(scenario,date,&live_models,&logger)-self.get_cashflows_value(scenario,date,termination_date,&live_models,&logger);
+        return -self.get_side_sign()*given_up-self.exercise_fee*self.get_discount_factor(scenario,date,termination_date,&live_models,&logger);
+    }
+
+    /// Exercise value used by the LSM: the value of exercising the right, or -V for the reversed underlying valuation
+    fn get_lsm_exercise_value(&self,is_exercise_right:bool,scenario:usize,date:f64,rich_dates:&Vec<(bool,f64,f64)>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        if is_exercise_right
+        {
+            return self.get_exercise_value(scenario,date,get_termination_date(&rich_dates,date),&live_models,&logger);
+        }
+        return -self.underlying.get_value(scenario,date,&live_models,&logger);
+    }
+
+    /// Cashflows used by the LSM: none for the exercise right, the negated cashflows of the underlying for the reversed underlying valuation
+    fn get_lsm_cashflows(&self,is_exercise_right:bool,scenario:usize,min_date:f64,max_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>
+    {
+        if is_exercise_right
+        {
+            return Vec::new();
+        }
+        return self.underlying.get_cashflows(scenario,min_date,max_date,&live_models,&logger).iter().map(|cf| (cf.0,-cf.1)).collect();
+    }
+}
+
+/// Termination date of an exercise date (the rich dates are sorted by date)
+fn get_termination_date(rich_dates:&Vec<(bool,f64,f64)>,date:f64) -> f64
+{
+    let tolerance:f64=0.00001;
+    let idx=rich_dates.partition_point(|d| d.1<date-tolerance);
+    return match rich_dates.get(idx)
+    {
+        Some(d) if (d.1-date).abs()<tolerance   =>  d.2,
+        _                                       =>  date
+    };
+}
+
+impl<I:Exercisable> Instrument for Callable<I>
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let exp_dsc_model=match live_models.get(&self.exposure_discount_model_name)
+        {
+            Some(leg)   =>  leg,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.exposure_discount_model_name)
+        };
+        let side_sign=self.get_side_sign();
+
+        let rich_dates=self.get_rich_dates(&result_cube.dates,&logger);
+        let dates:Vec<f64>=rich_dates.iter().map(|d| d.1).collect();
+        let exercise_flags=rich_dates.iter().map(|d| d.0).collect();
+
+        //The LSM values the exercise right alone (it has no cashflows before exercise), or the reversed underlying
+        let is_exercise_right=self.is_exercise_right_valuation();
+        let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| self.underlying.get_models_variables_values(date, &live_models,&logger);
+        let mut f_exercise_value=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| self.get_lsm_exercise_value(is_exercise_right,scenario,date,&rich_dates,&live_models,&logger);
+        let mut f_cashflows=|scenario:usize,min_date:f64,max_date:f64,live_models:&HashMap<String,LiveModel>| self.get_lsm_cashflows(is_exercise_right,scenario,min_date,max_date,&live_models,&logger);
+
+        //Two-pass LSM: the regressions are estimated on the training paths and then applied to the pricing paths
+        let training_regressions=match training_live_models
+        {
+            Some(training_models)   =>  {
+                                            let training_dsc_model=match training_models.get(&self.exposure_discount_model_name)
+                                            {
+                                                Some(m)     =>  m,
+                                                None        =>  panic!("Instrument {} refers to non-existent training model ({})!",&self.name,&self.exposure_discount_model_name)
+                                            };
+                                            let mut training_values_cube:Cube=Cube::make_empty_cube(dates.clone(), training_dsc_model.cube.num_scenarios, 1);
+                                            logger.log("callable|compute_values -> Starting lsm (training paths)...","instrument");
+                                            let (_,_,regressions)=compute_lsm_values(
+                                                &mut training_values_cube,
+                                                &training_models,
+                                                &exercise_flags,
+                                                &mut f_models_variables_values,
+                                                &mut f_exercise_value,
+                                                &mut f_cashflows,
+                                                &training_dsc_model,
+                                                None,
+                                                &logger
+                                            );
+                                            Some(regressions)
+                                        },
+            None                    =>  None
+        };
+
+        let mut instrument_values_cube:Cube=Cube::make_empty_cube(dates, result_cube.num_scenarios, 1);
+
+        logger.log("callable|compute_values -> Starting lsm...","instrument");
+        let (lsm_cashflows,exercise_cube,regressions)=compute_lsm_values(
+            &mut instrument_values_cube,
+            &live_models,
+            &exercise_flags,
+            &mut f_models_variables_values,
+            &mut f_exercise_value,
+            &mut f_cashflows,
+            &exp_dsc_model,
+            training_regressions.as_ref(),
+            &logger
+        );
+        logger.log("callable|compute_values -> lsm done.","instrument");
+
+        if !is_exercise_right
+        {
+            for s in 0..result_cube.num_scenarios
+            {
+                for dt_idx in 0..result_cube.dates.len()
+                {
+                    let v=instrument_values_cube.get_item_interp(s, 0, result_cube.dates[dt_idx], true).unwrap().2;
+                    let _=result_cube.set_item(s, start, dt_idx, v);
+                }
+            }
+            return (lsm_cashflows,exercise_cube,regressions);
+        }
+
+        //The position is the underlying plus (holder) or minus (issuer) the exercise right, up to the exercise (notice) date
+        //Between the exercise date and the termination (call) date the position is worth the remaining cashflows up to the termination date and the fee
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            let mut exercise_date:Option<f64>=None;
+            for dt_idx in 0..exercise_cube.dates.len()
+            {
+                if exercise_cube.get_item(s, 0, dt_idx).unwrap()>0.0
+                {
+                    exercise_date=Some(exercise_cube.dates[dt_idx]);
+                    break;
+                }
+            }
+
+            let termination_date=exercise_date.map(|ex_dt| get_termination_date(&rich_dates,ex_dt));
+
+            let mut scenario_cashflows=match termination_date
+            {
+                Some(term_dt)   =>  {
+                                        let mut cf=self.underlying.get_cashflows(s, 0.0, term_dt, &live_models, &logger);
+                                        if self.exercise_fee!=0.0 { cf.push((term_dt,-side_sign*self.exercise_fee)); }
+                                        cf
+                                    },
+                None            =>  self.underlying.get_cashflows(s, 0.0, self.underlying.get_maturity_date(), &live_models, &logger)
+            };
+            scenario_cashflows.sort_by(|a, b| a.0.partial_cmp(&(b.0)).unwrap());
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=match (exercise_date,termination_date)
+                {
+                    (Some(ex_dt),Some(term_dt)) if date>ex_dt && date>=term_dt  =>  0.0,
+                    (Some(ex_dt),Some(term_dt)) if date>ex_dt                   =>  {
+                                                                                        self.get_cashflows_value(s,date,term_dt,&live_models,&logger)-side_sign*self.exercise_fee*self.get_discount_factor(s,date,term_dt,&live_models,&logger)
+                                                                                    },
+                    _                                                           =>  {
+                                                                                        let option_value=instrument_values_cube.get_item_interp(s, 0, date, true).unwrap().2;
+                                                                                        self.underlying.get_value(s,date,&live_models,&logger)+side_sign*option_value
+                                                                                    }
+                };
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+        return (cashflows,exercise_cube,regressions);
+    }
+
+    fn compute_bounds(&self,dates:&Vec<f64>,live_models:&HashMap<String,LiveModel>,regressions:&Vec<LsmRegression>,simulator:&dyn InnerSimulator,num_outer_paths:usize,num_inner_paths:usize,logger:&Logger) -> Option<LsmBounds>
+    {
+        let rich_dates=self.get_rich_dates(&dates,&logger);
+        let dates:Vec<f64>=rich_dates.iter().map(|d| d.1).collect();
+        let exercise_flags=rich_dates.iter().map(|d| d.0).collect();
+        let is_exercise_right=self.is_exercise_right_valuation();
+
+        logger.log("callable|compute_bounds -> Starting dual bounds calculation...","instrument");
+        let bounds=compute_dual_bounds(
+            &self.name,
+            &dates,
+            &exercise_flags,
+            &regressions,
+            &live_models,
+            simulator,
+            num_outer_paths,
+            num_inner_paths,
+            &mut |date, live_models| self.underlying.get_models_variables_values(date, &live_models,&logger),
+            &mut |scenario,date,live_models| self.get_lsm_exercise_value(is_exercise_right,scenario,date,&rich_dates,&live_models,&logger),
+            &mut |scenario,min_date,max_date,live_models| self.get_lsm_cashflows(is_exercise_right,scenario,min_date,max_date,&live_models,&logger),
+            &self.exposure_discount_model_name,
+            &logger
+        );
+        logger.log("callable|compute_bounds -> dual bounds done.","instrument");
+        return Some(bounds);
+    }
+}
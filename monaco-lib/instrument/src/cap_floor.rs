@@ -0,0 +1,210 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::vanilla_swap::{find_fixing,add_fixings};
+use model::live_model::LiveModel;
+use model::hw1f::Hw1f;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+/// Cap/floor implementation
+/// (strip of caplets/floorlets on the simple rate of each period)
+#[derive(Serialize, Deserialize)]
+pub struct CapFloor
+{
+    pub name: String,
+    pub cap_or_floor:String,
+    pub buy_or_sell:String,
+    pub notional:f64,
+    pub strike:f64,
+    pub discount_model_name:String,
+    pub projection_model_name:String,
+    pub fx_model_name:String,
+    pub payment_dates:Vec<f64>,
+    #[serde(default)]
+    pub index_name:String,
+    #[serde(default)]
+    pub fixings:Vec<(f64,f64)>
+}
+
+impl CapFloor
+{
+    fn is_cap(&self) -> bool
+    {
+        return match self.cap_or_floor.as_str()
+        {
+            "cap"       =>  true,
+            "floor"     =>  false,
+            _           =>  panic!("Instrument {} has an invalid type ({}): it must be 'cap' or 'floor'!",&self.name,&self.cap_or_floor)
+        };
+    }
+
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    /// Checks the consistency of the definition (a single rate model for the discounting and the projection, and a fixing for the periods that reset before the evaluation date 0 and are paid after it)
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payment_dates.len()<2
+        {
+            return Err(format!("Cap/floor {} must have a start date and at least one payment date!",&self.name));
+        }
+        if self.cap_or_floor!="cap" && self.cap_or_floor!="floor"
+        {
+            return Err(format!("Cap/floor {} has an invalid type ({}): it must be 'cap' or 'floor'!",&self.name,&self.cap_or_floor));
+        }
+        if self.buy_or_sell!="buy" && self.buy_or_sell!="sell"
+        {
+            return Err(format!("Cap/floor {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell));
+        }
+        if self.discount_model_name!=self.projection_model_name
+        {
+            return Err(format!("Cap/floor {}: the discount model ({}) and the projection model ({}) must be the same (the closed form uses a single Hw1f model)!",&self.name,&self.discount_model_name,&self.projection_model_name));
+        }
+        for t in (1..self.payment_dates.len()).filter(|t| self.payment_dates[*t]>0.0)
+        {
+            if let Err(m)=find_fixing(&self.fixings,self.get_index_name(),self.payment_dates[t-1])
+            {
+                return Err(format!("Cap/floor {}: {}!",&self.name,m));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Name of the index of the floating rate ('index_name' or, if not set, the projection model name)
+    pub fn get_index_name(&self) -> &String
+    {
+        if self.index_name.is_empty()
+        {
+            return &self.projection_model_name;
+        }
+        return &self.index_name;
+    }
+
+    /// Adds the fixings of 'fixing_history' (index name -> list of (date,rate)) to the cap/floor (the fixings defined in the cap/floor take precedence)
+    pub fn apply_fixing_history(&mut self,fixing_history:&HashMap<String,Vec<(f64,f64)>>) -> ()
+    {
+        if let Some(history)=fixing_history.get(self.get_index_name())
+        {
+            add_fixings(&mut self.fixings,history);
+        }
+    }
+
+    /// Returns the (discount/projection,fx) live models and the Hw1f model used for the closed form
+    fn get_live_models<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>) -> (&'a LiveModel<'a>,&'a LiveModel<'a>,&'a Hw1f)
+    {
+        if self.discount_model_name!=self.projection_model_name
+        {
+            panic!("Instrument {}: the discount model ({}) and the projection model ({}) must be the same!",&self.name,&self.discount_model_name,&self.projection_model_name);
+        }
+        let rate_model=match live_models.get(&self.discount_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.discount_model_name)
+        };
+        let fx_model=match live_models.get(&self.fx_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.fx_model_name)
+        };
+        let hw1f=match rate_model.model.as_any().downcast_ref::<Hw1f>()
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {}: the model {} must be a Hw1f model!",&self.name,&self.discount_model_name)
+        };
+        return (rate_model,fx_model,hw1f);
+    }
+
+    /// Realised payoff (per unit of notional, in the instrument ccy) of the caplet/floorlet paid on 'payment_dates[t]' (the simple rate of a period that reset on or before the evaluation date 0 is its fixing)
+    fn get_realised_payoff(&self,t:usize,scenario:usize,rate_model:&LiveModel,logger:&Logger) -> f64
+    {
+        let reset=self.payment_dates[t-1];
+        let period=self.payment_dates[t]-reset;
+        let rate=match find_fixing(&self.fixings,self.get_index_name(),reset)
+        {
+            Ok(Some(r)) =>  r,
+            Ok(None)    =>  {
+                                let zero_rate=rate_model.get_value(scenario,reset.max(0.0),period,&logger).unwrap();
+                                ((zero_rate*period).exp()-1.0)/period
+                            },
+            Err(m)      =>  panic!("Cap/floor {}: {}!",&self.name,m)
+        };
+        return if self.is_cap() { period*(rate-self.strike).max(0.0) } else { period*(self.strike-rate).max(0.0) };
+    }
+
+    /// Value at 'date' of the caplets/floorlets paid after 'date'
+    fn get_value(&self,scenario:usize,date:f64,rate_model:&LiveModel,fx_model:&LiveModel,hw1f:&Hw1f,logger:&Logger) -> f64
+    {
+        let is_cap=self.is_cap();
+        let r=rate_model.get_variable_values(scenario,date,&logger)[0];
+        let mut v:f64=0.0;
+        for t in 1..self.payment_dates.len()
+        {
+            let payment=self.payment_dates[t];
+            if payment<=date
+            {
+                continue;
+            }
+            let reset=self.payment_dates[t-1];
+            if reset>date
+            {
+                //Caplet = (1+K*tau) zero bond puts, floorlet = (1+K*tau) zero bond calls
+                let factor=1.0+self.strike*(payment-reset);
+                v+=factor*hw1f.zero_bond_option(!is_cap,date,reset,payment,1.0/factor,r);
+            }
+            else
+            {
+                //The rate is already fixed
+                let df=(-rate_model.get_value(scenario,date,payment-date,&logger).unwrap()*(payment-date)).exp();
+                v+=self.get_realised_payoff(t,scenario,rate_model,&logger)*df;
+            }
+        }
+        let fx=fx_model.get_value(scenario,date,0.0,&logger).unwrap();
+        return self.get_sign()*self.notional*v*fx;
+    }
+}
+
+impl Instrument for CapFloor
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let (rate_model,fx_model,hw1f)=self.get_live_models(&live_models);
+        let sign=self.get_sign();
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            let mut scenario_cashflows:Vec<(f64,f64)>=Vec::new();
+            for t in (1..self.payment_dates.len()).filter(|t| self.payment_dates[*t]>0.0)
+            {
+                let fx=fx_model.get_value(s,self.payment_dates[t],0.0,&logger).unwrap();
+                let payoff=sign*self.notional*self.get_realised_payoff(t,s,rate_model,&logger)*fx;
+                scenario_cashflows.push((self.payment_dates[t],payoff));
+            }
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=self.get_value(s,date,rate_model,fx_model,hw1f,&logger);
+                logger.log(format!("cap-floor|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
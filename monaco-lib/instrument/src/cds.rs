@@ -0,0 +1,251 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_accrual_on_default() -> bool
+{
+    return true;
+}
+
+fn default_hazard_step() -> f64
+{
+    return 1.0/12.0;
+}
+
+/// Credit default swap implementation
+/// (the default intensity is simulated by a rate model: its zero rates are the average intensities and its discount factors the survival probabilities)
+#[derive(Serialize, Deserialize)]
+pub struct Cds
+{
+    pub name: String,
+    pub buyer_or_seller:String,
+    pub notional:f64,
+    pub spread:f64,
+    pub recovery_rate:f64,
+    #[serde(default="default_accrual_on_default")]
+    pub accrual_on_default:bool,
+    pub payment_dates:Vec<f64>,
+    pub discount_model_name:String,
+    pub intensity_model_name:String,
+    pub fx_model_name:String,
+    #[serde(default="default_hazard_step")]
+    pub hazard_step:f64,
+    pub default_seed:u64
+}
+
+impl Cds
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buyer_or_seller.as_str()
+        {
+            "buyer"     =>  1.0,
+            "seller"    =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buyer' or 'seller'!",&self.name,&self.buyer_or_seller)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payment_dates.len()<2
+        {
+            return Err(format!("Cds {} must have a protection start date and at least one premium payment date!",&self.name));
+        }
+        if self.hazard_step<=0.0
+        {
+            return Err(format!("Cds {} has an invalid hazard step ({})!",&self.name,self.hazard_step));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Splits [start,end] in steps of (at most) 'hazard_step'
+    fn get_steps(&self,start:f64,end:f64) -> Vec<(f64,f64)>
+    {
+        if self.hazard_step<=0.0
+        {
+            panic!("Instrument {} has an invalid hazard step ({})!",&self.name,self.hazard_step);
+        }
+        let tolerance:f64=0.0000001;
+        let mut steps:Vec<(f64,f64)>=Vec::new();
+        let mut step_start=start;
+        while step_start<end-tolerance
+        {
+            let step_end=(step_start+self.hazard_step).min(end);
+            steps.push((step_start,step_end));
+            step_start=step_end;
+        }
+        return steps;
+    }
+
+    /// Default date of each scenario (infinity if the reference entity survives the last payment date)
+    ///
+    /// # Remarks
+    ///
+    /// The default happens when the intensity integrated along the path (from the first payment date, or from 0 if it is in the past) exceeds an exponential variate drawn from 'default_seed':
+    /// the defaults are conditional on the survival up to 0.
+    fn get_default_dates(&self,num_scenarios:usize,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<f64>
+    {
+        let intensity_model=self.get_live_model(live_models,&self.intensity_model_name);
+        let normal_variates=math::math::simulate_normal_variates_with_seed(1, num_scenarios, &vec![1.0], self.default_seed);
+        let steps=self.get_steps(self.payment_dates[0].max(0.0),*self.payment_dates.last().unwrap());
+
+        let mut default_dates:Vec<f64>=vec![f64::INFINITY;num_scenarios];
+        for s in 0..num_scenarios
+        {
+            let threshold=-(1.0-math::math::normal_cdf(normal_variates[s])).ln();
+            let mut hazard:f64=0.0;
+            for (step_start,step_end) in steps.iter()
+            {
+                let step=step_end-step_start;
+                let intensity=intensity_model.get_value(s,*step_start,step,&logger).unwrap();
+                if intensity>0.0 && hazard+intensity*step>=threshold
+                {
+                    default_dates[s]=step_start+(threshold-hazard)/intensity;
+                    break;
+                }
+                hazard+=intensity*step;
+            }
+            logger.log(format!("cds|get_default_dates -> name: {}, s: {}, threshold: {}, default date: {}",self.name,s,threshold,default_dates[s]),"instrument");
+        }
+        return default_dates;
+    }
+
+    /// Premium and default cashflows (in the base ccy) of a scenario given its default date (the cashflows on or before 0 are already paid and left out)
+    fn get_leg_cashflows(&self,scenario:usize,default_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> (Vec<(f64,f64)>,Vec<(f64,f64)>)
+    {
+        let sign=self.get_sign();
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let mut premium_cashflows:Vec<(f64,f64)>=Vec::new();
+        let mut default_cashflows:Vec<(f64,f64)>=Vec::new();
+        for t in 1..self.payment_dates.len()
+        {
+            let period_start=self.payment_dates[t-1];
+            let period_end=self.payment_dates[t];
+            if period_end<=0.0
+            {
+                continue;
+            }
+            if period_end<default_date
+            {
+                let fx=fx_model.get_value(scenario,period_end,0.0,&logger).unwrap();
+                premium_cashflows.push((period_end,-sign*self.notional*self.spread*(period_end-period_start)*fx));
+            }
+            else if period_start<default_date
+            {
+                let fx=fx_model.get_value(scenario,default_date,0.0,&logger).unwrap();
+                if self.accrual_on_default
+                {
+                    premium_cashflows.push((default_date,-sign*self.notional*self.spread*(default_date-period_start)*fx));
+                }
+                default_cashflows.push((default_date,sign*self.notional*(1.0-self.recovery_rate)*fx));
+            }
+        }
+        return (premium_cashflows,default_cashflows);
+    }
+
+    /// Value at 'date' (in the base ccy) conditional on survival up to 'date'
+    ///
+    /// # Remarks
+    ///
+    /// The protection leg and the accrual on default are integrated over steps of 'hazard_step' (the default is assumed to happen in the middle of a step).
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let discount_model=self.get_live_model(live_models,&self.discount_model_name);
+        let intensity_model=self.get_live_model(live_models,&self.intensity_model_name);
+        let df=|u:f64| if u<=date { 1.0 } else { (-discount_model.get_value(scenario,date,u-date,&logger).unwrap()*(u-date)).exp() };
+        let survival=|u:f64| if u<=date { 1.0 } else { (-intensity_model.get_value(scenario,date,u-date,&logger).unwrap()*(u-date)).exp() };
+
+        let mut premium_leg:f64=0.0;
+        let mut protection_leg:f64=0.0;
+        for t in 1..self.payment_dates.len()
+        {
+            let period_start=self.payment_dates[t-1];
+            let period_end=self.payment_dates[t];
+            if period_end<=date
+            {
+                continue;
+            }
+            let mut prev_survival=survival(period_start.max(date));
+            for (step_start,step_end) in self.get_steps(period_start.max(date),period_end).iter()
+            {
+                let step_mid=0.5*(step_start+step_end);
+                let step_survival=survival(*step_end);
+                let step_df=df(step_mid);
+                protection_leg+=step_df*(prev_survival-step_survival);
+                if self.accrual_on_default
+                {
+                    premium_leg+=(step_mid-period_start)*step_df*(prev_survival-step_survival);
+                }
+                prev_survival=step_survival;
+            }
+            premium_leg+=(period_end-period_start)*df(period_end)*prev_survival;
+        }
+        let v=self.notional*((1.0-self.recovery_rate)*protection_leg-self.spread*premium_leg);
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+        return self.get_sign()*v*fx;
+    }
+}
+
+impl Instrument for Cds
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let default_dates=self.get_default_dates(result_cube.num_scenarios,&live_models,&logger);
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            let (mut scenario_cashflows,mut default_cashflows)=self.get_leg_cashflows(s,default_dates[s],&live_models,&logger);
+            scenario_cashflows.append(&mut default_cashflows);
+            scenario_cashflows.sort_by(|a, b| a.0.partial_cmp(&(b.0)).unwrap());
+            cashflows.push(scenario_cashflows);
+
+            //The contract terminates on the default date
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=if date>=default_dates[s] { 0.0 } else { self.get_value(s,date,&live_models,&logger) };
+                logger.log(format!("cds|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+
+    fn compute_cashflow_components(&self,num_scenarios:usize,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Option<HashMap<String,Vec<Vec<(f64,f64)>>>>
+    {
+        let default_dates=self.get_default_dates(num_scenarios,&live_models,&logger);
+        let mut premium_cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        let mut default_cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..num_scenarios
+        {
+            let (premium,default)=self.get_leg_cashflows(s,default_dates[s],&live_models,&logger);
+            premium_cashflows.push(premium);
+            default_cashflows.push(default);
+        }
+        let mut components:HashMap<String,Vec<Vec<(f64,f64)>>>=HashMap::new();
+        components.insert("premium".to_string(),premium_cashflows);
+        components.insert("default".to_string(),default_cashflows);
+        return Some(components);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+/// Spot value at 'date' net of the dividends paid up to 'maturity_date'
+/// (the spot is reduced by the continuous dividend yield and by the present value of the discrete dividends paid after 'date')
+///
+/// # Arguments
+///
+/// * `spot` - Spot value on 'date'
+/// * `scenario` - Scenario
+/// * `date` - Valuation date
+/// * `maturity_date` - Maturity date
+/// * `dividend_yield` - Continuous dividend yield
+/// * `dividends` - Discrete dividends (payment date, amount per share)
+/// * `discount_model` - Model used to discount the discrete dividends
+/// * `logger` - Logger
+pub fn get_spot_ex_dividends(spot:f64,scenario:usize,date:f64,maturity_date:f64,dividend_yield:f64,dividends:&Vec<(f64,f64)>,discount_model:&LiveModel,logger:&Logger) -> f64
+{
+    let mut v=spot*(-dividend_yield*(maturity_date-date)).exp();
+    for (dividend_date,amount) in dividends.iter()
+    {
+        if *dividend_date>date && *dividend_date<=maturity_date
+        {
+            let r=discount_model.get_value(scenario,date,dividend_date-date,&logger).unwrap();
+            v-=amount*(-r*(dividend_date-date)).exp();
+        }
+    }
+    return v;
+}
+
+/// Spot on the path at 'date', with the dividends paid up to 'horizon' (escrowed dividend model)
+///
+/// # Arguments
+///
+/// * `scenario` - Scenario
+/// * `date` - Date (on or before 'horizon')
+/// * `horizon` - Last date of the instrument (the dividends paid after it are ignored)
+/// * `dividend_yield` - Continuous dividend yield
+/// * `dividends` - Discrete dividends (payment date, amount per share)
+/// * `equity_model` - Model of the spot (simulated without dividends)
+/// * `discount_model` - Model used to discount the discrete dividends
+/// * `logger` - Logger
+///
+/// # Remarks
+///
+/// The spot net of the dividends paid up to 'horizon' (see 'get_spot_ex_dividends') is the simulated spot scaled by their ratio on date 0:
+/// the spot drops by the discrete dividends on their payment dates and grows at the rate of the equity model net of the dividend yield.
+pub fn get_spot_on_path(scenario:usize,date:f64,horizon:f64,dividend_yield:f64,dividends:&Vec<(f64,f64)>,equity_model:&LiveModel,discount_model:&LiveModel,logger:&Logger) -> f64
+{
+    let simulated_spot=equity_model.get_value(scenario,date,0.0,&logger).unwrap();
+    if dividend_yield==0.0 && dividends.is_empty()
+    {
+        return simulated_spot;
+    }
+    let initial_spot=equity_model.get_value(scenario,0.0,0.0,&logger).unwrap();
+    let scale=get_spot_ex_dividends(initial_spot,scenario,0.0,horizon,dividend_yield,&dividends,discount_model,&logger)/initial_spot;
+    if scale<=0.0
+    {
+        panic!("The dividends of the equity model {} exceed the initial spot ({})!",&equity_model.model.get_name(),initial_spot);
+    }
+    let dividends_value=-get_spot_ex_dividends(0.0,scenario,date,horizon,0.0,&dividends,discount_model,&logger);
+    return (scale*simulated_spot+dividends_value)*(dividend_yield*(horizon-date)).exp();
+}
+
+/// Equity forward implementation
+/// (purchase or sale of 'notional' shares at the 'strike' price on the maturity date)
+#[derive(Serialize, Deserialize)]
+pub struct EquityForward
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub notional:f64,
+    pub strike:f64,
+    pub maturity_date:f64,
+    pub equity_model_name:String,
+    pub discount_model_name:String,
+    pub fx_model_name:String,
+    #[serde(default)]
+    pub dividend_yield:f64,
+    #[serde(default)]
+    pub dividends:Vec<(f64,f64)>
+}
+
+impl EquityForward
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.buy_or_sell!="buy" && self.buy_or_sell!="sell"
+        {
+            return Err(format!("Equity forward {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell));
+        }
+        if !self.maturity_date.is_finite()
+        {
+            return Err(format!("Equity forward {} has an invalid maturity date ({})!",&self.name,self.maturity_date));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Spot on the path at 'date' (with the dividends paid up to the maturity date)
+    fn get_spot(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let equity_model=self.get_live_model(live_models,&self.equity_model_name);
+        let discount_model=self.get_live_model(live_models,&self.discount_model_name);
+        return get_spot_on_path(scenario,date,self.maturity_date,self.dividend_yield,&self.dividends,equity_model,discount_model,&logger);
+    }
+
+    /// Value at 'date' (in the base ccy) of the forward, if it has not settled yet
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        if self.maturity_date<=date
+        {
+            return 0.0;
+        }
+        let term=self.maturity_date-date;
+        let discount_model=self.get_live_model(live_models,&self.discount_model_name);
+        let spot=self.get_spot(scenario,date,&live_models,&logger);
+        let r=discount_model.get_value(scenario,date,term,&logger).unwrap();
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+
+        let v=get_spot_ex_dividends(spot,scenario,date,self.maturity_date,self.dividend_yield,&self.dividends,discount_model,&logger)-self.strike*(-r*term).exp();
+        return self.get_sign()*self.notional*v*fx;
+    }
+}
+
+impl Instrument for EquityForward
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let sign=self.get_sign();
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            //Settlement (net amount in the base ccy)
+            let spot=self.get_spot(s,self.maturity_date,&live_models,&logger);
+            let fx=fx_model.get_value(s,self.maturity_date,0.0,&logger).unwrap();
+            cashflows.push(vec![(self.maturity_date,sign*self.notional*(spot-self.strike)*fx)]);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=self.get_value(s,date,&live_models,&logger);
+                logger.log(format!("equity-forward|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::instrument::Instrument;
+use crate::lsm::{LsmRegression,compute_lsm_values};
+use crate::equity_forward::{get_spot_ex_dividends,get_spot_on_path};
+use crate::fx_option::garman_kohlhagen;
+use model::live_model::LiveModel;
+use model::black::Black;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_exercise_type() -> String
+{
+    return "european".to_string();
+}
+
+fn default_exercise_frequency() -> f64
+{
+    return 1.0/252.0;
+}
+
+/// Equity option implementation
+/// (option on 'notional' shares, cash settled on the exercise date)
+#[derive(Serialize, Deserialize)]
+pub struct EquityOption
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub call_or_put:String,
+    #[serde(default="default_exercise_type")]
+    pub exercise_type:String,
+    #[serde(default="default_exercise_frequency")]
+    pub exercise_frequency:f64,
+    pub notional:f64,
+    pub strike:f64,
+    pub expiry_date:f64,
+    pub equity_model_name:String,
+    pub discount_model_name:String,
+    pub fx_model_name:String,
+    #[serde(default)]
+    pub dividend_yield:f64,
+    #[serde(default)]
+    pub dividends:Vec<(f64,f64)>
+}
+
+impl EquityOption
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    fn get_omega(&self) -> f64
+    {
+        return match self.call_or_put.as_str()
+        {
+            "call"      =>  1.0,
+            "put"       =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid type ({}): it must be 'call' or 'put'!",&self.name,&self.call_or_put)
+        };
+    }
+
+    fn is_american(&self) -> bool
+    {
+        return match self.exercise_type.as_str()
+        {
+            "european"  =>  false,
+            "american"  =>  true,
+            _           =>  panic!("Instrument {} has an invalid exercise type ({}): it must be 'european' or 'american'!",&self.name,&self.exercise_type)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.buy_or_sell!="buy" && self.buy_or_sell!="sell"
+        {
+            return Err(format!("Equity option {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell));
+        }
+        if self.call_or_put!="call" && self.call_or_put!="put"
+        {
+            return Err(format!("Equity option {} has an invalid type ({}): it must be 'call' or 'put'!",&self.name,&self.call_or_put));
+        }
+        if self.exercise_type!="european" && self.exercise_type!="american"
+        {
+            return Err(format!("Equity option {} has an invalid exercise type ({}): it must be 'european' or 'american'!",&self.name,&self.exercise_type));
+        }
+        if self.exercise_frequency<=0.0
+        {
+            return Err(format!("Equity option {} has an invalid exercise frequency ({})!",&self.name,self.exercise_frequency));
+        }
+        if !self.expiry_date.is_finite()
+        {
+            return Err(format!("Equity option {} has an invalid expiry date ({})!",&self.name,self.expiry_date));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Returns the Black model of the spot (used for the volatility)
+    fn get_black_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>) -> &'a Black
+    {
+        return match self.get_live_model(live_models,&self.equity_model_name).model.as_any().downcast_ref::<Black>()
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {}: the model {} must be a Black model!",&self.name,&self.equity_model_name)
+        };
+    }
+
+    /// Spot on the path at 'date' (with the dividends paid up to the expiry date)
+    fn get_spot(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let equity_model=self.get_live_model(live_models,&self.equity_model_name);
+        let discount_model=self.get_live_model(live_models,&self.discount_model_name);
+        return get_spot_on_path(scenario,date,self.expiry_date,self.dividend_yield,&self.dividends,equity_model,discount_model,&logger);
+    }
+
+    /// Value of exercising at 'date' (in the base ccy, from the point of view of the holder)
+    fn get_exercise_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let spot=self.get_spot(scenario,date,&live_models,&logger);
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+        return self.notional*(self.get_omega()*(spot-self.strike)).max(0.0)*fx;
+    }
+
+    /// Closed form value at 'date' (in the base ccy, from the point of view of the holder) of the European option, if it has not expired yet
+    fn get_european_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,black:&Black,logger:&Logger) -> f64
+    {
+        if self.expiry_date<=date
+        {
+            return 0.0;
+        }
+        let term=self.expiry_date-date;
+        let discount_model=self.get_live_model(live_models,&self.discount_model_name);
+        let spot=self.get_spot(scenario,date,&live_models,&logger);
+        let df=(-discount_model.get_value(scenario,date,term,&logger).unwrap()*term).exp();
+        let fx=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+
+        //Black-Scholes on the spot net of dividends (Garman-Kohlhagen with a unit foreign discount factor)
+        let spot_ex_dividends=get_spot_ex_dividends(spot,scenario,date,self.expiry_date,self.dividend_yield,&self.dividends,discount_model,&logger);
+        let v=garman_kohlhagen(self.get_omega()>0.0,spot_ex_dividends,self.strike,df,1.0,black.get_variance(date,self.expiry_date));
+        return self.notional*v*fx;
+    }
+
+    /// Dates of the LSM for the American exercise
+    ///
+    /// # Remarks
+    ///
+    /// The dates are the simulation dates before expiry plus an exercise grid with 'exercise_frequency' spacing ending on the expiry date.
+    /// A final date (where the value is 0) follows the expiry date.
+    fn get_rich_dates(&self,dates:&Vec<f64>) -> Vec<(bool,f64)>
+    {
+        let date_threshold=0.001;
+        if self.exercise_frequency<=0.0
+        {
+            panic!("Instrument {} has an invalid exercise frequency ({})!",&self.name,self.exercise_frequency);
+        }
+        let mut rich_dates:Vec<(bool,f64)>=Vec::new();
+        let mut exercise_date=self.expiry_date;
+        while exercise_date>date_threshold
+        {
+            rich_dates.push((true,exercise_date));
+            exercise_date-=self.exercise_frequency;
+        }
+        for date in dates.iter()
+        {
+            if *date<self.expiry_date && rich_dates.iter().all(|d| (d.1-date).abs()>date_threshold)
+            {
+                rich_dates.push((false,*date));
+            }
+        }
+        rich_dates.sort_by(|a, b| a.1.partial_cmp(&(b.1)).unwrap());
+        rich_dates.push((false,self.expiry_date+date_threshold));
+        return rich_dates;
+    }
+}
+
+impl Instrument for EquityOption
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let sign=self.get_sign();
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+
+        if !self.is_american()
+        {
+            let black=self.get_black_model(&live_models);
+            for s in 0..result_cube.num_scenarios
+            {
+                cashflows.push(vec![(self.expiry_date,sign*self.get_exercise_value(s,self.expiry_date,&live_models,&logger))]);
+                for dt_idx in 0..result_cube.dates.len()
+                {
+                    let date=result_cube.dates[dt_idx];
+                    let v=sign*self.get_european_value(s,date,&live_models,black,&logger);
+                    logger.log(format!("equity-option|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                    let _=result_cube.set_item(s, start, dt_idx, v);
+                }
+            }
+            let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+            return (cashflows,exercise_cube,Vec::new());
+        }
+
+        let rich_dates=self.get_rich_dates(&result_cube.dates);
+        let dates:Vec<f64>=rich_dates.iter().map(|d| d.1).collect();
+        let exercise_flags:Vec<bool>=rich_dates.iter().map(|d| d.0).collect();
+
+        //The regression variable is the spot
+        let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| {
+            let equity_model=self.get_live_model(live_models,&self.equity_model_name);
+            let mut values:Vec<f64>=Vec::new();
+            for s in 0..equity_model.cube.num_scenarios { values.append(&mut equity_model.get_variable_values(s,date,&logger)); }
+            values
+        };
+        let mut f_exercise_value=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| self.get_exercise_value(scenario,date,&live_models,&logger);
+        let mut f_cashflows=|_scenario:usize,_min_date:f64,_max_date:f64,_live_models:&HashMap<String,LiveModel>| Vec::new();
+
+        //Two-pass LSM: the regressions are estimated on the training paths and then applied to the pricing paths
+        let training_regressions=match training_live_models
+        {
+            Some(training_models)   =>  {
+                                            let training_dsc_model=self.get_live_model(training_models,&self.discount_model_name);
+                                            let mut training_values_cube:Cube=Cube::make_empty_cube(dates.clone(), training_dsc_model.cube.num_scenarios, 1);
+                                            let (_,_,r)=compute_lsm_values(&mut training_values_cube,&training_models,&exercise_flags,&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,&training_dsc_model,None,&logger);
+                                            Some(r)
+                                        },
+            None                    =>  None
+        };
+
+        let dsc_model=self.get_live_model(live_models,&self.discount_model_name);
+        let mut values_cube:Cube=Cube::make_empty_cube(dates, result_cube.num_scenarios, 1);
+        logger.log("equity-option|compute_values -> Starting lsm...","instrument");
+        let (_,exercise_cube,regressions)=compute_lsm_values(&mut values_cube,&live_models,&exercise_flags,&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,&dsc_model,training_regressions.as_ref(),&logger);
+        logger.log("equity-option|compute_values -> lsm done.","instrument");
+
+        //The payoff is paid on the exercise date, the value is 0 afterwards
+        for s in 0..result_cube.num_scenarios
+        {
+            let mut exercise_date:Option<f64>=None;
+            for dt_idx in 0..exercise_cube.dates.len()
+            {
+                if exercise_cube.get_item(s, 0, dt_idx).unwrap()>0.0
+                {
+                    exercise_date=Some(exercise_cube.dates[dt_idx]);
+                    break;
+                }
+            }
+            cashflows.push(match exercise_date
+            {
+                Some(ex_dt) =>  vec![(ex_dt,sign*self.get_exercise_value(s,ex_dt,&live_models,&logger))],
+                None        =>  Vec::new()
+            });
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=match exercise_date
+                {
+                    Some(ex_dt) if date>ex_dt           =>  0.0,
+                    _ if date>=self.expiry_date         =>  0.0,
+                    _                                   =>  sign*values_cube.get_item_interp(s, 0, date, true).unwrap().2
+                };
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+        return (cashflows,exercise_cube,regressions);
+    }
+}
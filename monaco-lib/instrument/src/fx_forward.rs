@@ -0,0 +1,115 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+/// FX forward implementation
+/// (exchange of 'notional' units of the foreign ccy against 'notional*strike' units of the domestic ccy on the maturity date)
+#[derive(Serialize, Deserialize)]
+pub struct FxForward
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub notional:f64,
+    pub strike:f64,
+    pub maturity_date:f64,
+    pub fx_model_name:String,
+    pub domestic_discount_model_name:String,
+    pub foreign_discount_model_name:String,
+    pub domestic_fx_model_name:String
+}
+
+impl FxForward
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.buy_or_sell!="buy" && self.buy_or_sell!="sell"
+        {
+            return Err(format!("FX forward {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell));
+        }
+        if !self.maturity_date.is_finite()
+        {
+            return Err(format!("FX forward {} has an invalid maturity date ({})!",&self.name,self.maturity_date));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Value at 'date' (in the base ccy) of the exchange, if it has not settled yet
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        if self.maturity_date<=date
+        {
+            return 0.0;
+        }
+        let term=self.maturity_date-date;
+        let spot=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+        let domestic_rate=self.get_live_model(live_models,&self.domestic_discount_model_name).get_value(scenario,date,term,&logger).unwrap();
+        let foreign_rate=self.get_live_model(live_models,&self.foreign_discount_model_name).get_value(scenario,date,term,&logger).unwrap();
+        let domestic_fx=self.get_live_model(live_models,&self.domestic_fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+
+        let v=spot*(-foreign_rate*term).exp()-self.strike*(-domestic_rate*term).exp();
+        return self.get_sign()*self.notional*v*domestic_fx;
+    }
+}
+
+impl Instrument for FxForward
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let sign=self.get_sign();
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let domestic_fx_model=self.get_live_model(live_models,&self.domestic_fx_model_name);
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            //Settlement of the exchange (net amount in the base ccy), unless it has already settled
+            let mut scenario_cashflows:Vec<(f64,f64)>=Vec::new();
+            if self.maturity_date>0.0
+            {
+                let spot=fx_model.get_value(s,self.maturity_date,0.0,&logger).unwrap();
+                let domestic_fx=domestic_fx_model.get_value(s,self.maturity_date,0.0,&logger).unwrap();
+                scenario_cashflows.push((self.maturity_date,sign*self.notional*(spot-self.strike)*domestic_fx));
+            }
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=self.get_value(s,date,&live_models,&logger);
+                logger.log(format!("fx-forward|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
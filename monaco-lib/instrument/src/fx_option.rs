@@ -0,0 +1,159 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use model::live_model::LiveModel;
+use model::black::Black;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+/// Garman-Kohlhagen price (in the domestic ccy) of a European option on one unit of the foreign ccy
+///
+/// # Arguments
+///
+/// * `is_call` - True for a call on the foreign ccy, false for a put
+/// * `spot` - Spot value of one unit of the foreign ccy in the domestic ccy
+/// * `strike` - Strike (domestic ccy per unit of foreign ccy)
+/// * `domestic_df` - Domestic discount factor to the expiry date
+/// * `foreign_df` - Foreign discount factor to the expiry date
+/// * `variance` - Integrated variance of the log spot to the expiry date
+pub fn garman_kohlhagen(is_call:bool,spot:f64,strike:f64,domestic_df:f64,foreign_df:f64,variance:f64) -> f64
+{
+    let forward=spot*foreign_df/domestic_df;
+    return domestic_df*math::math::black_formula(is_call,forward,strike,variance);
+}
+
+/// European FX option implementation
+/// (option on 'notional' units of the foreign ccy, cash settled in the domestic ccy on the expiry date)
+#[derive(Serialize, Deserialize)]
+pub struct FxOption
+{
+    pub name: String,
+    pub buy_or_sell:String,
+    pub call_or_put:String,
+    pub notional:f64,
+    pub strike:f64,
+    pub expiry_date:f64,
+    pub fx_model_name:String,
+    pub domestic_discount_model_name:String,
+    pub foreign_discount_model_name:String,
+    pub domestic_fx_model_name:String
+}
+
+impl FxOption
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.buy_or_sell.as_str()
+        {
+            "buy"       =>  1.0,
+            "sell"      =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell)
+        };
+    }
+
+    fn is_call(&self) -> bool
+    {
+        return match self.call_or_put.as_str()
+        {
+            "call"      =>  true,
+            "put"       =>  false,
+            _           =>  panic!("Instrument {} has an invalid type ({}): it must be 'call' or 'put'!",&self.name,&self.call_or_put)
+        };
+    }
+
+    /// Checks the consistency of the definition
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.buy_or_sell!="buy" && self.buy_or_sell!="sell"
+        {
+            return Err(format!("FX option {} has an invalid side ({}): it must be 'buy' or 'sell'!",&self.name,&self.buy_or_sell));
+        }
+        if self.call_or_put!="call" && self.call_or_put!="put"
+        {
+            return Err(format!("FX option {} has an invalid type ({}): it must be 'call' or 'put'!",&self.name,&self.call_or_put));
+        }
+        if !self.expiry_date.is_finite()
+        {
+            return Err(format!("FX option {} has an invalid expiry date ({})!",&self.name,self.expiry_date));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Returns the Black model of the spot (used for the volatility)
+    fn get_black_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>) -> &'a Black
+    {
+        return match self.get_live_model(live_models,&self.fx_model_name).model.as_any().downcast_ref::<Black>()
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {}: the model {} must be a Black model!",&self.name,&self.fx_model_name)
+        };
+    }
+
+    /// Value at 'date' (in the base ccy) of the option, if it has not expired yet
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,black:&Black,logger:&Logger) -> f64
+    {
+        if self.expiry_date<=date
+        {
+            return 0.0;
+        }
+        let term=self.expiry_date-date;
+        let spot=self.get_live_model(live_models,&self.fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+        let domestic_rate=self.get_live_model(live_models,&self.domestic_discount_model_name).get_value(scenario,date,term,&logger).unwrap();
+        let foreign_rate=self.get_live_model(live_models,&self.foreign_discount_model_name).get_value(scenario,date,term,&logger).unwrap();
+        let domestic_fx=self.get_live_model(live_models,&self.domestic_fx_model_name).get_value(scenario,date,0.0,&logger).unwrap();
+
+        let v=garman_kohlhagen(self.is_call(),spot,self.strike,(-domestic_rate*term).exp(),(-foreign_rate*term).exp(),black.get_variance(date,self.expiry_date));
+        return self.get_sign()*self.notional*v*domestic_fx;
+    }
+}
+
+impl Instrument for FxOption
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let sign=self.get_sign();
+        let omega:f64=if self.is_call() { 1.0 } else { -1.0 };
+        let black=self.get_black_model(&live_models);
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let domestic_fx_model=self.get_live_model(live_models,&self.domestic_fx_model_name);
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            //Cash settlement of the payoff, unless the option has already expired
+            let mut scenario_cashflows:Vec<(f64,f64)>=Vec::new();
+            if self.expiry_date>0.0
+            {
+                let spot=fx_model.get_value(s,self.expiry_date,0.0,&logger).unwrap();
+                let domestic_fx=domestic_fx_model.get_value(s,self.expiry_date,0.0,&logger).unwrap();
+                scenario_cashflows.push((self.expiry_date,sign*self.notional*(omega*(spot-self.strike)).max(0.0)*domestic_fx));
+            }
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=self.get_value(s,date,&live_models,black,&logger);
+                logger.log(format!("fx-option|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
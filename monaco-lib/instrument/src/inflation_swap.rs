@@ -0,0 +1,279 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::vanilla_swap::add_fixings;
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_index_interpolation() -> String
+{
+    return "flat".to_string();
+}
+
+/// Inflation swap implementation
+/// (zero-coupon or year-on-year inflation against a fixed rate, with the CPI observed with a lag)
+#[derive(Serialize, Deserialize)]
+pub struct InflationSwap
+{
+    pub name: String,
+    pub inflation_type:String,
+    pub pay_or_receive:String,
+    pub notional:f64,
+    pub fixed_rate:f64,
+    pub payment_dates:Vec<f64>,
+    pub cpi_model_name:String,
+    pub discount_model_name:String,
+    pub real_discount_model_name:String,
+    pub fx_model_name:String,
+    #[serde(default)]
+    pub index_lag:f64,
+    #[serde(default="default_index_interpolation")]
+    pub index_interpolation:String,
+    #[serde(default)]
+    pub index_name:String,
+    #[serde(default)]
+    pub fixings:Vec<(f64,f64)>
+}
+
+/// Live models of an inflation swap
+struct InflationModels<'a>
+{
+    cpi:&'a LiveModel<'a>,
+    discount:&'a LiveModel<'a>,
+    real_discount:&'a LiveModel<'a>,
+    fx:&'a LiveModel<'a>
+}
+
+impl InflationSwap
+{
+    /// Sign of the inflation leg ('receive' receives the inflation and pays the fixed rate)
+    fn get_sign(&self) -> f64
+    {
+        return match self.pay_or_receive.as_str()
+        {
+            "receive"   =>  1.0,
+            "pay"       =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'pay' or 'receive'!",&self.name,&self.pay_or_receive)
+        };
+    }
+
+    fn is_zero_coupon(&self) -> bool
+    {
+        return match self.inflation_type.as_str()
+        {
+            "zero-coupon"       =>  true,
+            "year-on-year"      =>  false,
+            _                   =>  panic!("Instrument {} has an invalid inflation type ({}): it must be 'zero-coupon' or 'year-on-year'!",&self.name,&self.inflation_type)
+        };
+    }
+
+    /// Name of the CPI index in the fixing history (the CPI model name if not set)
+    pub fn get_index_name(&self) -> &String
+    {
+        if self.index_name.is_empty()
+        {
+            return &self.cpi_model_name;
+        }
+        return &self.index_name;
+    }
+
+    /// Adds the fixings of 'fixing_history' (index name -> list of (date,CPI)) to the swap (the fixings defined in the swap take precedence)
+    pub fn apply_fixing_history(&mut self,fixing_history:&HashMap<String,Vec<(f64,f64)>>) -> ()
+    {
+        if let Some(history)=fixing_history.get(self.get_index_name())
+        {
+            add_fixings(&mut self.fixings,history);
+        }
+    }
+
+    /// Known CPI fixing of the month starting on 'month_date' (the fixing dated within half a month of the start of the month)
+    fn get_fixing(&self,month_date:f64) -> Option<f64>
+    {
+        let half_month:f64=0.5/12.0;
+        return self.fixings.iter().filter(|f| (f.0-month_date).abs()<half_month).min_by(|a, b| (a.0-month_date).abs().partial_cmp(&(b.0-month_date).abs()).unwrap()).map(|f| f.1);
+    }
+
+    /// Start dates of the months whose CPI is observed by a payment date ('month_start', followed by the next month if the CPI is interpolated)
+    fn get_observed_months(&self,payment_date:f64) -> Vec<f64>
+    {
+        let month:f64=1.0/12.0;
+        let reference_date=payment_date-self.index_lag;
+        let month_start=((reference_date/month)+0.0000001).floor()*month;
+        if self.index_interpolation=="linear" && (reference_date-month_start)/month>0.0000001
+        {
+            return vec![month_start,month_start+month];
+        }
+        return vec![month_start];
+    }
+
+    /// Checks the consistency of the definition
+    ///
+    /// # Remarks
+    ///
+    /// The fixing history must be applied first: the CPI of every month observed before the evaluation date 0 must be known.
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payment_dates.len()<2
+        {
+            return Err(format!("Inflation swap {} must have a start date and at least one payment date!",&self.name));
+        }
+        if self.inflation_type!="zero-coupon" && self.inflation_type!="year-on-year"
+        {
+            return Err(format!("Inflation swap {} has an invalid inflation type ({}): it must be 'zero-coupon' or 'year-on-year'!",&self.name,&self.inflation_type));
+        }
+        if self.pay_or_receive!="pay" && self.pay_or_receive!="receive"
+        {
+            return Err(format!("Inflation swap {} has an invalid side ({}): it must be 'pay' or 'receive'!",&self.name,&self.pay_or_receive));
+        }
+        if self.is_zero_coupon() && self.payment_dates.len()!=2
+        {
+            return Err(format!("Zero-coupon inflation swap {} must have only a start date and a maturity date!",&self.name));
+        }
+        if self.index_interpolation!="flat" && self.index_interpolation!="linear"
+        {
+            return Err(format!("Inflation swap {} has an invalid index interpolation ({}): it must be 'flat' or 'linear'!",&self.name,&self.index_interpolation));
+        }
+        let tolerance:f64=0.0000001;
+        for payment_date in self.payment_dates.iter()
+        {
+            for month_date in self.get_observed_months(*payment_date)
+            {
+                if month_date< -tolerance && self.get_fixing(month_date).is_none()
+                {
+                    return Err(format!("Inflation swap {} has no fixing of {} for the month starting on {}!",&self.name,self.get_index_name(),month_date));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn get_live_models<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>) -> InflationModels<'a>
+    {
+        let cpi_model=match live_models.get(&self.cpi_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.cpi_model_name)
+        };
+        let discount_model=match live_models.get(&self.discount_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.discount_model_name)
+        };
+        let real_discount_model=match live_models.get(&self.real_discount_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.real_discount_model_name)
+        };
+        let fx_model=match live_models.get(&self.fx_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.fx_model_name)
+        };
+        return InflationModels { cpi:cpi_model, discount:discount_model, real_discount:real_discount_model, fx:fx_model };
+    }
+
+    /// CPI for the month starting on 'month_date', as known on 'date' (known fixing, realised on the path or forward)
+    fn get_monthly_index(&self,scenario:usize,date:f64,month_date:f64,models:&InflationModels,logger:&Logger) -> f64
+    {
+        let tolerance:f64=0.0000001;
+        if month_date< -tolerance
+        {
+            return match self.get_fixing(month_date)
+            {
+                Some(index) =>  index,
+                None        =>  panic!("Inflation swap {} has no fixing of {} for the month starting on {}!",&self.name,self.get_index_name(),month_date)
+            };
+        }
+        if month_date<=date
+        {
+            return models.cpi.get_value(scenario,month_date.max(0.0),0.0,&logger).unwrap();
+        }
+        let term=month_date-date;
+        let spot=models.cpi.get_value(scenario,date,0.0,&logger).unwrap();
+        let nominal_rate=models.discount.get_value(scenario,date,term,&logger).unwrap();
+        let real_rate=models.real_discount.get_value(scenario,date,term,&logger).unwrap();
+        return spot*((nominal_rate-real_rate)*term).exp();
+    }
+
+    /// Reference CPI for a payment date (observed 'index_lag' earlier, flat or linearly interpolated between monthly fixings), as known on 'date'
+    fn get_reference_index(&self,scenario:usize,date:f64,payment_date:f64,models:&InflationModels,logger:&Logger) -> f64
+    {
+        let month:f64=1.0/12.0;
+        let months=self.get_observed_months(payment_date);
+        let index=self.get_monthly_index(scenario,date,months[0],&models,&logger);
+        if months.len()>1
+        {
+            let weight=(payment_date-self.index_lag-months[0])/month;
+            let next_index=self.get_monthly_index(scenario,date,months[1],&models,&logger);
+            return index+weight*(next_index-index);
+        }
+        return index;
+    }
+
+    /// Net amount (inflation minus fixed, per unit of notional) paid on 'payment_dates[t]', as known on 'date'
+    fn get_payment(&self,t:usize,scenario:usize,date:f64,models:&InflationModels,logger:&Logger) -> f64
+    {
+        let start_index=self.get_reference_index(scenario,date,self.payment_dates[t-1],&models,&logger);
+        let end_index=self.get_reference_index(scenario,date,self.payment_dates[t],&models,&logger);
+        let period=self.payment_dates[t]-self.payment_dates[t-1];
+        let fixed=if self.is_zero_coupon() { (1.0+self.fixed_rate).powf(period)-1.0 } else { self.fixed_rate*period };
+        return end_index/start_index-1.0-fixed;
+    }
+
+    /// Value at 'date' (in the base ccy) of the payments after 'date'
+    fn get_value(&self,scenario:usize,date:f64,models:&InflationModels,logger:&Logger) -> f64
+    {
+        let mut v:f64=0.0;
+        for t in 1..self.payment_dates.len()
+        {
+            if self.payment_dates[t]>date
+            {
+                let term=self.payment_dates[t]-date;
+                let df=(-models.discount.get_value(scenario,date,term,&logger).unwrap()*term).exp();
+                v+=self.get_payment(t,scenario,date,&models,&logger)*df;
+            }
+        }
+        let fx=models.fx.get_value(scenario,date,0.0,&logger).unwrap();
+        return self.get_sign()*self.notional*v*fx;
+    }
+}
+
+impl Instrument for InflationSwap
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let models=self.get_live_models(&live_models);
+        let sign=self.get_sign();
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            //Realised inflation payments
+            let mut scenario_cashflows:Vec<(f64,f64)>=Vec::new();
+            for t in 1..self.payment_dates.len()
+            {
+                let payment_date=self.payment_dates[t];
+                let fx=models.fx.get_value(s,payment_date,0.0,&logger).unwrap();
+                scenario_cashflows.push((payment_date,sign*self.notional*self.get_payment(t,s,payment_date,&models,&logger)*fx));
+            }
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=self.get_value(s,date,&models,&logger);
+                logger.log(format!("inflation-swap|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+
+        let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
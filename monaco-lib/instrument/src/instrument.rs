@@ -2,12 +2,53 @@ use data_cube::data_cube::Cube;
 use model::live_model::LiveModel;
 use std::collections::HashMap;
 use logger::Logger;
+use crate::lsm::LsmRegression;
+use crate::lsm::LsmBounds;
+use model::live_model::InnerSimulator;
 //use std::any::Any;
 
 pub trait Instrument
 {
     fn get_name(&self) -> String;
-    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube);
+    /// Populates the instrument series in the results cube
+    /// 
+    /// Returns the cashflows, the exercise cube, and the LSM regressions used (empty if the instrument does not use LSM)
+    /// 
+    /// # Remarks
+    /// 
+    /// When 'training_live_models' is set, LSM regressions should be estimated on the training paths and then applied to the pricing paths ('live_models')
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>);
+    /// Computes lower and upper bounds for the value of the instrument (only for instruments with an exercise policy)
+    /// 
+    /// # Remarks
+    /// 
+    /// 'dates' are the simulation dates and 'regressions' the ones returned by 'compute_values'
+    fn compute_bounds(&self,_dates:&Vec<f64>,_live_models:&HashMap<String,LiveModel>,_regressions:&Vec<LsmRegression>,_simulator:&dyn InnerSimulator,_num_outer_paths:usize,_num_inner_paths:usize,_logger:&Logger) -> Option<LsmBounds>
+    {
+        return None;
+    }
+    /// Returns the cashflows split by component (component name -> cashflows for each scenario), for instruments whose legs are reported separately
+    /// 
+    /// # Remarks
+    /// 
+    /// The components must add up to the cashflows returned by 'compute_values'
+    fn compute_cashflow_components(&self,_num_scenarios:usize,_live_models:&HashMap<String,LiveModel>,_logger:&Logger) -> Option<HashMap<String,Vec<Vec<(f64,f64)>>>>
+    {
+        return None;
+    }
 
     //fn as_any(&self) -> &dyn Any;
-}
\ No newline at end of file
+}
+
+/// Instruments that can be terminated early by a 'Callable' wrapper
+pub trait Exercisable
+{
+    /// Date of the last cashflow
+    fn get_maturity_date(&self) -> f64;
+    /// Returns the variable values used as regressors in the LSM algorithm (rows are scenarios, cols are variables)
+    fn get_models_variables_values(&self,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<f64>;
+    /// Value of the cashflows after 'date'
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64;
+    /// Cashflows in (min_date,max_date]
+    fn get_cashflows(&self,scenario:usize,min_date:f64,max_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>;
+}
@@ -1,12 +1,1022 @@
 pub mod instrument;
 pub mod lsm;
 pub mod vanilla_swap;
+pub mod callable;
 pub mod callable_swap;
+pub mod swaption;
+pub mod cap_floor;
+pub mod fx_forward;
+pub mod fx_option;
+pub mod equity_forward;
+pub mod equity_option;
+pub mod bond;
+pub mod cds;
+pub mod inflation_swap;
+pub mod path_dependent;
+pub mod range_accrual;
+pub mod tarn;
+pub mod barrier_option;
+pub mod asian_option;
+pub mod script;
+pub mod scripted;
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use model::model::Model;
+    use model::live_model::LiveModel;
+    use data_cube::data_cube::Cube;
+    use logger::Logger;
+
+    /// Simulates the models (uncorrelated variables) on 'dates': returns the paths and the raw cube
+    fn simulate(models:&Vec<Box<dyn Model>>,dates:&Vec<f64>,num_paths:usize,seed:u64) -> (Cube,Cube)
+    {
+        let num_variables:usize=models.iter().map(|m| m.get_number_of_variables()).sum();
+        let num_outputs:usize=models.iter().map(|m| m.get_number_of_outputs()).sum();
+        let raw_cube=match num_variables
+        {
+            0   =>  Cube::make_empty_cube(dates.clone(),num_paths,0),
+            _   =>  {
+                        let mut correlation_matrix=vec![0.0;num_variables*num_variables];
+                        for v in 0..num_variables { correlation_matrix[v*num_variables+v]=1.0; }
+                        let variates=math::math::simulate_normal_variates_with_seed(num_variables,num_paths*dates.len(),&correlation_matrix,seed);
+                        Cube::make_cube(variates,dates.clone(),num_paths,num_variables)
+                    }
+        };
+        let logger=Logger { log_tags:Vec::new() };
+        let mut paths=Cube::make_empty_cube(dates.clone(),num_paths,num_outputs);
+        let (mut start,mut raw_start)=(0,0);
+        for model in models.iter()
+        {
+            model.populate_factors(raw_start,&raw_cube,start,&mut paths,&logger);
+            start+=model.get_number_of_outputs();
+            raw_start+=model.get_number_of_variables();
+        }
+        return (paths,raw_cube);
+    }
+
+    fn make_live_models<'a>(models:&'a Vec<Box<dyn Model>>,paths:&'a Cube,raw_cube:&'a Cube) -> HashMap<String,LiveModel<'a>>
+    {
+        let mut live_models:HashMap<String,LiveModel>=HashMap::new();
+        let (mut start,mut raw_start)=(0,0);
+        for model in models.iter()
+        {
+            live_models.insert(model.get_name(),LiveModel { cube:paths, start:start, raw_cube:raw_cube, raw_start:raw_start, model:model });
+            start+=model.get_number_of_outputs();
+            raw_start+=model.get_number_of_variables();
+        }
+        return live_models;
+    }
+
+    fn make_hw1f(name:&str,rate:f64,sigma:f64) -> Box<dyn Model>
+    {
+        let mut hw1f=model::hw1f::Hw1f { name:name.to_string(), interpolation:model::model::OutputInterpolation::Linear, term_structure:vec![(0.0,rate),(1.0,rate),(10.0,rate)],
+                                         thetas:Vec::new(), a:vec![(0.0,1.0)], sigmas:vec![(0.0,sigma)], initial_rate:rate };
+        hw1f.init();
+        return Box::new(hw1f);
+    }
+
+    /// Swap receiving 'fixed_rate' against the 'USD' short rate on 'payment_dates' (the first date is the start date)
+    fn make_swap_json(fixed_rate:f64,payment_dates:&Vec<f64>,fx_model_name:&str) -> String
+    {
+        let mut fixed_values:Vec<f64>=vec![0.0];
+        fixed_values.extend((1..payment_dates.len()).map(|t| fixed_rate*(payment_dates[t]-payment_dates[t-1])));
+        let spreads=vec![0.0;payment_dates.len()];
+        return format!(r#"{{"name":"swap","legs":[
+            {{"notional":100.0,"pay_or_receive":"receive","discount_model_name":"USD","projection_model_name":"USD","fx_model_name":"{fx}","payment_dates":{d:?},"is_fixed":true,"fixed_values":{f:?}}},
+            {{"notional":100.0,"pay_or_receive":"pay","discount_model_name":"USD","projection_model_name":"USD","fx_model_name":"{fx}","payment_dates":{d:?},"is_fixed":false,"fixed_values":{s:?}}}]}}"#,
+            fx=fx_model_name,d=payment_dates,f=fixed_values,s=spreads);
+    }
+
+    /// Fixed cashflows (underlying of the callable tests): the value is the sum of the cashflows after the date (the tests use 0 rates)
+    struct FixedCashflows
+    {
+        cashflows:Vec<(f64,f64)>
+    }
+
+    impl crate::instrument::Exercisable for FixedCashflows
+    {
+        fn get_maturity_date(&self) -> f64
+        {
+            return self.cashflows[self.cashflows.len()-1].0;
+        }
+        fn get_models_variables_values(&self,_date:f64,_live_models:&HashMap<String,LiveModel>,_logger:&Logger) -> Vec<f64>
+        {
+            return Vec::new();
+        }
+        fn get_value(&self,_scenario:usize,date:f64,_live_models:&HashMap<String,LiveModel>,_logger:&Logger) -> f64
+        {
+            return self.cashflows.iter().filter(|c| c.0>date).map(|c| c.1).sum();
+        }
+        fn get_cashflows(&self,_scenario:usize,min_date:f64,max_date:f64,_live_models:&HashMap<String,LiveModel>,_logger:&Logger) -> Vec<(f64,f64)>
+        {
+            return self.cashflows.iter().filter(|c| c.0>min_date && c.0<=max_date).map(|c| *c).collect();
+        }
+    }
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn exercise_window_grid() {
+        let window=crate::callable::ExerciseWindow { start_date:1.0, end_date:2.0, notice_period:0.1, exercise_frequency:0.3 };
+        assert!(window.validate().is_ok());
+        assert!(crate::callable::ExerciseWindow { start_date:2.0, end_date:1.0, notice_period:0.0, exercise_frequency:0.3 }.validate().is_err());
+        assert!(crate::callable::ExerciseWindow { start_date:1.0, end_date:2.0, notice_period:0.0, exercise_frequency:0.0 }.validate().is_err());
+        let dates=window.get_exercise_dates();
+        assert_eq!(dates.len(),5);
+        assert!((dates[0]-0.9).abs()<1e-12);
+        assert!((dates[4]-1.9).abs()<1e-12);
+    }
+
+    #[test]
+    fn garman_kohlhagen_parity() {
+        let (spot,strike,domestic_df,foreign_df,variance)=(1.1,1.05,0.97,0.99,0.02);
+        let call=crate::fx_option::garman_kohlhagen(true,spot,strike,domestic_df,foreign_df,variance);
+        let put=crate::fx_option::garman_kohlhagen(false,spot,strike,domestic_df,foreign_df,variance);
+        assert!((call-put-(spot*foreign_df-strike*domestic_df)).abs()<1e-7);
+    }
+
+    #[test]
+    fn fx_settlements() {
+        use crate::instrument::Instrument;
+        //0 rates: the values are the expectations of the settlement cashflows
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"EURUSD".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.2)], initial_value:1.1 })];
+        let dates=vec![0.0,0.25,0.5,0.75,1.0];
+        let num_paths=5000;
+        let (paths,raw_cube)=simulate(&models,&dates,num_paths,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let fx_json=|side:&str,date:f64,option_type:&str| format!(r#"{{"name":"fx","buy_or_sell":"{}","notional":100.0,"strike":1.05,"maturity_date":{},"expiry_date":{},"call_or_put":"{}",
+            "fx_model_name":"EURUSD","domestic_discount_model_name":"USD","foreign_discount_model_name":"USD","domestic_fx_model_name":"FX"}}"#,side,date,date,option_type);
+
+        let forward:crate::fx_forward::FxForward=serde_json::from_str(&fx_json("buy",1.0,"call")).unwrap();
+        assert!(forward.validate().is_ok());
+        assert!(serde_json::from_str::<crate::fx_forward::FxForward>(&fx_json("long",1.0,"call")).unwrap().validate().is_err());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),num_paths,1);
+        let (cashflows,_,_)=forward.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let fx_model=live_models.get("EURUSD").unwrap();
+        for s in 0..num_paths
+        {
+            let spot=fx_model.get_value(s,1.0,0.0,&logger).unwrap();
+            assert!(cashflows[s].len()==1 && cashflows[s][0].0==1.0 && (cashflows[s][0].1-100.0*(spot-1.05)).abs()<1e-9);
+            assert!((results_cube.get_item(s,0,2).unwrap()-100.0*(fx_model.get_value(s,0.5,0.0,&logger).unwrap()-1.05)).abs()<1e-9);
+            assert_eq!(results_cube.get_item(s,0,4).unwrap(),0.0);
+        }
+        assert!((results_cube.get_item(0,0,0).unwrap()-5.0).abs()<1e-9);
+
+        let option:crate::fx_option::FxOption=serde_json::from_str(&fx_json("buy",1.0,"call")).unwrap();
+        assert!(option.validate().is_ok());
+        assert!(serde_json::from_str::<crate::fx_option::FxOption>(&fx_json("buy",1.0,"straddle")).unwrap().validate().is_err());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),num_paths,1);
+        let (cashflows,_,_)=option.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let (mean,std_error)=math::math::mean_and_standard_error(&cashflows.iter().map(|c| c[0].1).collect());
+        assert!((mean-results_cube.get_item(0,0,0).unwrap()).abs()<3.0*std_error);
+
+        //Settled trades have no cashflows and a value of 0
+        let (forward_cashflows,_,_)=serde_json::from_str::<crate::fx_forward::FxForward>(&fx_json("buy",-0.5,"call")).unwrap().compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let (option_cashflows,_,_)=serde_json::from_str::<crate::fx_option::FxOption>(&fx_json("buy",0.0,"put")).unwrap().compute_values(0,&mut results_cube,&live_models,None,&logger);
+        assert!(forward_cashflows.iter().chain(option_cashflows.iter()).all(|c| c.is_empty()));
+        assert_eq!(results_cube.get_item(0,0,0).unwrap(),0.0);
+    }
+
+    #[test]
+    fn payoff_script_evaluation() {
+        let statements=crate::script::parse_script("x=max(2,3)*2^2-1; if x>10 and not x==12 then pay(x) else pay(0) end; if x<0 then terminate end; exercise(if(x>=11,-x,x))").unwrap();
+        assert!(crate::script::has_exercise(&statements));
+        let live_models=std::collections::HashMap::new();
+        let parameters=std::collections::HashMap::new();
+        let logger=logger::Logger { log_tags:Vec::new() };
+        let mut context=crate::script::ScriptContext { scenario:0, date:1.0, period:0.5, live_models:&live_models, parameters:&parameters, variables:std::collections::HashMap::new(),
+                                                       payments:Vec::new(), exercise_value:None, terminated:false, logger:&logger };
+        context.execute(&statements).unwrap();
+        assert_eq!(context.payments,vec![11.0]);
+        assert_eq!(context.exercise_value,Some(-11.0));
+        assert!(!context.terminated);
+        assert!(crate::script::parse_script("if x>1 then pay(x)").is_err());
+    }
+
+    #[test]
+    fn payoff_script_checks() {
+        let scripted_json=|script:&str| format!(r#"{{"name":"scripted","parameters":{{"N":100.0}},"events":[{{"dates":[0.5],"script":"perf=spot(\"EQ\")/100"}},{{"dates":[1.0],"script":"{}"}}],
+            "discount_model_name":"USD","fx_model_name":"FX"}}"#,script);
+        let model_names=vec!["EQ".to_string(),"USD".to_string(),"FX".to_string()];
+        let validate=|script:&str| serde_json::from_str::<crate::scripted::ScriptedInstrument>(&scripted_json(script)).unwrap().validate(&model_names);
+        assert!(validate(r#"if perf>=1 and date==1 then pay(N*max(perf,fixing(\"EQ\",0.5,0)/100)) else pay(N*df(\"USD\",period)) end"#).is_ok());
+
+        //Unknown functions, wrong numbers of arguments, model names without quotes, undefined variables and assigned parameters are found before any path is evaluated
+        assert!(validate("pay(N*maximum(perf,1))").unwrap_err().contains("unknown function (maximum)"));
+        assert!(validate("pay(N*max())").unwrap_err().contains("max expects at least 1 arguments"));
+        assert!(validate(r#"pay(N*rate(\"USD\"))"#).unwrap_err().contains("rate expects 2 arguments"));
+        assert!(validate("pay(N*spot(EQ))").unwrap_err().contains("spot expects a model name in quotes"));
+        assert!(validate(r#"pay(N*\"EQ\")"#).unwrap_err().contains("unexpected text"));
+        assert!(validate("pay(N*perf_total)").unwrap_err().contains("undefined variable (perf_total)"));
+        assert!(validate("N=2; pay(N)").unwrap_err().contains("N can not be assigned"));
+        assert!(validate("if perf>1 then x=1 end; pay(if(x>0,N,0,1))").unwrap_err().contains("if expects 3 arguments"));
+        let mut with_state:crate::scripted::ScriptedInstrument=serde_json::from_str(&scripted_json("pay(N)")).unwrap();
+        with_state.state_variables=Some(vec!["perf".to_string(),"coupon".to_string()]);
+        assert!(with_state.validate(&model_names).unwrap_err().contains("state variable (coupon)"));
+
+        //Misspelt model names are found before any path is evaluated
+        assert!(validate(r#"pay(N*spot(\"EQX\"))"#).unwrap_err().contains("non-existent model (EQX)"));
+        assert!(validate(r#"if perf>1 then pay(N*df(\"EUR\",period)) end"#).unwrap_err().contains("non-existent model (EUR)"));
+        let mut with_regression:crate::scripted::ScriptedInstrument=serde_json::from_str(&scripted_json("pay(N)")).unwrap();
+        with_regression.regression_model_names=vec!["EQ".to_string()];
+        assert!(with_regression.validate(&model_names).is_ok());
+        with_regression.regression_model_names=vec!["EQX".to_string()];
+        assert!(with_regression.validate(&model_names).unwrap_err().contains("regression model (EQX)"));
+    }
+
+    #[test]
+    fn callable_notice_period() {
+        use crate::instrument::Instrument;
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"ZERO".to_string(), value:0.0 })];
+        let dates=vec![0.0,1.0,1.4,1.5,1.75];
+        let (paths,raw_cube)=simulate(&models,&dates,2,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+
+        //The issuer calls on 1.5 with a notice of 0.25 (decision on 1.25): the coupon on 1.5 is still paid along with the fee
+        let callable=crate::callable::Callable
+        {
+            name:"Callable".to_string(),
+            exposure_discount_model_name:"ZERO".to_string(),
+            underlying:FixedCashflows { cashflows:vec![(1.0,1.0),(1.5,1.0),(2.0,1.0)] },
+            call_dates:Vec::new(),
+            exercise_windows:vec![crate::callable::ExerciseWindow { start_date:1.5, end_date:1.5, notice_period:0.25, exercise_frequency:0.1 }],
+            side:"issuer".to_string(),
+            exercise_fee:0.5,
+            valuation:"exercise-right".to_string()
+        };
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),2,1);
+        let (cashflows,exercise_cube,_)=callable.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        assert_eq!(cashflows[0],vec![(1.0,1.0),(1.5,1.0),(1.5,0.5)]);
+        assert_eq!(exercise_cube.get_item_interp(0,0,1.25,false).unwrap().2,1.0);
+        let values:Vec<f64>=(0..dates.len()).map(|d| results_cube.get_item(0,0,d).unwrap()).collect();
+        assert_eq!(values,vec![2.5,1.5,1.5,0.0,0.0]);
+    }
+
+    #[test]
+    fn callable_swap_against_baseline() {
+        use crate::instrument::Instrument;
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),make_hw1f("USD",0.03,0.02)];
+        let dates=vec![0.0,0.5,1.0,1.5];
+        let (paths,raw_cube)=simulate(&models,&dates,1000,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+
+        let callable_json=|valuation:&str,side:&str| format!(r#"{{"name":"callable","exposure_discount_model_name":"USD","underlying":{},"call_dates":[0.5,1.0,1.5],"valuation":"{}","side":"{}"}}"#,
+                                                            make_swap_json(0.03,&vec![0.0,0.5,1.0,1.5,2.0],"FX"),valuation,side);
+        let callable:crate::callable_swap::CallableSwap=serde_json::from_str(&format!(r#"{{"name":"callable","exposure_discount_model_name":"USD","underlying":{},"call_dates":[0.5,1.0,1.5]}}"#,make_swap_json(0.03,&vec![0.0,0.5,1.0,1.5,2.0],"FX"))).unwrap();
+        assert!(callable.validate().is_ok());
+        assert!(serde_json::from_str::<crate::callable_swap::CallableSwap>(&callable_json("exercise-right","buyer")).unwrap().validate().is_err());
+        assert!(serde_json::from_str::<crate::callable_swap::CallableSwap>(&callable_json("reversed-underlying","issuer")).unwrap().validate().is_err());
+        let underlying_value=callable.underlying.get_value(0,0.0,&live_models,&logger);
+
+        //Baseline valuation (before the generic callable): LSM on the negated cashflows, with the negated underlying value as exercise value
+        let mut baseline_cube=Cube::make_empty_cube(vec![0.0,0.5,1.0,1.5,2.0],1000,1);
+        let dsc_model=&live_models["USD"];
+        let (baseline_cashflows,_,_)=crate::lsm::compute_lsm_values(&mut baseline_cube,&live_models,&vec![false,true,true,true,false],
+                                       &mut |date,live_models| callable.underlying.get_models_variables_values(date,&live_models,&logger),
+                                       &mut |scenario,date,live_models| -callable.underlying.get_value(scenario,date,&live_models,&logger),
+                                       &mut |scenario,min_date,max_date,live_models| callable.underlying.get_cashflows(scenario,min_date,max_date,&live_models,&logger).iter().map(|cf| (cf.0,-cf.1)).collect(),
+                                       dsc_model,None,&logger);
+
+        //The default (reversed underlying) valuation keeps the baseline values and cashflows
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),1000,1);
+        let (cashflows,_,_)=callable.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        assert_eq!(cashflows,baseline_cashflows);
+        for s in 0..1000
+        {
+            for dt_idx in 0..dates.len()
+            {
+                assert_eq!(results_cube.get_item(s,0,dt_idx).unwrap(),baseline_cube.get_item(s,0,dt_idx).unwrap());
+            }
+        }
+        //The baseline is the value of the reversed underlying (whatever the exercise policy, up to the Monte Carlo error)
+        assert!((baseline_cube.get_item(0,0,0).unwrap()+underlying_value).abs()<0.2);
+
+        //The exercise right valuation is the underlying plus the exercise right
+        let exercise_right:crate::callable_swap::CallableSwap=serde_json::from_str(&callable_json("exercise-right","holder")).unwrap();
+        assert!(exercise_right.validate().is_ok());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),1000,1);
+        let (_,exercise_cube,_)=exercise_right.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        assert!(results_cube.get_item(0,0,0).unwrap()-underlying_value>0.4);
+        assert!((0..1000).any(|s| exercise_cube.get_item(s,0,1).unwrap()>0.0));
+    }
+
+    #[test]
+    fn callable_swap_out_of_the_money_paths() {
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),make_hw1f("USD",0.03,0.01)];
+        let dates=vec![0.0,0.5,1.0,1.5,2.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1000,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let callable:crate::callable_swap::CallableSwap=serde_json::from_str(&format!(r#"{{"name":"callable","exposure_discount_model_name":"USD","underlying":{},"call_dates":[0.5,1.0,1.5]}}"#,make_swap_json(0.04,&dates,"FX"))).unwrap();
+
+        //Exercise right of a callable swap deep in the money for the holder (no fee, no notice period): the exercise value is the negated underlying value,
+        //and only a handful of paths are in the money for the exercise regression
+        let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| callable.underlying.get_models_variables_values(date,&live_models,&logger);
+        let exercise_value=|scenario:usize,date:f64| -callable.underlying.get_value(scenario,date,&live_models,&logger);
+        let mut values_cube=Cube::make_empty_cube(dates.clone(),1000,1);
+        let (_,exercise_cube,regressions)=crate::lsm::compute_lsm_values(&mut values_cube,&live_models,&vec![false,true,true,true,false],&mut f_models_variables_values,
+                                                                         &mut |scenario,date,_live_models| exercise_value(scenario,date),&mut |_,_,_,_| Vec::new(),
+                                                                         &live_models["USD"],None,&logger);
+
+        //Paths out of the money whose exercise value is above the in-the-money regression were exercised before the LSM left them out (expected shift):
+        //they are now kept alive, with the value of the regression over all the paths
+        let mut num_shifted_paths=0;
+        for dt_idx in 1..4
+        {
+            let variable_values=f_models_variables_values(dates[dt_idx],&live_models);
+            let num_variables=variable_values.len()/1000;
+            for s in 0..1000
+            {
+                let v=exercise_value(s,dates[dt_idx]);
+                if v<=0.0 && v>crate::lsm::get_regression_value(&regressions[dt_idx].exercise_parameters,&variable_values,num_variables,s)
+                {
+                    num_shifted_paths+=1;
+                    assert_eq!(exercise_cube.get_item(s,0,dt_idx).unwrap(),0.0);
+                    assert_eq!(values_cube.get_item(s,0,dt_idx).unwrap(),crate::lsm::get_regression_value(&regressions[dt_idx].pricing_parameters,&variable_values,num_variables,s));
+                }
+            }
+        }
+        assert!(num_shifted_paths>0);
+        //The right is worth about 0 (the old rule exercised these paths at a loss and valued it around -1.4)
+        let right_value=values_cube.get_item(0,0,0).unwrap();
+        assert!(right_value>=0.0 && right_value<0.1);
+    }
+
+    #[test]
+    fn swaption_valuation() {
+        use crate::instrument::Instrument;
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"FXB".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.0)], initial_value:1.0 }),
+                                            make_hw1f("USD",0.03,0.02)];
+        let dates=vec![0.0,0.5,1.0];
+        let (paths,raw_cube)=simulate(&models,&dates,2000,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let payment_dates=vec![1.0,1.5,2.0,2.5,3.0];
+
+        let value_at_0=|payer_or_receiver:&str,settlement:&str,fx_model_name:&str| -> f64 {
+            let swaption:crate::swaption::Swaption=serde_json::from_str(&format!(r#"{{"name":"swaption","exposure_discount_model_name":"USD","payer_or_receiver":"{}","settlement":"{}","expiry_date":1.0,"underlying":{}}}"#,
+                                                                                  payer_or_receiver,settlement,make_swap_json(0.03,&payment_dates,fx_model_name))).unwrap();
+            let mut results_cube=Cube::make_empty_cube(dates.clone(),2000,1);
+            swaption.compute_values(0,&mut results_cube,&live_models,None,&logger);
+            return results_cube.get_item(0,0,0).unwrap();
+        };
+
+        //Payer/receiver parity (Jamshidian): payer-receiver is the value of the forward payer swap on the flat 3% curve
+        let payer=value_at_0("payer","physical","FX");
+        let receiver=value_at_0("receiver","physical","FX");
+        let df=|t:f64| (-0.03*t).exp();
+        let forward_payer_swap=100.0*(df(1.0)-df(3.0))-100.0*0.03*0.5*payment_dates[1..].iter().map(|t| df(*t)).sum::<f64>();
+        assert!(payer>0.0 && receiver>0.0);
+        assert!((payer-receiver-forward_payer_swap).abs()<1e-4);
+
+        //LSM valuation (the Black FX model with 0 volatility makes the swaption ineligible for Jamshidian's formula)
+        let lsm_payer=value_at_0("payer","physical","FXB");
+        assert!((lsm_payer-payer).abs()<0.05*payer);
+
+        //Cash settlement: the par-yield annuity is close to the annuity on the curve near the money
+        let cash_payer=value_at_0("payer","cash","FX");
+        assert!((cash_payer-payer).abs()<0.02*payer);
+
+        //Definitions rejected at load
+        let swaption_json=|payer_or_receiver:&str,settlement:&str,swap_json:&str| format!(r#"{{"name":"swaption","exposure_discount_model_name":"USD","payer_or_receiver":"{}","settlement":"{}","expiry_date":1.0,"underlying":{}}}"#,
+                                                                                       payer_or_receiver,settlement,swap_json);
+        let validate=|json:String| serde_json::from_str::<crate::swaption::Swaption>(&json).unwrap().validate();
+        let swap_json=make_swap_json(0.03,&payment_dates,"FX");
+        assert!(validate(swaption_json("receiver","cash",&swap_json)).is_ok());
+        assert!(validate(swaption_json("straddle","physical",&swap_json)).is_err());
+        assert!(validate(swaption_json("payer","net",&swap_json)).is_err());
+        assert!(validate(swaption_json("payer","physical",&swap_json.replace(r#""is_fixed":true"#,r#""is_fixed":false"#))).is_err());
+        assert!(validate(swaption_json("payer","physical",&swap_json.replace(r#""is_fixed":false"#,r#""is_fixed":true"#))).is_err());
+    }
+
+    #[test]
+    fn caplet_closed_form() {
+        use crate::instrument::Instrument;
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),make_hw1f("USD",0.03,0.01)];
+        let dates=vec![0.0,0.5,1.0,1.5];
+        let (paths,raw_cube)=simulate(&models,&dates,10,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+
+        let cap_json=|cap_or_floor:&str,buy_or_sell:&str,projection_model_name:&str| format!(r#"{{"name":"caplet","cap_or_floor":"{}","buy_or_sell":"{}","notional":100.0,"strike":0.03,
+                "discount_model_name":"USD","projection_model_name":"{}","fx_model_name":"FX","payment_dates":[1.0,1.5]}}"#,cap_or_floor,buy_or_sell,projection_model_name);
+        let validate=|json:String| serde_json::from_str::<crate::cap_floor::CapFloor>(&json).unwrap().validate();
+        assert!(validate(cap_json("cap","sell","USD")).is_ok());
+        assert!(validate(cap_json("cap","long","USD")).is_err());
+        assert!(validate(cap_json("cap","buy","EUR")).is_err());
+        let value_at_0=|cap_or_floor:&str| -> f64 {
+            let cap:crate::cap_floor::CapFloor=serde_json::from_str(&cap_json(cap_or_floor,"buy","USD")).unwrap();
+            let mut results_cube=Cube::make_empty_cube(dates.clone(),10,1);
+            cap.compute_values(0,&mut results_cube,&live_models,None,&logger);
+            return results_cube.get_item(0,0,0).unwrap();
+        };
+
+        //Hull-White caplet: (1+K*tau) puts with strike 1/(1+K*tau) on the zero bond paying on 1.5 and expiring on 1.0 (flat 3% curve, a=1, sigma=1%)
+        let (a,sigma,strike,reset,payment)=(1.0_f64,0.01_f64,0.03_f64,1.0_f64,1.5_f64);
+        let factor=1.0+strike*(payment-reset);
+        let (df_reset,df_payment)=((-0.03*reset).exp(),(-0.03*payment).exp());
+        let sigma_p=sigma/a*(1.0-(-a*(payment-reset)).exp())*((1.0-(-2.0*a*reset).exp())/(2.0*a)).sqrt();
+        let h=(df_payment*factor/df_reset).ln()/sigma_p+0.5*sigma_p;
+        let zero_bond_put=df_reset/factor*math::math::normal_cdf(-h+sigma_p)-df_payment*math::math::normal_cdf(-h);
+        let caplet=100.0*factor*zero_bond_put;
+
+        let cap=value_at_0("cap");
+        let floor=value_at_0("floor");
+        assert!(caplet>0.0);
+        assert!((cap-caplet).abs()<1e-6);
+        //Cap/floor parity: caplet-floorlet is the forward rate agreement
+        assert!((cap-floor-100.0*(df_reset-factor*df_payment)).abs()<1e-6);
+    }
+
+    #[test]
+    fn mtm_reset_notional() {
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),
+                                            Box::new(model::fixed::Fixed { name:"EUR".to_string(), value:0.0 }),
+                                            Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"EURUSD".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.2)], initial_value:1.1 })];
+        let dates=vec![0.0,0.5,1.0,1.5];
+        let (paths,raw_cube)=simulate(&models,&dates,5,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+
+        let swap_json=|reference_fx_model_name:&str| format!(r#"{{"name":"mtm_swap","legs":[
+            {{"notional":100.0,"pay_or_receive":"receive","discount_model_name":"EUR","projection_model_name":"EUR","fx_model_name":"EURUSD","payment_dates":[0.0,0.5,1.0,1.5],"is_fixed":true,"fixed_values":[0.0,0.01,0.01,0.01],
+              "mtm_reset":true,"mtm_reference_fx_model_name":"{}","mtm_reference_discount_model_name":"USD"}}]}}"#,reference_fx_model_name);
+        let invalid_swap:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&swap_json("")).unwrap();
+        assert!(invalid_swap.validate().is_err());
+        let swap:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&swap_json("FX")).unwrap();
+        assert!(swap.validate().is_ok());
+
+        //The notional of every period after the first is reset so that its USD value stays 100*fx(0)
+        let fx_model=live_models.get("EURUSD").unwrap();
+        for s in 0..5
+        {
+            let fx=|date:f64| fx_model.get_value(s,date,0.0,&logger).unwrap();
+            let cashflows=swap.get_cashflows(s,-1.0,10.0,&live_models,&logger);
+            let expected=vec![(0.0,0.0),(0.5,100.0*0.01*fx(0.5)),(1.0,100.0*fx(0.0)/fx(0.5)*0.01*fx(1.0)),(1.5,100.0*fx(0.0)/fx(1.0)*0.01*fx(1.5)),
+                              (0.5,-100.0*(fx(0.0)/fx(0.5)-1.0)*fx(0.5)),(1.0,-100.0*(fx(0.0)/fx(1.0)-fx(0.0)/fx(0.5))*fx(1.0))];
+            assert_eq!(cashflows.len(),expected.len());
+            for (cf,exp) in cashflows.iter().zip(expected.iter())
+            {
+                assert!(cf.0==exp.0 && (cf.1-exp.1).abs()<1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn amortizing_leg() {
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.03 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 })];
+        let dates=vec![0.0,1.0,2.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+
+        let swap_json=|notionals:&str,final_exchange:bool| format!(r#"{{"name":"amortizing_swap","legs":[
+            {{"notional":100.0,"notionals":{},"pay_or_receive":"receive","discount_model_name":"USD","projection_model_name":"USD","fx_model_name":"FX","payment_dates":[0.0,0.5,1.0,1.5,2.0],"is_fixed":true,"fixed_values":[0.0,0.02,0.02,0.02,0.02],
+              "final_exchange":{}}}]}}"#,notionals,final_exchange);
+        let invalid_swap:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&swap_json("[100.0,100.0,50.0]",false)).unwrap();
+        assert!(invalid_swap.validate().is_err());
+        //A leg needs a notional or notionals (a misspelt key is not read as a 0 notional)
+        let missing_notional:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&swap_json("[]",false).replace(r#""notional":"#,r#""notinal":"#)).unwrap();
+        assert!(missing_notional.validate().is_err());
+
+        //Annuity on the amortizing notionals (the first notional goes with the start date)
+        let notionals=vec![0.0,100.0,75.0,50.0,25.0];
+        let df=|t:f64| (-0.03*t).exp();
+        let annuity:f64=(1..5).map(|t| notionals[t]*0.02*df(0.5*t as f64)).sum();
+        let swap:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&swap_json("[0.0,100.0,75.0,50.0,25.0]",false)).unwrap();
+        assert!(swap.validate().is_ok());
+        assert!((swap.get_value(0,0.0,&live_models,&logger)-annuity).abs()<1e-9);
+        let cashflows=swap.get_cashflows(0,0.0,10.0,&live_models,&logger);
+        assert_eq!(cashflows,vec![(0.5,2.0),(1.0,1.5),(1.5,1.0),(2.0,0.5)]);
+
+        //With a final exchange, the notional reductions are received at the start of the periods (an amortizing bond)
+        let bond:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&swap_json("[0.0,100.0,75.0,50.0,25.0]",true)).unwrap();
+        let redemptions:f64=25.0*(df(0.5)+df(1.0)+df(1.5)+df(2.0));
+        assert!((bond.get_value(0,0.0,&live_models,&logger)-annuity-redemptions).abs()<1e-9);
+        assert!((bond.get_value(0,1.25,&live_models,&logger)-(50.0*0.02*df(0.25)+25.0*0.02*df(0.75)+25.0*(df(0.25)+df(0.75)))).abs()<1e-9);
+    }
+
+    #[test]
+    fn overnight_lookback_lockout() {
+        //Deterministic overnight rate (0 volatility Black model), 0 discount rate
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"ON".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.1, sigmas:vec![(0.0,0.0)], initial_value:0.02 })];
+        let dates=vec![0.0,0.15,0.4,1.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let swap:crate::vanilla_swap::VanillaSwap=serde_json::from_str(r#"{"name":"ois","legs":[
+            {"notional":100.0,"pay_or_receive":"receive","discount_model_name":"USD","projection_model_name":"ON","fx_model_name":"FX","payment_dates":[0.0,1.0],"is_fixed":false,"fixed_values":[0.0,0.0],
+             "is_overnight":true,"compounding_step":0.25,"lookback":0.1,"lockout":0.5,"fixings":[[-0.1,0.05]]}]}"#).unwrap();
+        assert!(swap.validate().is_ok());
+        let rate=|d:f64| live_models.get("ON").unwrap().get_value(0,d,0.0,&logger).unwrap();
+
+        //The steps start on 0, 0.25, 0.5 and 0.75: they are observed on -0.1 (fixing), 0.15, 0.4 and 0.4 (lockout from 0.5)
+        let growth=(0.25*(0.05+rate(0.15)+rate(0.4)+rate(0.4))).exp();
+        let cashflows=swap.get_cashflows(0,0.0,10.0,&live_models,&logger);
+        assert_eq!(cashflows.len(),1);
+        assert!(cashflows[0].0==1.0 && (cashflows[0].1-100.0*(growth-1.0)).abs()<1e-9);
+        //Every step is observed on 0.4
+        assert!((swap.get_value(0,0.4,&live_models,&logger)-100.0*(growth-1.0)).abs()<1e-9);
+        //On 0 the first step is observed, the rest of the period is projected (without lockout) over the period shifted by the lookback
+        let forward_growth=(0.25*0.05+0.75*rate(0.0)).exp();
+        assert!((swap.get_value(0,0.0,&live_models,&logger)-100.0*(forward_growth-1.0)).abs()<1e-9);
+        assert!(rate(0.4)>rate(0.15) && rate(0.15)>rate(0.0));
+    }
+
+    #[test]
+    fn floating_fixings() {
+        use crate::instrument::Instrument;
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::fixed::Fixed { name:"LIBOR".to_string(), value:0.03 }),make_hw1f("HW",0.03,0.01)];
+        let dates=vec![0.0,0.25,0.75];
+        let (paths,raw_cube)=simulate(&models,&dates,1,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let swap_json=|payment_dates:&str,overnight:&str,fixings:&str| format!(r#"{{"name":"swap","legs":[{{"notional":100.0,"pay_or_receive":"receive","discount_model_name":"USD","projection_model_name":"LIBOR",
+            "fx_model_name":"FX","payment_dates":{},"is_fixed":false,"fixed_values":[0.0,0.0,0.0]{},"fixings":{}}}]}}"#,payment_dates,overnight,fixings);
+        let swap=|payment_dates:&str,overnight:&str,fixings:&str| serde_json::from_str::<crate::vanilla_swap::VanillaSwap>(&swap_json(payment_dates,overnight,fixings)).unwrap();
+
+        //The fixing of a period that reset before 0 is the one dated on its fixing date: an earlier fixing is not used
+        assert!(swap("[-0.25,0.25,0.75]","","[[-0.3,0.04]]").validate().unwrap_err().contains("no fixing of LIBOR on -0.25"));
+        let seasoned=swap("[-0.25,0.25,0.75]","","[[-0.3,0.05],[-0.2501,0.04]]");
+        assert!(seasoned.validate().is_ok());
+        let cashflows=seasoned.get_cashflows(0,0.0,10.0,&live_models,&logger);
+        assert!(cashflows.len()==2 && (cashflows[0].1-2.0).abs()<1e-9 && (cashflows[1].1-1.5).abs()<1e-9);
+
+        //A period (or an overnight step) fixed on 0 uses the fixing of 0 if it is known, the model otherwise
+        for overnight in ["",r#","is_overnight":true,"compounding_step":0.5"#]
+        {
+            let coupon=|fixings:&str| swap("[0.0,0.5,1.0]",overnight,fixings).get_cashflows(0,0.0,0.5,&live_models,&logger)[0].1;
+            let (projected,fixed)=(coupon("[[-0.1,0.05]]"),coupon("[[-0.1,0.05],[0.0,0.05]]"));
+            if overnight.is_empty()
+            {
+                assert!((projected-1.5).abs()<1e-9 && (fixed-2.5).abs()<1e-9);
+            }
+            else
+            {
+                assert!((projected-100.0*((0.015_f64).exp()-1.0)).abs()<1e-9 && (fixed-100.0*((0.025_f64).exp()-1.0)).abs()<1e-9);
+            }
+        }
+
+        //The fixing history applies to the floating coupons of bonds and to the caplets
+        let history=HashMap::from([("LIBOR".to_string(),vec![(-0.25,0.04)]),("HW".to_string(),vec![(-0.25,0.04)])]);
+        let bond_json=r#"{"name":"bond","buy_or_sell":"buy","coupon_type":"floating","notional":100.0,"payment_dates":[-0.25,0.25,0.75],"discount_model_name":"USD","projection_model_name":"LIBOR","fx_model_name":"FX"}"#;
+        let mut bond:crate::bond::Bond=serde_json::from_str(bond_json).unwrap();
+        assert!(bond.validate().unwrap_err().contains("no fixing of LIBOR on -0.25"));
+        bond.apply_fixing_history(&history);
+        assert!(bond.validate().is_ok());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),1,1);
+        bond.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        assert!((results_cube.get_item(0,0,0).unwrap()-103.5).abs()<1e-9);
+
+        let cap_json=r#"{"name":"cap","cap_or_floor":"cap","buy_or_sell":"buy","notional":100.0,"strike":0.02,"discount_model_name":"HW","projection_model_name":"HW","fx_model_name":"FX","payment_dates":[-0.25,0.25]}"#;
+        let mut cap:crate::cap_floor::CapFloor=serde_json::from_str(cap_json).unwrap();
+        assert!(cap.validate().unwrap_err().contains("no fixing of HW on -0.25"));
+        cap.apply_fixing_history(&history);
+        assert!(cap.validate().is_ok());
+        let (cashflows,_,_)=cap.compute_values(0,&mut Cube::make_empty_cube(dates.clone(),1,1),&live_models,None,&logger);
+        assert!(cashflows[0].len()==1 && cashflows[0][0].0==0.25 && (cashflows[0][0].1-1.0).abs()<1e-9);
+    }
+
+    #[test]
+    fn lsm_out_of_the_money_paths() {
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),
+                                            Box::new(model::black::Black { name:"X".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.3)], initial_value:1.0 })];
+        let dates=vec![0.0,0.5,1.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1000,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let x=|s:usize,date:f64| live_models.get("X").unwrap().get_value(s,date,0.0,&logger).unwrap();
+
+        //Exercise on 0.5 pays max(X-1,0), the instrument pays 2*(X-1) on 1.0: the continuation value 2*(X-1) is negative out of the money,
+        //so the regression over the in-the-money paths is below the (0) exercise value there
+        let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| {
+            let model=live_models.get("X").unwrap();
+            (0..model.cube.num_scenarios).flat_map(|s| model.get_variable_values(s,date,&logger)).collect::<Vec<f64>>()
+        };
+        let mut f_exercise_value=|s:usize,date:f64,_live_models:&HashMap<String,LiveModel>| (x(s,date)-1.0).max(0.0);
+        let mut f_cashflows=|s:usize,min_date:f64,max_date:f64,_live_models:&HashMap<String,LiveModel>| if min_date<1.0 && max_date>=1.0 { vec![(1.0,2.0*(x(s,1.0)-1.0))] } else { Vec::new() };
+        let mut values_cube=Cube::make_empty_cube(vec![0.0,0.5,1.0],1000,1);
+        let (_,exercise_cube,_)=crate::lsm::compute_lsm_values(&mut values_cube,&live_models,&vec![false,true,false],&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,
+                                                               live_models.get("USD").unwrap(),None,&logger);
+
+        //Out of the money paths are not exercised and their continuation value comes from the regression over all the paths
+        let otm_paths:Vec<usize>=(0..1000).filter(|s| x(*s,0.5)<1.0).collect();
+        assert!(otm_paths.len()>100);
+        for s in otm_paths.iter()
+        {
+            assert_eq!(exercise_cube.get_item(*s,0,1).unwrap(),0.0);
+            assert!((values_cube.get_item(*s,0,1).unwrap()-2.0*(x(*s,0.5)-1.0)).abs()<0.1);
+        }
+    }
+
+    #[test]
+    fn equity_dividends_on_path() {
+        use crate::instrument::Instrument;
+        let make_models=|sigma:f64| -> Vec<Box<dyn Model>> { vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                                                  Box::new(model::black::Black { name:"EQ".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,sigma)], initial_value:100.0 })] };
+        let logger=Logger { log_tags:Vec::new() };
+        let dates=vec![0.0,0.25,0.5,0.75,1.0];
+        let num_paths=4000;
+        let models=make_models(0.2);
+        let (paths,raw_cube)=simulate(&models,&dates,num_paths,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let dividends=r#""dividend_yield":0.02,"dividends":[[0.5,2.0]]"#;
+
+        //Forward: the settlement amount on the path is consistent with the value (0 rates)
+        let forward:crate::equity_forward::EquityForward=serde_json::from_str(&format!(r#"{{"name":"fwd","buy_or_sell":"buy","notional":1.0,"strike":90.0,"maturity_date":1.0,
+            "equity_model_name":"EQ","discount_model_name":"USD","fx_model_name":"FX",{}}}"#,dividends)).unwrap();
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),num_paths,1);
+        let (cashflows,_,_)=forward.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let value_at_0=100.0*(-0.02_f64).exp()-2.0-90.0;
+        assert!((results_cube.get_item(0,0,0).unwrap()-value_at_0).abs()<1e-9);
+        let (mean,std_error)=math::math::mean_and_standard_error(&cashflows.iter().map(|c| c[0].1).collect());
+        assert!((mean-value_at_0).abs()<3.0*std_error);
+
+        //European call: the closed form is consistent with the payoff on the path
+        let option_json=|exercise_type:&str,strike:f64,dividends:&str| format!(r#"{{"name":"option","buy_or_sell":"buy","call_or_put":"call","exercise_type":"{}","exercise_frequency":0.1,"notional":1.0,"strike":{},"expiry_date":1.0,
+            "equity_model_name":"EQ","discount_model_name":"USD","fx_model_name":"FX",{}}}"#,exercise_type,strike,dividends);
+        let european:crate::equity_option::EquityOption=serde_json::from_str(&option_json("european",90.0,dividends)).unwrap();
+        assert!(forward.validate().is_ok() && european.validate().is_ok());
+        assert!(serde_json::from_str::<crate::equity_option::EquityOption>(&option_json("bermudan",90.0,dividends)).unwrap().validate().is_err());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),num_paths,1);
+        let (cashflows,_,_)=european.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let (mean,std_error)=math::math::mean_and_standard_error(&cashflows.iter().map(|c| c[0].1).collect());
+        assert!((mean-results_cube.get_item(0,0,0).unwrap()).abs()<3.0*std_error);
+
+        //American call (0 volatility): the spot drops by the dividend on 0.55, so the option is exercised on the last exercise date before it
+        let models=make_models(0.0);
+        let (paths,raw_cube)=simulate(&models,&dates,10,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let american:crate::equity_option::EquityOption=serde_json::from_str(&option_json("american",80.0,r#""dividends":[[0.55,10.0]]"#)).unwrap();
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),10,1);
+        let (cashflows,_,_)=american.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        for c in cashflows.iter()
+        {
+            assert_eq!(c.len(),1);
+            assert!(c[0].0>0.45 && c[0].0<0.55 && (c[0].1-20.0).abs()<1e-9);
+        }
+    }
+
+    #[test]
+    fn bond_flat_spread() {
+        use crate::instrument::Instrument;
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.03 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 })];
+        let dates=vec![0.0,1.25,2.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let bond_json=|redemptions:&str| format!(r#"{{"name":"bond","buy_or_sell":"buy","coupon_type":"fixed","notional":100.0,"coupon_rate":0.04,"payment_dates":[0.0,0.5,1.0,1.5,2.0],
+            "redemptions":{},"discount_model_name":"USD","fx_model_name":"FX","spread_curve":[[1.0,0.01],[10.0,0.01]]}}"#,redemptions);
+        let invalid_bond:crate::bond::Bond=serde_json::from_str(&bond_json("[[1.0,50.0],[2.0,40.0]]")).unwrap();
+        assert!(invalid_bond.validate().is_err());
+        let bond:crate::bond::Bond=serde_json::from_str(&bond_json("[[1.0,50.0],[2.0,50.0]]")).unwrap();
+        assert!(bond.validate().is_ok());
+
+        //Coupons accrue on the outstanding notional, every payment is discounted at 3% plus the 1% spread
+        let df=|t:f64| (-0.04*t).exp();
+        let price=2.0*df(0.5)+2.0*df(1.0)+50.0*df(1.0)+1.0*df(1.5)+1.0*df(2.0)+50.0*df(2.0);
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),1,1);
+        let (cashflows,_,_)=bond.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        assert!((results_cube.get_item(0,0,0).unwrap()-price).abs()<1e-9);
+        assert!((results_cube.get_item(0,0,1).unwrap()-(1.0*df(0.25)+51.0*df(0.75))).abs()<1e-9);
+        assert_eq!(results_cube.get_item(0,0,2).unwrap(),0.0);
+        assert_eq!(cashflows[0].iter().map(|c| c.1).sum::<f64>(),106.0);
+    }
+
+    #[test]
+    fn cds_defaults() {
+        use crate::instrument::Instrument;
+        //Constant intensity of 20% and 0 rates
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::fixed::Fixed { name:"CREDIT".to_string(), value:0.2 })];
+        let dates=vec![0.0,0.5,1.0];
+        let num_paths=4000;
+        let (paths,raw_cube)=simulate(&models,&dates,num_paths,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let cds_json=|payment_dates:&str,seed:u64| format!(r#"{{"name":"cds","buyer_or_seller":"buyer","notional":100.0,"spread":0.1,"recovery_rate":0.4,"payment_dates":{},
+            "discount_model_name":"USD","intensity_model_name":"CREDIT","fx_model_name":"FX","hazard_step":0.01,"default_seed":{}}}"#,payment_dates,seed);
+        let invalid_cds:crate::cds::Cds=serde_json::from_str(&cds_json("[0.0]",1)).unwrap();
+        assert!(invalid_cds.validate().is_err());
+        let cds:crate::cds::Cds=serde_json::from_str(&cds_json("[0.0,0.5,1.0]",1)).unwrap();
+        assert!(cds.validate().is_ok());
+
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),num_paths,1);
+        let (cashflows,_,_)=cds.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let default_dates:Vec<Option<f64>>=cashflows.iter().map(|c| c.iter().find(|cf| cf.1>0.0).map(|cf| cf.0)).collect();
+
+        //The default probability over the year is 1-exp(-0.2)
+        let defaulted:Vec<f64>=default_dates.iter().map(|d| if d.is_some() { 1.0 } else { 0.0 }).collect();
+        let (default_probability,std_error)=math::math::mean_and_standard_error(&defaulted);
+        assert!((default_probability-(1.0-(-0.2_f64).exp())).abs()<3.0*std_error);
+
+        for s in 0..num_paths
+        {
+            match default_dates[s]
+            {
+                //The premium accrued since the start of the period is paid with the protection on the default date, and the value is 0 afterwards
+                Some(d) =>  {
+                                let period_start=if d>0.5 { 0.5 } else { 0.0 };
+                                let expected=if d>0.5 { vec![(0.5,-5.0),(d,-10.0*(d-period_start)),(d,60.0)] } else { vec![(d,-10.0*(d-period_start)),(d,60.0)] };
+                                assert_eq!(cashflows[s].len(),expected.len());
+                                for (cf,exp) in cashflows[s].iter().zip(expected.iter()) { assert!(cf.0==exp.0 && (cf.1-exp.1).abs()<1e-9); }
+                                assert_eq!(results_cube.get_item(s,0,if d>0.5 { 2 } else { 1 }).unwrap(),0.0);
+                            },
+                None    =>  assert_eq!(cashflows[s],vec![(0.5,-5.0),(1.0,-5.0)])
+            };
+        }
+
+        //The value is the expectation of the cashflows
+        let (mean,std_error)=math::math::mean_and_standard_error(&cashflows.iter().map(|c| c.iter().map(|cf| cf.1).sum::<f64>()).collect());
+        assert!((mean-results_cube.get_item(0,0,0).unwrap()).abs()<3.0*std_error);
+
+        //Another seed draws other default dates
+        let other_cds:crate::cds::Cds=serde_json::from_str(&cds_json("[0.0,0.5,1.0]",2)).unwrap();
+        let (other_cashflows,_,_)=other_cds.compute_values(0,&mut Cube::make_empty_cube(dates.clone(),num_paths,1),&live_models,None,&logger);
+        assert!(other_cashflows!=cashflows);
+
+        //Seasoned CDS (protection started at -0.5): the defaults happen after 0 and the premium paid at -0.5 is left out
+        let seasoned_cds:crate::cds::Cds=serde_json::from_str(&cds_json("[-1.0,-0.5,0.5,1.0]",1)).unwrap();
+        let mut seasoned_cube=Cube::make_empty_cube(dates.clone(),num_paths,1);
+        let (seasoned_cashflows,_,_)=seasoned_cds.compute_values(0,&mut seasoned_cube,&live_models,None,&logger);
+        assert!(seasoned_cashflows.iter().all(|c| c.iter().all(|cf| cf.0>0.0)));
+        assert!((0..num_paths).all(|s| seasoned_cube.get_item(s,0,0).unwrap()!=0.0));
+        let seasoned_defaulted:Vec<f64>=seasoned_cashflows.iter().map(|c| if c.iter().any(|cf| cf.1>0.0) { 1.0 } else { 0.0 }).collect();
+        let (default_probability,std_error)=math::math::mean_and_standard_error(&seasoned_defaulted);
+        assert!((default_probability-(1.0-(-0.2_f64).exp())).abs()<3.0*std_error);
+        for s in (0..num_paths).filter(|s| seasoned_defaulted[*s]==0.0)
+        {
+            assert_eq!(seasoned_cashflows[s],vec![(0.5,-10.0),(1.0,-5.0)]);
+        }
+    }
+
+    #[test]
+    fn inflation_swap_fixings() {
+        use crate::instrument::Instrument;
+        //The CPI stays at 100 on the path, its forward grows at 3%-1%
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.03 }),Box::new(model::fixed::Fixed { name:"REAL".to_string(), value:0.01 }),
+                                            Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"CPI".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.0)], initial_value:100.0 })];
+        let dates=vec![0.0,1.0,2.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let swap_json=|inflation_type:&str,payment_dates:&str,lag:f64,interpolation:&str,fixings:&str| format!(r#"{{"name":"inflation","inflation_type":"{}","pay_or_receive":"receive","notional":100.0,"fixed_rate":0.01,
+            "payment_dates":{},"cpi_model_name":"CPI","discount_model_name":"USD","real_discount_model_name":"REAL","fx_model_name":"FX","index_lag":{},"index_interpolation":"{}","fixings":{}}}"#,
+            inflation_type,payment_dates,lag,interpolation,fixings);
+        let forward_index=|month_date:f64| 100.0*(0.02*month_date).exp();
+        let df=|t:f64| (-0.03*t).exp();
+
+        //Zero-coupon swap with a 3 months lag: the base CPI is the fixing of the month starting on -0.25
+        let missing_fixing:crate::inflation_swap::InflationSwap=serde_json::from_str(&swap_json("zero-coupon","[0.0,2.0]",0.25,"flat","[]")).unwrap();
+        assert!(missing_fixing.validate().is_err());
+        let invalid_type:crate::inflation_swap::InflationSwap=serde_json::from_str(&swap_json("zero coupon","[0.0,2.0]",0.0,"flat","[]")).unwrap();
+        assert!(invalid_type.validate().is_err());
+        let mut zero_coupon:crate::inflation_swap::InflationSwap=serde_json::from_str(&swap_json("zero-coupon","[0.0,2.0]",0.25,"flat","[]")).unwrap();
+        zero_coupon.apply_fixing_history(&HashMap::from([("CPI".to_string(),vec![(-0.2466,98.0)])]));
+        assert!(zero_coupon.validate().is_ok());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),1,1);
+        let (cashflows,_,_)=zero_coupon.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let fixed=1.01_f64.powf(2.0)-1.0;
+        assert!((results_cube.get_item(0,0,0).unwrap()-100.0*(forward_index(1.75)/98.0-1.0-fixed)*df(2.0)).abs()<1e-9);
+        assert!(cashflows[0].len()==1 && (cashflows[0][0].1-100.0*(100.0/98.0-1.0-fixed)).abs()<1e-9);
+
+        //Year-on-year swap with a lag of 0.2 and linear interpolation: the reference dates -0.2, 0.8 and 1.8 are 60% into their months
+        let year_on_year:crate::inflation_swap::InflationSwap=serde_json::from_str(&swap_json("year-on-year","[0.0,1.0,2.0]",0.2,"linear","[[-0.25,98.0],[-0.1667,99.0]]")).unwrap();
+        assert!(year_on_year.validate().is_ok());
+        let interpolated=|month_date:f64| forward_index(month_date)+0.6*(forward_index(month_date+1.0/12.0)-forward_index(month_date));
+        let (base,first,second)=(98.6,interpolated(0.75),interpolated(1.75));
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),1,1);
+        let (cashflows,_,_)=year_on_year.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let value=100.0*((first/base-1.0-0.01)*df(1.0)+(second/first-1.0-0.01)*df(2.0));
+        assert!((results_cube.get_item(0,0,0).unwrap()-value).abs()<1e-9);
+        assert!((cashflows[0][0].1-100.0*(100.0/base-1.0-0.01)).abs()<1e-9 && (cashflows[0][1].1-100.0*(-0.01)).abs()<1e-9);
+    }
+
+    #[test]
+    fn cms_convexity_adjustment() {
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.03 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 })];
+        let dates=vec![0.0,1.0,2.0];
+        let (paths,raw_cube)=simulate(&models,&dates,1,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let cms_value=|volatility:f64,cap:&str| -> f64 {
+            let swap:crate::vanilla_swap::VanillaSwap=serde_json::from_str(&format!(r#"{{"name":"cms","legs":[
+                {{"notional":100.0,"pay_or_receive":"receive","discount_model_name":"USD","projection_model_name":"USD","fx_model_name":"FX","payment_dates":[0.0,1.0,2.0],"is_fixed":false,"fixed_values":[0.0,0.0,0.0],
+                  "is_cms":true,"cms_tenor":10.0,"cms_frequency":1.0,"cms_volatility":{}{}}}]}}"#,volatility,cap)).unwrap();
+            assert!(swap.validate().is_ok());
+            return swap.get_value(0,0.0,&live_models,&logger);
+        };
+
+        //The convexity adjustment of a 10y swap rate is positive and outweighs the (negative) timing adjustment of a 1y period: the CMS coupon is above the forward swap rate
+        let forward=cms_value(0.0,"");
+        let adjusted=cms_value(0.25,"");
+        assert!(adjusted>forward);
+        assert!(adjusted-forward<0.01*forward);
+        //A cap at the forward swap rate reduces the adjusted coupon, below the forward swap rate
+        let capped=cms_value(0.25,r#","cms_cap":0.0305"#);
+        assert!(capped<forward);
+    }
+
+    #[test]
+    fn lsm_regression_on_path_state() {
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),
+                                            Box::new(model::black::Black { name:"X".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.3)], initial_value:1.0 })];
+        let dates=vec![0.0,0.5,1.0];
+        let (paths,raw_cube)=simulate(&models,&dates,200,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let state=|s:usize| if live_models.get("X").unwrap().get_value(s,0.5,0.0,&logger).unwrap()>1.0 { 1.0 } else { 0.0 };
+
+        //The regression variable is a 0/1 path state (its square, in the regression basis, is the same column) and the instrument pays 1+state on 1.0
+        let mut f_models_variables_values=|date:f64,_live_models:&HashMap<String,LiveModel>| (0..200).map(|s| if date>0.0 { state(s) } else { 0.0 }).collect::<Vec<f64>>();
+        let mut f_exercise_value=|_s:usize,_date:f64,_live_models:&HashMap<String,LiveModel>| 0.0;
+        let mut f_cashflows=|s:usize,min_date:f64,max_date:f64,_live_models:&HashMap<String,LiveModel>| if min_date<1.0 && max_date>=1.0 { vec![(1.0,1.0+state(s))] } else { Vec::new() };
+        let mut values_cube=Cube::make_empty_cube(dates.clone(),200,1);
+        crate::lsm::compute_lsm_values(&mut values_cube,&live_models,&vec![false,false,false],&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,
+                                       live_models.get("USD").unwrap(),None,&logger);
+        for s in 0..200
+        {
+            assert!((values_cube.get_item(s,0,1).unwrap()-1.0-state(s)).abs()<1e-9);
+        }
+    }
+
+    #[test]
+    fn range_accrual_coupons() {
+        use crate::instrument::Instrument;
+        //The reference rate grows by 25% per quarter from 1%: 1%, 1.25%, 1.5625%, 1.953% on the observation dates
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"REF".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:1.0, sigmas:vec![(0.0,0.0)], initial_value:0.01 })];
+        let dates=vec![0.0,0.25,0.5,0.75,1.0];
+        let (paths,raw_cube)=simulate(&models,&dates,10,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let range_accrual_json=|lower_bound:f64,upper_bound:f64,payment_dates:&str| format!(r#"{{"name":"range","pay_or_receive":"receive","notional":100.0,"coupon_rate":0.04,"lower_bound":{},"upper_bound":{},
+            "reference_model_name":"REF","reference_term":0.25,"observation_frequency":0.25,"payment_dates":{},"discount_model_name":"USD","fx_model_name":"FX","final_redemption":true}}"#,
+            lower_bound,upper_bound,payment_dates);
+        let no_period:crate::range_accrual::RangeAccrual=serde_json::from_str(&range_accrual_json(0.012,0.018,"[]")).unwrap();
+        assert!(no_period.validate().is_err());
+        let inverted_range:crate::range_accrual::RangeAccrual=serde_json::from_str(&range_accrual_json(0.018,0.012,"[0.0,1.0]")).unwrap();
+        assert!(inverted_range.validate().is_err());
+
+        //2 of the 4 observations are in the range: half the coupon is paid with the notional
+        let range_accrual:crate::range_accrual::RangeAccrual=serde_json::from_str(&range_accrual_json(0.012,0.018,"[0.0,1.0]")).unwrap();
+        assert!(range_accrual.validate().is_ok());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),10,1);
+        let (cashflows,_,_)=range_accrual.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        for s in 0..10
+        {
+            assert!(cashflows[s].len()==1 && cashflows[s][0].0==1.0 && (cashflows[s][0].1-102.0).abs()<1e-9);
+            assert!((results_cube.get_item(s,0,0).unwrap()-102.0).abs()<1e-6);
+        }
+    }
+
+    #[test]
+    fn tarn_early_redemption() {
+        use crate::instrument::Instrument;
+        //Coupon rate of 2%+3%
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::fixed::Fixed { name:"LIBOR".to_string(), value:0.03 })];
+        let dates=vec![0.0,1.0,2.0,3.0,4.0];
+        let (paths,raw_cube)=simulate(&models,&dates,10,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let tarn_json=|target:f64,payment_dates:&str| format!(r#"{{"name":"tarn","pay_or_receive":"receive","notional":100.0,"fixed_rate":0.02,"leverage":1.0,"target":{},"payment_dates":{},
+            "projection_model_name":"LIBOR","discount_model_name":"USD","fx_model_name":"FX"}}"#,target,payment_dates);
+        let no_period:crate::tarn::Tarn=serde_json::from_str(&tarn_json(0.12,"[]")).unwrap();
+        assert!(no_period.validate().is_err());
+        let no_target:crate::tarn::Tarn=serde_json::from_str(&tarn_json(0.0,"[0.0,1.0]")).unwrap();
+        assert!(no_target.validate().is_err());
+
+        //The third coupon is capped at 0.12-0.10 and the note is redeemed with it: the value is 0 from then
+        let tarn:crate::tarn::Tarn=serde_json::from_str(&tarn_json(0.12,"[0.0,1.0,2.0,3.0,4.0]")).unwrap();
+        assert!(tarn.validate().is_ok());
+        let mut results_cube=Cube::make_empty_cube(dates.clone(),10,1);
+        let (cashflows,_,_)=tarn.compute_values(0,&mut results_cube,&live_models,None,&logger);
+        let expected=vec![(1.0,5.0),(2.0,5.0),(3.0,102.0)];
+        for s in 0..10
+        {
+            assert_eq!(cashflows[s].len(),expected.len());
+            for (cf,exp) in cashflows[s].iter().zip(expected.iter()) { assert!(cf.0==exp.0 && (cf.1-exp.1).abs()<1e-9); }
+            assert!((results_cube.get_item(s,0,0).unwrap()-112.0).abs()<1e-6);
+            assert_eq!(results_cube.get_item(s,0,3).unwrap(),0.0);
+        }
+
+        //The target is not reached: the notional is paid with the last coupon
+        let tarn:crate::tarn::Tarn=serde_json::from_str(&tarn_json(0.5,"[0.0,1.0,2.0]")).unwrap();
+        let (cashflows,_,_)=tarn.compute_values(0,&mut Cube::make_empty_cube(dates.clone(),10,1),&live_models,None,&logger);
+        assert!(cashflows[0].len()==2 && (cashflows[0][1].1-105.0).abs()<1e-9);
+    }
+
+    #[test]
+    fn asian_option_average() {
+        use crate::instrument::Instrument;
+        //The underlying grows by 25% per quarter from 100: 125, 156.25, 195.3125 and 244.140625 on the averaging dates
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"EQ".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:1.0, sigmas:vec![(0.0,0.0)], initial_value:100.0 })];
+        let dates=vec![0.0,0.25,0.5,0.75,1.0];
+        let (paths,raw_cube)=simulate(&models,&dates,10,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let asian_json=|buy_or_sell:&str,call_or_put:&str,average_type:&str,strike:f64,averaging_dates:&str| format!(r#"{{"name":"asian","buy_or_sell":"{}","call_or_put":"{}","average_type":"{}","notional":2.0,"strike":{},
+            "averaging_dates":{},"expiry_date":1.0,"underlying_model_name":"EQ","discount_model_name":"USD","fx_model_name":"FX"}}"#,buy_or_sell,call_or_put,average_type,strike,averaging_dates);
+        let invalid_type:crate::asian_option::AsianOption=serde_json::from_str(&asian_json("buy","call","harmonic",150.0,"[0.25,0.5,0.75,1.0]")).unwrap();
+        assert!(invalid_type.validate().is_err());
+        let no_date:crate::asian_option::AsianOption=serde_json::from_str(&asian_json("buy","call","arithmetic",150.0,"[]")).unwrap();
+        assert!(no_date.validate().is_err());
+        let late_date:crate::asian_option::AsianOption=serde_json::from_str(&asian_json("buy","call","arithmetic",150.0,"[0.5,1.25]")).unwrap();
+        assert!(late_date.validate().is_err());
+
+        let fixings:Vec<f64>=(1..=4).map(|i| 100.0*1.25_f64.powi(i)).collect();
+        let arithmetic_averages:Vec<f64>=(1..=4).map(|n| fixings[..n].iter().sum::<f64>()/(n as f64)).collect();
+        let geometric_averages:Vec<f64>=(1..=4).map(|n| (fixings[..n].iter().map(|f| f.ln()).sum::<f64>()/(n as f64)).exp()).collect();
+        for (buy_or_sell,call_or_put,average_type,strike,averages,expected_payoff) in [("buy","call","arithmetic",150.0,&arithmetic_averages,2.0*(arithmetic_averages[3]-150.0)),
+                                                                                      ("sell","put","arithmetic",200.0,&arithmetic_averages,-2.0*(200.0-arithmetic_averages[3])),
+                                                                                      ("buy","call","geometric",150.0,&geometric_averages,2.0*(geometric_averages[3]-150.0)),
+                                                                                      ("buy","call","geometric",180.0,&geometric_averages,0.0)]
+        {
+            let option:crate::asian_option::AsianOption=serde_json::from_str(&asian_json(buy_or_sell,call_or_put,average_type,strike,"[0.25,0.5,0.75,1.0]")).unwrap();
+            assert!(option.validate().is_ok());
+
+            //The state is the running average from each averaging date
+            let path=option.get_path(0,&live_models,&logger);
+            assert_eq!(path.states.len(),4);
+            for (i,(state_date,state)) in path.states.iter().enumerate()
+            {
+                assert!(*state_date==0.25*((i+1) as f64) && (state[0]-averages[i]).abs()<1e-9);
+            }
+            assert!((path.get_state(0.6,1)[0]-averages[1]).abs()<1e-9);
+
+            let mut results_cube=Cube::make_empty_cube(dates.clone(),10,1);
+            let (cashflows,_,_)=option.compute_values(0,&mut results_cube,&live_models,None,&logger);
+            for s in 0..10
+            {
+                assert!(cashflows[s].len()==1 && cashflows[s][0].0==1.0 && (cashflows[s][0].1-expected_payoff).abs()<1e-9);
+                assert!((results_cube.get_item(s,0,0).unwrap()-expected_payoff).abs()<1e-6);
+            }
+        }
+        assert!(geometric_averages[3]<arithmetic_averages[3]);
+    }
+
+    #[test]
+    fn barrier_crossing_probability() {
+        use crate::instrument::Instrument;
+        let barrier_json=|knock_type:&str,barriers:&str,brownian_bridge:bool| format!(r#"{{"name":"barrier","buy_or_sell":"buy","call_or_put":"call","knock_type":"{}","notional":1.0,"strike":100.0,{}
+            "expiry_date":1.0,"underlying_model_name":"EQ","discount_model_name":"USD","fx_model_name":"FX","brownian_bridge":{}}}"#,knock_type,barriers,brownian_bridge);
+
+        //The crossing probability over an interval is the expectation, over the bridge value in the middle, of the probability of crossing in either half (no crossing if the barrier is not reached)
+        let normal_grid:Vec<(f64,f64)>=(0..=16000).map(|i| { let z=-8.0+0.001*(i as f64); return (z,0.001*(-0.5*z*z).exp()/(2.0*std::f64::consts::PI).sqrt()); }).collect();
+        for (barriers,start_spot,end_spot,is_beyond_barrier) in [(r#""upper_barrier":130.0,"#,100.0,120.0,(|spot:f64| spot>=130.0) as fn(f64) -> bool),(r#""lower_barrier":80.0,"#,100.0,90.0,|spot:f64| spot<=80.0)]
+        {
+            let option:crate::barrier_option::BarrierOption=serde_json::from_str(&barrier_json("out",barriers,true)).unwrap();
+            let variance=0.04;
+            let probability=option.get_crossing_probability(start_spot,end_spot,variance);
+            assert!(probability>0.05 && probability<0.5);
+            assert_eq!(option.get_crossing_probability(start_spot,end_spot,0.0),0.0);
+            let get_crossed=|z:f64| -> f64 {
+                let middle_spot=(0.5*(start_spot.ln()+end_spot.ln())+0.5*variance.sqrt()*z).exp();
+                let (first,second)=(option.get_crossing_probability(start_spot,middle_spot,0.5*variance),option.get_crossing_probability(middle_spot,end_spot,0.5*variance));
+                return if is_beyond_barrier(middle_spot) { 1.0 } else { 1.0-(1.0-first)*(1.0-second) };
+            };
+            let expected:f64=normal_grid.iter().map(|(z,weight)| weight*get_crossed(*z)).sum();
+            assert!((expected-probability).abs()<1e-4);
+        }
+
+        //On the paths the bridge knocks out more options than the monitoring on the simulation dates only, and a knock-in and a knock-out option make up the vanilla option
+        let models:Vec<Box<dyn Model>>=vec![Box::new(model::fixed::Fixed { name:"USD".to_string(), value:0.0 }),Box::new(model::fixed::Fixed { name:"FX".to_string(), value:1.0 }),
+                                            Box::new(model::black::Black { name:"EQ".to_string(), interpolation:model::model::OutputInterpolation::Linear, r:0.0, sigmas:vec![(0.0,0.2)], initial_value:100.0 })];
+        let dates=vec![0.0,0.25,0.5,0.75,1.0];
+        let num_paths=2000;
+        let (paths,raw_cube)=simulate(&models,&dates,num_paths,1);
+        let live_models=make_live_models(&models,&paths,&raw_cube);
+        let logger=Logger { log_tags:Vec::new() };
+        let get_payoffs=|knock_type:&str,brownian_bridge:bool| -> Vec<f64> {
+            let option:crate::barrier_option::BarrierOption=serde_json::from_str(&barrier_json(knock_type,r#""upper_barrier":130.0,"#,brownian_bridge)).unwrap();
+            assert!(option.validate().is_ok());
+            let (cashflows,_,_)=option.compute_values(0,&mut Cube::make_empty_cube(dates.clone(),num_paths,1),&live_models,None,&logger);
+            return cashflows.iter().map(|c| c[0].1).collect();
+        };
+        let (knock_out,knock_in,discrete_knock_out)=(get_payoffs("out",true),get_payoffs("in",true),get_payoffs("out",false));
+        let mut num_bridge_knocks=0;
+        for s in 0..num_paths
+        {
+            let spot=live_models.get("EQ").unwrap().get_value(s,1.0,0.0,&logger).unwrap();
+            assert!((knock_out[s]+knock_in[s]-(spot-100.0).max(0.0)).abs()<1e-9);
+            assert!(knock_out[s]==discrete_knock_out[s] || knock_out[s]==0.0);
+            if knock_out[s]!=discrete_knock_out[s] { num_bridge_knocks+=1; }
+        }
+        assert!(num_bridge_knocks>0);
+    }
 }
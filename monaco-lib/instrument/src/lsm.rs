@@ -1,11 +1,189 @@
 //use macros::debug;
 use data_cube::data_cube::Cube;
 use model::live_model::LiveModel;
+use model::live_model::InnerSimulator;
 use math::matrix::*;
 use math::linear_regressor::*;
 use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
 use logger::Logger;
 
+/// Regression parameters estimated by the LSM backward pass for a date (along with diagnostics on the priced paths)
+/// 
+/// # Remarks
+/// 
+/// The regression basis is (x_1..x_n,x_1^2..x_n^2,1), where x_i are the model variables values.
+/// An empty parameters vector means that no regression could be estimated (the regression value is then 0).
+/// The exercise boundary is the hyperplane (b_1..b_n,b_0) that best separates exercise from continuation: exercise_value-continuation_value ~ b_1*x_1+..+b_n*x_n+b_0 (fitted on the in-the-money paths)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LsmRegression
+{
+    pub date:f64,
+    /// Continuation value regression (estimated over the in-the-money paths)
+    pub exercise_parameters:Vec<f64>,
+    /// Value regression (estimated over all paths)
+    pub pricing_parameters:Vec<f64>,
+    #[serde(default)]
+    pub is_exercise_date:bool,
+    #[serde(default)]
+    pub num_paths_itm:usize,
+    #[serde(default)]
+    pub exercise_r_squared:f64,
+    #[serde(default)]
+    pub pricing_r_squared:f64,
+    /// Share of the paths exercised on the date
+    #[serde(default)]
+    pub exercise_probability:f64,
+    #[serde(default)]
+    pub exercise_boundary:Vec<f64>
+}
+
+impl LsmRegression
+{
+    fn empty(date:f64) -> LsmRegression
+    {
+        return LsmRegression
+        {
+            date:date,
+            exercise_parameters:Vec::new(),
+            pricing_parameters:Vec::new(),
+            is_exercise_date:false,
+            num_paths_itm:0,
+            exercise_r_squared:0.0,
+            pricing_r_squared:0.0,
+            exercise_probability:0.0,
+            exercise_boundary:Vec::new()
+        };
+    }
+}
+
+/// Builds the regression basis (x,x^2) for a matrix of variable values (rows are scenarios)
+fn get_regression_basis(variable_values:&Vec<f64>,num_rows:usize) -> Vec<f64>
+{
+    let mut variable_values_squared:Vec<f64>=vec![0.0;variable_values.len()];
+    for i in 0..variable_values.len()
+    {
+        variable_values_squared[i]=variable_values[i]*variable_values[i];
+    }
+    return horizontal_add(&variable_values,&variable_values_squared,num_rows);
+}
+
+/// Evaluates a regression for a scenario (returns 0 if no regression parameters are available)
+pub(crate) fn get_regression_value(parameters:&Vec<f64>,variable_values:&Vec<f64>,num_variables:usize,scenario:usize) -> f64
+{
+    if parameters.is_empty()
+    {
+        return 0.0;
+    }
+    let mut x:Vec<f64>=vec![0.0;num_variables*2];
+    for i in 0..num_variables
+    {
+        let val=variable_values[(scenario*num_variables)+i];
+        x[i]=val;
+        x[num_variables+i]=val*val;
+    }
+    let regressor=LinearRegressor { parameters:parameters.clone() };
+    return regressor.get_value(&x);
+}
+
+/// Estimates the regression parameters of y over x (n x m) leaving out the columns of x that are constant or linear combinations of the previous columns
+///
+/// # Remarks
+///
+/// The columns left out (e.g. all the variables on the first simulation date, or the square of a 0/1 path state) get a 0 parameter: this keeps the regression matrix invertible.
+/// A column is left out when its residual, after projecting it on the intercept and on the columns kept, is negligible (Gram-Schmidt).
+fn create_regression(x:&Vec<f64>,y:&Vec<f64>) -> Result<Vec<f64>,String>
+{
+    if y.is_empty()
+    {
+        return Err("No data cases.".to_string());
+    }
+    let num_rows=y.len();
+    let num_variables=x.len()/num_rows;
+    let tolerance:f64=0.000000001;
+    let intercept_norm=1.0/(num_rows as f64).sqrt();
+    let mut basis:Vec<Vec<f64>>=vec![vec![intercept_norm;num_rows]];
+    let mut kept_columns:Vec<usize>=Vec::new();
+    for j in 0..num_variables
+    {
+        let column:Vec<f64>=(0..num_rows).map(|i| x[i*num_variables+j]).collect();
+        let mut residual=column.clone();
+        for b in basis.iter()
+        {
+            let projection:f64=(0..num_rows).map(|i| residual[i]*b[i]).sum();
+            for i in 0..num_rows { residual[i]-=projection*b[i]; }
+        }
+        let column_norm:f64=column.iter().map(|v| v*v).sum();
+        let residual_norm:f64=residual.iter().map(|v| v*v).sum();
+        if residual_norm>tolerance*column_norm && residual_norm>0.0
+        {
+            let scale=1.0/residual_norm.sqrt();
+            basis.push(residual.iter().map(|v| v*scale).collect());
+            kept_columns.push(j);
+        }
+    }
+
+    let mut kept_x:Vec<f64>=Vec::new();
+    for i in 0..num_rows
+    {
+        for j in kept_columns.iter()
+        {
+            kept_x.push(x[i*num_variables+j]);
+        }
+    }
+    let kept_parameters=LinearRegressor::create_regressor(&kept_x,&y)?.parameters;
+
+    let mut parameters:Vec<f64>=vec![0.0;num_variables+1];
+    for (k,j) in kept_columns.iter().enumerate()
+    {
+        parameters[*j]=kept_parameters[k];
+    }
+    parameters[num_variables]=kept_parameters[kept_columns.len()];
+    return Ok(parameters);
+}
+
+/// Estimates the exercise and pricing regressions for a date
+fn estimate_regression(dt:f64,variable_values:&Vec<f64>,num_variables:usize,exercise_values:&Vec<f64>,num_paths_itm:usize,next_values:&Vec<f64>,logger:&Logger) -> LsmRegression
+{
+    //Trim variable values and next values to show only in-the-money paths
+    let mut itm_variable_values:Vec<f64>=vec![0.0;num_paths_itm*num_variables];
+    let mut itm_next_values:Vec<f64>=vec![0.0;num_paths_itm];
+    let mut itm_s:usize=0;
+    for s in 0..exercise_values.len()
+    {
+        if exercise_values[s]>0.0
+        {
+            for v in 0..num_variables
+            {
+                itm_variable_values[itm_s*num_variables+v]=variable_values[s*num_variables+v];
+            }
+            itm_next_values[itm_s]=next_values[s];
+            itm_s+=1;
+        }
+    }
+
+    let mut regression=LsmRegression::empty(dt);
+
+    //Get regression parameters for next_values over variable values
+    if num_paths_itm>0
+    {
+        let regression_variables=get_regression_basis(&itm_variable_values,num_paths_itm);
+        
+        let x_str=math::matrix::display_matrix(&itm_variable_values,itm_next_values.len(),num_variables,false);
+        let y_str=math::matrix::display_matrix(&itm_next_values,itm_next_values.len(),1,false);
+        logger.log(format!("lsm|regression -> date: {} - x: {} - y: {}",dt,x_str,y_str),"lsm");
+        if let Ok(r)=create_regression(&regression_variables,&itm_next_values) { regression.exercise_parameters=r; }
+    }
+    
+    //Pricing regressor
+    if next_values.len()>0
+    {
+        let pricing_regression_variables=get_regression_basis(&variable_values,next_values.len());
+        if let Ok(r)=create_regression(&pricing_regression_variables,&next_values) { regression.pricing_parameters=r; }
+    }
+    return regression;
+}
+
 /// LSM calculator
 /// 
 /// # Arguments
@@ -17,10 +195,17 @@ use logger::Logger;
 /// * `f_exercise_value` - Function that calculates the exercise value for a scenario and date
 /// * `f_cashflows` - Function that returns the *effective* cashflows for a scenario between two dates, with: (start_date,end_date]
 /// * `discount_model` - Model used to discount values and cashflows to the evaluation dates
+/// * `regressions` - Regressions to use instead of estimating them on the paths (one for each date of 'instrument_values_cube')
 /// 
 /// # Remarks
 /// 
 ///  Last date of 'instrument_values_cube' is maturity date, previous dates are (evaluation dates U call/exercise dates)
+/// 
+///  The regressions used for each date are returned along with the cashflows and the exercise cube.
+///  Estimating them on an independent (training) set of paths and passing them back through 'regressions' removes the foresight bias.
+/// 
+///  As in Longstaff-Schwartz, the paths out of the money (exercise value not positive) are never exercised: the continuation value regression is estimated on the paths in the money only,
+///  and its extrapolation can fall below the exercise value elsewhere (e.g. when the continuation value is negative out of the money). Their continuation value is taken from the pricing regression.
 pub fn compute_lsm_values   (   
                                 instrument_values_cube:&mut Cube,
                                 live_models:&HashMap<String,LiveModel>,
@@ -29,12 +214,24 @@ pub fn compute_lsm_values   (
                                 f_exercise_value:&mut impl FnMut(usize,f64,&HashMap<String,LiveModel>) -> f64,
                                 f_cashflows:&mut impl FnMut(usize,f64,f64,&HashMap<String,LiveModel>) -> Vec<(f64,f64)>,
                                 discount_model:&LiveModel,
+                                regressions:Option<&Vec<LsmRegression>>,
                                 logger:&Logger
-                            ) -> (Vec<Vec<(f64,f64)>>,Cube)
+                            ) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
 {
+    if let Some(r)=regressions
+    {
+        if r.len()!=instrument_values_cube.dates.len()
+        {
+            panic!("lsm -> {} regressions provided for {} dates!",r.len(),instrument_values_cube.dates.len());
+        }
+    }
     let mut ret_cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+    let mut ret_regressions:Vec<LsmRegression>=instrument_values_cube.dates.iter().map(|d| LsmRegression::empty(*d)).collect();
     //Prepare the exercise cube (it contains 1 for exercise dates and 0 for non-exercise)
     let mut exercise_values_cube:Cube=Cube::make_empty_cube(instrument_values_cube.dates.clone(), instrument_values_cube.num_scenarios, 1);
+    //Index of the next date of exercise (or maturity) for each scenario: it is updated during the backward pass so that dense exercise grids do not require scanning the following dates
+    let maturity_idx=instrument_values_cube.dates.len()-1;
+    let mut next_exercise_idx:Vec<usize>=vec![maturity_idx;instrument_values_cube.num_scenarios];
     for dt_inv_idx in 0..instrument_values_cube.dates.len()
     {
         //Index is inverted for American Monte Carlo backward pass
@@ -60,31 +257,27 @@ pub fn compute_lsm_values   (
             let num_variables=variable_values.len()/instrument_values_cube.num_scenarios;
             logger.log(format!("lsm -> num_variables: {}",num_variables),"lsm");
 
+            //Exercise values are only needed on exercise dates
             let mut exercise_values:Vec<f64>=vec![0.0;instrument_values_cube.num_scenarios];
             let mut num_paths_itm:usize=0;
-            for s in 0..instrument_values_cube.num_scenarios
+            if exercise_flags[dt_idx]
             {
-                let exercise_value=f_exercise_value(s,dt,&live_models);
-                exercise_values[s]=exercise_value;
-                if exercise_value>0.0
+                for s in 0..instrument_values_cube.num_scenarios
                 {
-                    num_paths_itm+=1;
+                    let exercise_value=f_exercise_value(s,dt,&live_models);
+                    exercise_values[s]=exercise_value;
+                    if exercise_value>0.0
+                    {
+                        num_paths_itm+=1;
+                    }
                 }
             }
             logger.log(format!("lsm -> num_paths_itm: {}",num_paths_itm),"lsm");
 
             for s in 0..instrument_values_cube.num_scenarios
             {
-                //Find date of previous exercise/maturity
-                let mut next_dt_idx=dt_idx;
-                loop
-                {
-                        next_dt_idx+=1;
-                        if next_dt_idx==instrument_values_cube.dates.len()-1 || exercise_values_cube.get_item(s, 0, next_dt_idx).unwrap()>0.0
-                        {
-                            break;
-                        }
-                }
+                //Date of next exercise/maturity
+                let next_dt_idx=next_exercise_idx[s];
                 let next_dt=instrument_values_cube.dates[next_dt_idx];
 
                 //logger.log(format!("lsm -> next_dt_idx: {}, next_dt: {}, dt_idx: {}, dt: {}",next_dt_idx,next_dt,dt_idx,dt),"lsm");
@@ -104,103 +297,66 @@ pub fn compute_lsm_values   (
                 date_cashflows.push(cashflows);
             }
 
-            //Trim variable values and next values to show only in-the-money paths
-            let mut itm_variable_values:Vec<f64>=vec![0.0;num_paths_itm*num_variables];
-            let mut itm_next_values:Vec<f64>=vec![0.0;num_paths_itm];
-            let mut itm_s:usize=0;
-            for s in 0..exercise_values.len()
-            {
-                if exercise_values[s]>0.0
-                {
-                    for v in 0..num_variables
-                    {
-                        itm_variable_values[itm_s]=variable_values[s*num_variables+v];
-                    }
-                    itm_next_values[itm_s]=next_values[s];
-                    itm_s+=1;
-                }
-            }
-
-            //Get regression parameters for next_values over variable values
-            let mut itm_variable_values_squared:Vec<f64>=vec![0.0;itm_variable_values.len()];
-            for i in 0..itm_variable_values.len()
+            //Regression parameters are either estimated on the current paths or taken from a previous (training) pass
+            let regression=match regressions
             {
-                itm_variable_values_squared[i]=itm_variable_values[i]*itm_variable_values[i];
-            }
-            let regression_variables=horizontal_add(&itm_variable_values,&itm_variable_values_squared,instrument_values_cube.num_scenarios);
-            
-            let x_str=math::matrix::display_matrix(&itm_variable_values,itm_next_values.len(),num_variables,false);
-            let y_str=math::matrix::display_matrix(&itm_next_values,itm_next_values.len(),1,false);
-            logger.log(format!("lsm|regression -> date: {} - x: {} - y: {}",dt,x_str,y_str),"lsm");
-            let regressor=LinearRegressor::create_regressor(&regression_variables,&itm_next_values);
-            
-            //Pricing regressor
-            let mut variable_values_squared:Vec<f64>=vec![0.0;variable_values.len()];
-            for i in 0..variable_values.len()
-            {
-                variable_values_squared[i]=variable_values[i]*variable_values[i];
-            }
-            let pricing_regression_variables=horizontal_add(&variable_values,&variable_values_squared,instrument_values_cube.num_scenarios);
-            let pricing_regressor=LinearRegressor::create_regressor(&pricing_regression_variables,&next_values);
+                Some(r) =>  r[dt_idx].clone(),
+                None    =>  estimate_regression(dt,&variable_values,num_variables,&exercise_values,num_paths_itm,&next_values,&logger)
+            };
 
             for s in 0..instrument_values_cube.num_scenarios
             {
-                //Calculate value from regression
-                let regression_value:f64=match regressor
-                {
-                    Ok(ref r)   =>  {
-                                        let mut x:Vec<f64>=vec![0.0;num_variables*2];
-                                        for i in 0..num_variables
-                                        {
-                                            let val=variable_values[(s*num_variables)+i];
-                                            x[i]=val;
-                                            x[num_variables+i]=val*val;
-                                        }
-                                        r.get_value(&x)
-                                },
-                    Err(_)  =>  0.0
-                };
                 if exercise_flags[dt_idx]
                 {
+                    //Calculate value from regression
+                    let regression_value:f64=get_regression_value(&regression.exercise_parameters,&variable_values,num_variables,s);
                     //let exercise_value=f_exercise_value(s,dt,&live_models);
                     let exercise_value:f64=exercise_values[s];
-                    if num_paths_itm>0 && exercise_value>regression_value
+                    //Paths out of the money are never exercised: the exercise regression is only estimated on the paths in the money and is not meaningful outside them
+                    if !regression.exercise_parameters.is_empty() && exercise_value>0.0 && exercise_value>regression_value
                     {
                         logger.log(format!("lsm -> early exercise - scenario: {}, dt_idx: {}, dt: {}, exercise_value: {}, regression_value: {}",s,dt_idx,dt,exercise_value,regression_value),"lsm");
-                        let _=exercise_values_cube.set_item(s, 0, dt_idx, 1.0);
-                        for d in dt_idx+1..exercise_values_cube.dates.len()
-                        {
-                            let _=exercise_values_cube.set_item(s, 0, d, 0.0);
-                            let _=instrument_values_cube.set_item(s, 0, d, 0.0);
-                        }
+                        next_exercise_idx[s]=dt_idx;
                         let _=instrument_values_cube.set_item(s, 0, dt_idx, exercise_value);
                     }
                     else
                     {
                         logger.log(format!("lsm -> no early exercise - scenario: {}, dt_idx: {}, dt: {}, exercise_value: {}, regression_value: {}",s,dt_idx,dt,exercise_value,regression_value),"lsm");
-                        let _=instrument_values_cube.set_item(s, 0, dt_idx, regression_value);
+                        //The continuation value of the paths out of the money comes from the pricing regression (estimated on all the paths)
+                        let continuation_value=if exercise_value>0.0 { regression_value } else { get_regression_value(&regression.pricing_parameters,&variable_values,num_variables,s) };
+                        let _=instrument_values_cube.set_item(s, 0, dt_idx, continuation_value);
                     }
                 }
                 else
                 {
-                    let pricing_regression_value:f64=match pricing_regressor
-                    {
-                        Ok(ref r)   =>  {
-                                            let mut x:Vec<f64>=vec![0.0;num_variables*2];
-                                            for i in 0..num_variables
-                                            {
-                                                let val=variable_values[(s*num_variables)+i];
-                                                x[i]=val;
-                                                x[num_variables+i]=val*val;
-                                            }
-                                            r.get_value(&x)
-                                    },
-                        Err(_)  =>  0.0
-                    };
+                    let pricing_regression_value:f64=get_regression_value(&regression.pricing_parameters,&variable_values,num_variables,s);
                     logger.log(format!("lsm -> not an exercise date - scenario: {}, dt_idx: {}, dt: {}, pricing_regression_value: {}",s,dt_idx,dt,pricing_regression_value),"lsm");
                     let _=instrument_values_cube.set_item(s, 0, dt_idx, pricing_regression_value);
                 }
             }
+
+            //Diagnostics (on the priced paths)
+            let mut regression=regression;
+            regression.is_exercise_date=exercise_flags[dt_idx];
+            regression.num_paths_itm=num_paths_itm;
+            let fitted_next_values:Vec<f64>=(0..next_values.len()).map(|s| get_regression_value(&regression.pricing_parameters,&variable_values,num_variables,s)).collect();
+            regression.pricing_r_squared=math::math::r_squared(&next_values,&fitted_next_values);
+            if exercise_flags[dt_idx]
+            {
+                let itm_next_values:Vec<f64>=(0..next_values.len()).filter(|s| exercise_values[*s]>0.0).map(|s| next_values[s]).collect();
+                let itm_fitted_values:Vec<f64>=(0..next_values.len()).filter(|s| exercise_values[*s]>0.0).map(|s| get_regression_value(&regression.exercise_parameters,&variable_values,num_variables,s)).collect();
+                regression.exercise_r_squared=math::math::r_squared(&itm_next_values,&itm_fitted_values);
+
+                //The boundary is fitted on the same (in-the-money) paths as the exercise regression
+                let itm_paths:Vec<usize>=(0..next_values.len()).filter(|s| exercise_values[*s]>0.0).collect();
+                let itm_variable_values:Vec<f64>=itm_paths.iter().flat_map(|s| variable_values[s*num_variables..(s+1)*num_variables].to_vec()).collect();
+                let exercise_premia:Vec<f64>=itm_paths.iter().map(|s| exercise_values[*s]-get_regression_value(&regression.exercise_parameters,&variable_values,num_variables,*s)).collect();
+                if !itm_paths.is_empty()
+                {
+                    if let Ok(r)=create_regression(&itm_variable_values,&exercise_premia) { regression.exercise_boundary=r; }
+                }
+            }
+            ret_regressions[dt_idx]=regression;
         }
         if date_cashflows.len()!=0
         {
@@ -218,10 +374,267 @@ pub fn compute_lsm_values   (
         }
     }
 
+    //Exercise cube (the exercise date of a scenario is final only at the end of the backward pass): values after exercise are 0
+    for s in 0..instrument_values_cube.num_scenarios
+    {
+        if next_exercise_idx[s]<maturity_idx
+        {
+            let _=exercise_values_cube.set_item(s, 0, next_exercise_idx[s], 1.0);
+            for d in next_exercise_idx[s]+1..instrument_values_cube.dates.len()
+            {
+                let _=instrument_values_cube.set_item(s, 0, d, 0.0);
+            }
+        }
+    }
+
+    //Exercise probabilities
+    for dt_idx in 0..exercise_values_cube.dates.len()
+    {
+        let mut num_exercised:usize=0;
+        for s in 0..exercise_values_cube.num_scenarios
+        {
+            if exercise_values_cube.get_item(s, 0, dt_idx).unwrap()>0.0 { num_exercised+=1; }
+        }
+        ret_regressions[dt_idx].exercise_probability=(num_exercised as f64)/(exercise_values_cube.num_scenarios as f64);
+    }
+
     //Make sure that the cashflows are in the correct order
     for s in 0..instrument_values_cube.num_scenarios
     {
         ret_cashflows[s].sort_by(|a, b| if a.0>b.0 {std::cmp::Ordering::Greater} else {if a.0<b.0 {std::cmp::Ordering::Less} else {std::cmp::Ordering::Equal}});
     }
-    return (ret_cashflows,exercise_values_cube);
-}
\ No newline at end of file
+    return (ret_cashflows,exercise_values_cube,ret_regressions);
+}
+
+/// Lower and upper bounds for the value of an instrument with an exercise policy
+/// 
+/// # Remarks
+/// 
+/// The lower bound is the value of the exercise policy, the upper bound is the Andersen-Broadie dual bound
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LsmBounds
+{
+    pub name:String,
+    pub lower_bound:f64,
+    pub lower_bound_std_error:f64,
+    pub upper_bound:f64,
+    pub upper_bound_std_error:f64,
+    pub duality_gap:f64,
+    pub duality_gap_std_error:f64,
+    pub num_outer_paths:usize,
+    pub num_inner_paths:usize
+}
+
+/// Values along a set of paths when following an exercise policy (all values are discounted to the first date)
+struct PolicyPaths
+{
+    /// Discount factors from the first date (dates x scenarios)
+    discount_factors:Vec<Vec<f64>>,
+    /// Cashflows paid up to each date, irrespective of exercise (dates x scenarios)
+    accrued_cashflows:Vec<Vec<f64>>,
+    /// Exercise values (dates x scenarios, 0 on non-exercise dates)
+    exercise_values:Vec<Vec<f64>>,
+    /// Whether the policy exercises on a date if the instrument is still alive (dates x scenarios)
+    exercise_decisions:Vec<Vec<bool>>,
+    /// Value of following the policy from the first date
+    values:Vec<f64>
+}
+
+/// Follows an exercise policy along a set of paths
+/// 
+/// # Remarks
+/// 
+/// The first date is the starting date (no exercise is possible on it), the last date is the maturity date
+fn follow_policy    (
+                        dates:&[f64],
+                        exercise_flags:&[bool],
+                        regressions:&[LsmRegression],
+                        num_scenarios:usize,
+                        live_models:&HashMap<String,LiveModel>,
+                        f_models_variables_values:&mut impl FnMut(f64,&HashMap<String,LiveModel>) -> Vec<f64>,
+                        f_exercise_value:&mut impl FnMut(usize,f64,&HashMap<String,LiveModel>) -> f64,
+                        f_cashflows:&mut impl FnMut(usize,f64,f64,&HashMap<String,LiveModel>) -> Vec<(f64,f64)>,
+                        discount_model_name:&str,
+                        logger:&Logger
+                    ) -> PolicyPaths
+{
+    let discount_model=match live_models.get(discount_model_name)
+    {
+        Some(m)     =>  m,
+        None        =>  panic!("lsm -> non-existent discount model ({})!",discount_model_name)
+    };
+    let num_dates=dates.len();
+    let mut policy=PolicyPaths
+    {
+        discount_factors:vec![vec![1.0;num_scenarios];num_dates],
+        accrued_cashflows:vec![vec![0.0;num_scenarios];num_dates],
+        exercise_values:vec![vec![0.0;num_scenarios];num_dates],
+        exercise_decisions:vec![vec![false;num_scenarios];num_dates],
+        values:vec![0.0;num_scenarios]
+    };
+    let mut alive:Vec<bool>=vec![true;num_scenarios];
+
+    for k in 1..num_dates
+    {
+        let prev_dt=dates[k-1];
+        let dt=dates[k];
+        for s in 0..num_scenarios
+        {
+            let prev_df=policy.discount_factors[k-1][s];
+            let mut accrued=policy.accrued_cashflows[k-1][s];
+            let cashflows=f_cashflows(s,prev_dt,dt,&live_models);
+            for c in 0..cashflows.len()
+            {
+                let r=discount_model.get_value(s, prev_dt, cashflows[c].0-prev_dt,&logger).unwrap();
+                accrued+=prev_df*cashflows[c].1*(-r*(cashflows[c].0-prev_dt)).exp();
+            }
+            if alive[s]
+            {
+                policy.values[s]+=accrued-policy.accrued_cashflows[k-1][s];
+            }
+            policy.accrued_cashflows[k][s]=accrued;
+
+            let r=discount_model.get_value(s, prev_dt, dt-prev_dt,&logger).unwrap();
+            policy.discount_factors[k][s]=prev_df*(-r*(dt-prev_dt)).exp();
+        }
+
+        if exercise_flags[k] && k<num_dates-1
+        {
+            let variable_values:Vec<f64>=f_models_variables_values(dt,&live_models);
+            let num_variables=variable_values.len()/num_scenarios;
+            for s in 0..num_scenarios
+            {
+                let exercise_value=f_exercise_value(s,dt,&live_models);
+                let regression_value=get_regression_value(&regressions[k].exercise_parameters,&variable_values,num_variables,s);
+                policy.exercise_values[k][s]=exercise_value*policy.discount_factors[k][s];
+                policy.exercise_decisions[k][s]=!regressions[k].exercise_parameters.is_empty() && exercise_value>0.0 && exercise_value>regression_value;
+                if alive[s] && policy.exercise_decisions[k][s]
+                {
+                    alive[s]=false;
+                    policy.values[s]+=policy.exercise_values[k][s];
+                }
+            }
+        }
+    }
+    logger.log(format!("lsm|follow_policy -> start date: {}, scenarios: {}, exercised: {}",dates[0],num_scenarios,alive.iter().filter(|a| !**a).count()),"lsm");
+    return policy;
+}
+
+/// Andersen-Broadie dual bounds calculator
+/// 
+/// # Arguments
+/// 
+/// * `name` - Instrument name (used for reporting)
+/// * `dates` - Dates used for the LSM calculation (see 'compute_lsm_values')
+/// * `exercise_flags` - Vec of flags that indicate the callability of a date
+/// * `regressions` - Regressions that define the exercise policy (as returned by 'compute_lsm_values')
+/// * `live_models` - Dictionary of live models of the outer simulation
+/// * `simulator` - Nested simulation engine
+/// * `num_outer_paths` - Number of outer paths (the first paths of the outer simulation are used)
+/// * `num_inner_paths` - Number of inner paths for each continuation value estimate
+/// * `f_models_variables_values` - Function that returns the variable values for a date
+/// * `f_exercise_value` - Function that calculates the exercise value for a scenario and date
+/// * `f_cashflows` - Function that returns the *effective* cashflows for a scenario between two dates, with: (start_date,end_date]
+/// * `discount_model_name` - Name of the model used to discount values and cashflows
+/// 
+/// # Remarks
+/// 
+/// The lower bound is the value of following the exercise policy on the outer paths.
+/// It is a true lower bound only if the regressions were estimated on paths independent of the outer ones (e.g. training paths):
+/// with regressions estimated in-sample the policy has foresight of the outer paths, and the lower bound is biased upwards.
+/// The martingale used for the upper bound is built from the continuation values of the policy, estimated by nested simulation on every exercise date.
+/// The standard errors do not include the noise of the inner estimates.
+pub fn compute_dual_bounds  (
+                                name:&str,
+                                dates:&Vec<f64>,
+                                exercise_flags:&Vec<bool>,
+                                regressions:&Vec<LsmRegression>,
+                                live_models:&HashMap<String,LiveModel>,
+                                simulator:&dyn InnerSimulator,
+                                num_outer_paths:usize,
+                                num_inner_paths:usize,
+                                f_models_variables_values:&mut impl FnMut(f64,&HashMap<String,LiveModel>) -> Vec<f64>,
+                                f_exercise_value:&mut impl FnMut(usize,f64,&HashMap<String,LiveModel>) -> f64,
+                                f_cashflows:&mut impl FnMut(usize,f64,f64,&HashMap<String,LiveModel>) -> Vec<(f64,f64)>,
+                                discount_model_name:&str,
+                                logger:&Logger
+                            ) -> LsmBounds
+{
+    let num_scenarios=match live_models.get(discount_model_name)
+    {
+        Some(m)     =>  m.cube.num_scenarios,
+        None        =>  panic!("lsm -> non-existent discount model ({})!",discount_model_name)
+    };
+    let num_outer_paths=num_outer_paths.min(num_scenarios);
+    let num_dates=dates.len();
+
+    logger.log(format!("lsm|dual -> name: {}, outer paths: {}, inner paths: {}",name,num_outer_paths,num_inner_paths),"lsm");
+    let outer=follow_policy(&dates, &exercise_flags, &regressions, num_scenarios, &live_models, f_models_variables_values, f_exercise_value, f_cashflows, discount_model_name, &logger);
+
+    //The martingale is observed on the first date, on the exercise dates, and on the maturity date
+    let mut points:Vec<usize>=vec![0];
+    for k in 1..num_dates-1
+    {
+        if exercise_flags[k] { points.push(k); }
+    }
+    points.push(num_dates-1);
+
+    let mut lower_samples:Vec<f64>=vec![0.0;num_outer_paths];
+    let mut upper_samples:Vec<f64>=vec![0.0;num_outer_paths];
+    let mut gap_samples:Vec<f64>=vec![0.0;num_outer_paths];
+    for s in 0..num_outer_paths
+    {
+        let mut martingale:f64=0.0;
+        let mut prev_continuation:f64=0.0;
+        let mut upper:f64=f64::NEG_INFINITY;
+        for p in 0..points.len()
+        {
+            let k=points[p];
+            //Value of stopping on this date
+            let stopping_value=outer.accrued_cashflows[k][s]+outer.exercise_values[k][s];
+            //Value of continuing (estimated on inner paths)
+            let continuation=if k==num_dates-1
+            {
+                stopping_value
+            }
+            else
+            {
+                let inner_dates:Vec<f64>=dates[k..].to_vec();
+                let mut inner_value:f64=0.0;
+                simulator.with_inner_paths(&live_models, s, &inner_dates, num_inner_paths, &logger, &mut |inner_live_models| {
+                    let inner=follow_policy(&dates[k..], &exercise_flags[k..], &regressions[k..], num_inner_paths, &inner_live_models, &mut *f_models_variables_values, &mut *f_exercise_value, &mut *f_cashflows, discount_model_name, &logger);
+                    inner_value=math::math::mean_and_standard_error(&inner.values).0;
+                });
+                outer.accrued_cashflows[k][s]+outer.discount_factors[k][s]*inner_value
+            };
+            let policy_value=if outer.exercise_decisions[k][s] { stopping_value } else { continuation };
+            if p>0
+            {
+                martingale+=policy_value-prev_continuation;
+                upper=upper.max(stopping_value-martingale);
+            }
+            prev_continuation=continuation;
+        }
+        lower_samples[s]=outer.values[s];
+        upper_samples[s]=upper;
+        gap_samples[s]=upper-outer.values[s];
+        logger.log(format!("lsm|dual -> scenario: {}, lower: {}, upper: {}",s,lower_samples[s],upper_samples[s]),"lsm");
+    }
+
+    let (lower_bound,lower_bound_std_error)=math::math::mean_and_standard_error(&lower_samples);
+    let (upper_bound,upper_bound_std_error)=math::math::mean_and_standard_error(&upper_samples);
+    let (duality_gap,duality_gap_std_error)=math::math::mean_and_standard_error(&gap_samples);
+
+    return LsmBounds
+    {
+        name:name.to_string(),
+        lower_bound:lower_bound,
+        lower_bound_std_error:lower_bound_std_error,
+        upper_bound:upper_bound,
+        upper_bound_std_error:upper_bound_std_error,
+        duality_gap:duality_gap,
+        duality_gap_std_error:duality_gap_std_error,
+        num_outer_paths:num_outer_paths,
+        num_inner_paths:num_inner_paths
+    };
+}
@@ -0,0 +1,241 @@
+use crate::lsm::{LsmRegression,compute_lsm_values};
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use logger::Logger;
+
+/// Cashflows and path state of a scenario of a path-dependent instrument
+pub struct PathData
+{
+    /// Cashflows (date,amount in the base ccy)
+    pub cashflows:Vec<(f64,f64)>,
+    /// State of the path (date from which the state applies,state variables), in date order
+    pub states:Vec<(f64,Vec<f64>)>,
+    /// Value of exercising on each exercise date reached by the path (date,amount in the base ccy): empty for instruments that can not be exercised
+    pub exercise_values:Vec<(f64,f64)>,
+    /// Date of the last cashflow of the path (e.g. an early redemption date): the value is 0 from this date
+    pub end_date:f64
+}
+
+impl PathData
+{
+    /// State of the path on 'date' (padded with 0 up to 'num_states' variables, all 0 before the first state)
+    pub fn get_state(&self,date:f64,num_states:usize) -> Vec<f64>
+    {
+        let mut state:Vec<f64>=vec![0.0;num_states];
+        for (state_date,values) in self.states.iter()
+        {
+            if *state_date>date
+            {
+                break;
+            }
+            for i in 0..values.len().min(num_states)
+            {
+                state[i]=values[i];
+            }
+        }
+        return state;
+    }
+
+    /// Value of exercising on 'date' (None if the path can not be exercised on that date)
+    pub fn get_exercise_value(&self,date:f64) -> Option<f64>
+    {
+        let date_threshold=0.0000001;
+        if date>=self.end_date
+        {
+            return None;
+        }
+        return self.exercise_values.iter().find(|e| (e.0-date).abs()<date_threshold).map(|e| e.1);
+    }
+}
+
+/// Regression variables of all the scenarios on a date: the model variables followed by the path state
+fn get_regression_variables(paths:&Vec<PathData>,date:f64,live_models:&HashMap<String,LiveModel>,f_variables:&dyn Fn(usize,f64,&HashMap<String,LiveModel>) -> Vec<f64>) -> Vec<f64>
+{
+    let num_states=paths.iter().map(|p| p.states.iter().map(|st| st.1.len()).max().unwrap_or(0)).max().unwrap_or(0);
+    let mut values:Vec<f64>=Vec::new();
+    for s in 0..paths.len()
+    {
+        values.append(&mut f_variables(s,date,&live_models));
+        values.append(&mut paths[s].get_state(date,num_states));
+    }
+    return values;
+}
+
+fn get_discount_model<'a>(live_models:&'a HashMap<String,LiveModel<'a>>,discount_model_name:&String) -> &'a LiveModel<'a>
+{
+    return match live_models.get(discount_model_name)
+    {
+        Some(m)     =>  m,
+        None        =>  panic!("Path-dependent instrument refers to non-existent model ({})!",discount_model_name)
+    };
+}
+
+/// Values the paths on 'dates' and returns them along with the exercise cube and the regressions: the value is 0 from the end date of each path
+///
+/// # Remarks
+///
+/// On the dates flagged in 'exercise_flags' the holder exercises (by LSM) when the exercise value of the path is higher than the continuation value.
+fn value_paths(dates:&Vec<f64>,exercise_flags:&Vec<bool>,num_scenarios:usize,live_models:&HashMap<String,LiveModel>,discount_model_name:&String,regressions:Option<&Vec<LsmRegression>>,
+                f_path:&dyn Fn(usize,&HashMap<String,LiveModel>) -> PathData,f_variables:&dyn Fn(usize,f64,&HashMap<String,LiveModel>) -> Vec<f64>,
+                logger:&Logger) -> (Vec<PathData>,Cube,Cube,Vec<LsmRegression>)
+{
+    let discount_model=get_discount_model(live_models,discount_model_name);
+    let paths:Vec<PathData>=(0..num_scenarios).map(|s| f_path(s,&live_models)).collect();
+    let mut values_cube:Cube=Cube::make_empty_cube(dates.clone(), num_scenarios, 1);
+
+    let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| get_regression_variables(&paths,date,&live_models,f_variables);
+    let mut f_exercise_value=|scenario:usize,date:f64,_live_models:&HashMap<String,LiveModel>| paths[scenario].get_exercise_value(date).unwrap_or(0.0);
+    let mut f_cashflows=|scenario:usize,min_date:f64,max_date:f64,_live_models:&HashMap<String,LiveModel>| paths[scenario].cashflows.iter().filter(|cf| cf.0>min_date && cf.0<=max_date).map(|cf| *cf).collect::<Vec<(f64,f64)>>();
+    let (_,exercise_cube,r)=compute_lsm_values(&mut values_cube,&live_models,&exercise_flags,&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,discount_model,regressions,&logger);
+
+    for s in 0..num_scenarios
+    {
+        for dt_idx in 0..dates.len()
+        {
+            if dates[dt_idx]>=paths[s].end_date
+            {
+                let _=values_cube.set_item(s, 0, dt_idx, 0.0);
+            }
+        }
+    }
+    return (paths,values_cube,exercise_cube,r);
+}
+
+/// Values the exercise right of the counterparty on the paths: the exercise value of the right is the value without exercise minus the exercise value
+fn value_counterparty_exercise(dates:&Vec<f64>,exercise_flags:&Vec<bool>,paths:&Vec<PathData>,values_cube:&Cube,live_models:&HashMap<String,LiveModel>,discount_model_name:&String,
+                                regressions:Option<&Vec<LsmRegression>>,f_variables:&dyn Fn(usize,f64,&HashMap<String,LiveModel>) -> Vec<f64>,logger:&Logger) -> (Cube,Cube,Vec<LsmRegression>)
+{
+    let discount_model=get_discount_model(live_models,discount_model_name);
+    let mut option_values_cube:Cube=Cube::make_empty_cube(dates.clone(), paths.len(), 1);
+
+    let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| get_regression_variables(&paths,date,&live_models,f_variables);
+    let mut f_exercise_value=|scenario:usize,date:f64,_live_models:&HashMap<String,LiveModel>| match paths[scenario].get_exercise_value(date)
+    {
+        Some(v)     =>  values_cube.get_item_interp(scenario, 0, date, true).unwrap().2-v,
+        None        =>  0.0
+    };
+    let mut f_cashflows=|_scenario:usize,_min_date:f64,_max_date:f64,_live_models:&HashMap<String,LiveModel>| Vec::new();
+    let (_,exercise_cube,r)=compute_lsm_values(&mut option_values_cube,&live_models,&exercise_flags,&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,discount_model,regressions,&logger);
+    return (option_values_cube,exercise_cube,r);
+}
+
+/// Populates the values of a path-dependent instrument by regressing the discounted future cashflows on the model variables and on the path state
+///
+/// # Arguments
+///
+/// * `start` - Series of the instrument in 'result_cube'
+/// * `result_cube` - Results cube
+/// * `maturity_date` - Date of the last cashflow (the value is 0 from this date)
+/// * `exercise_dates` - Dates on which the instrument can be exercised (empty if it can not be exercised)
+/// * `exercise_sign` - 1 if the holder of the position owns the exercise right, -1 if the counterparty does
+/// * `live_models` - Live models of the pricing paths
+/// * `training_live_models` - Live models of the training paths (if set, the regressions are estimated on these paths)
+/// * `discount_model_name` - Model used to discount the cashflows
+/// * `f_path` - Function that returns the cashflows, the state, and the exercise values of a scenario
+/// * `f_variables` - Function that returns the model variables of a scenario on a date
+/// * `logger` - Logger
+///
+/// # Remarks
+///
+/// The regression variables are the model variables and the path state (e.g. the running coupon total): the state makes the regression conditional on the history of the path.
+/// When the holder owns the exercise right, the exercise is part of the LSM (the exercise values that are not positive are never exercised).
+/// When the counterparty owns it, the value without exercise is obtained first; the exercise right is then valued by LSM as an option whose exercise value is the difference between the value without exercise and the exercise value (as for callable instruments).
+/// The cashflows after the exercise date are replaced by the exercise value, paid on that date.
+pub fn compute_path_dependent_values(start:usize,result_cube:&mut Cube,maturity_date:f64,exercise_dates:&Vec<f64>,exercise_sign:f64,live_models:&HashMap<String,LiveModel>,
+                                        training_live_models:Option<&HashMap<String,LiveModel>>,discount_model_name:&String,f_path:&dyn Fn(usize,&HashMap<String,LiveModel>) -> PathData,
+                                        f_variables:&dyn Fn(usize,f64,&HashMap<String,LiveModel>) -> Vec<f64>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+{
+    let date_threshold=0.0000001;
+    let mut dates:Vec<f64>=result_cube.dates.iter().filter(|d| **d<maturity_date).map(|d| *d).collect();
+    for exercise_date in exercise_dates.iter().filter(|d| **d<maturity_date)
+    {
+        if dates.iter().all(|d| (d-exercise_date).abs()>date_threshold)
+        {
+            dates.push(*exercise_date);
+        }
+    }
+    dates.sort_by(|a, b| a.partial_cmp(&b).unwrap());
+    dates.push(maturity_date);
+    let exercise_flags:Vec<bool>=dates.iter().map(|d| *d<maturity_date && exercise_dates.iter().any(|e| (e-d).abs()<date_threshold)).collect();
+    let is_exercisable=exercise_flags.iter().any(|f| *f);
+
+    //The regressions are estimated on the training paths (if any) and then applied to the pricing paths
+    let no_exercise_flags:Vec<bool>=vec![false;dates.len()];
+    let is_counterparty_exercise=is_exercisable && exercise_sign<0.0;
+    let holder_exercise_flags=if is_counterparty_exercise { &no_exercise_flags } else { &exercise_flags };
+    let (training_regressions,training_exercise_regressions)=match training_live_models
+    {
+        Some(training_models)   =>  {
+                                        let num_training_scenarios=get_discount_model(training_models,discount_model_name).cube.num_scenarios;
+                                        let (training_paths,training_values_cube,_,r)=value_paths(&dates,holder_exercise_flags,num_training_scenarios,&training_models,discount_model_name,None,f_path,f_variables,&logger);
+                                        let exercise_r=if is_counterparty_exercise
+                                        {
+                                            let (_,_,er)=value_counterparty_exercise(&dates,&exercise_flags,&training_paths,&training_values_cube,&training_models,discount_model_name,None,f_variables,&logger);
+                                            Some(er)
+                                        } else { None };
+                                        (Some(r),exercise_r)
+                                    },
+        None                    =>  (None,None)
+    };
+
+    let (paths,values_cube,holder_exercise_cube,regressions)=value_paths(&dates,holder_exercise_flags,result_cube.num_scenarios,&live_models,discount_model_name,training_regressions.as_ref(),f_path,f_variables,&logger);
+
+    //The counterparty exercise right is valued as an option on the instrument without exercise (as for callable instruments)
+    let (option_values_cube,exercise_cube,regressions)=if is_counterparty_exercise
+    {
+        let (o,e,r)=value_counterparty_exercise(&dates,&exercise_flags,&paths,&values_cube,&live_models,discount_model_name,training_exercise_regressions.as_ref(),f_variables,&logger);
+        (Some(o),e,r)
+    } else { (None,holder_exercise_cube,regressions) };
+
+    let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+    for s in 0..result_cube.num_scenarios
+    {
+        let mut exercise_date:Option<f64>=None;
+        if is_exercisable
+        {
+            for dt_idx in 0..exercise_cube.dates.len()
+            {
+                if exercise_cube.get_item(s, 0, dt_idx).unwrap()>0.0
+                {
+                    exercise_date=Some(exercise_cube.dates[dt_idx]);
+                    break;
+                }
+            }
+        }
+        cashflows.push(match exercise_date
+        {
+            Some(ex_dt) =>  {
+                                let mut cf:Vec<(f64,f64)>=paths[s].cashflows.iter().filter(|c| c.0<=ex_dt).map(|c| *c).collect();
+                                cf.push((ex_dt,paths[s].get_exercise_value(ex_dt).unwrap()));
+                                cf
+                            },
+            None        =>  paths[s].cashflows.clone()
+        });
+
+        //With a counterparty exercise, the position is the instrument without exercise minus the exercise right, up to the exercise date
+        for dt_idx in 0..result_cube.dates.len()
+        {
+            let date=result_cube.dates[dt_idx];
+            let v=match exercise_date
+            {
+                Some(ex_dt) if date>ex_dt                               =>  0.0,
+                _ if date>=maturity_date || date>=paths[s].end_date     =>  0.0,
+                _                                                       =>  {
+                                                                                let value=values_cube.get_item_interp(s, 0, date, true).unwrap().2;
+                                                                                match &option_values_cube
+                                                                                {
+                                                                                    Some(o) =>  value-o.get_item_interp(s, 0, date, true).unwrap().2,
+                                                                                    None    =>  value
+                                                                                }
+                                                                            }
+            };
+            let _=result_cube.set_item(s, start, dt_idx, v);
+        }
+    }
+    if !is_exercisable
+    {
+        return (cashflows,Cube::make_empty_cube(vec![0.0], 1, 1),regressions);
+    }
+    return (cashflows,exercise_cube,regressions);
+}
@@ -0,0 +1,143 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::path_dependent::{PathData,compute_path_dependent_values};
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_observation_frequency() -> f64
+{
+    return 1.0/52.0;
+}
+
+/// Range accrual note implementation
+/// (the coupon of each period accrues only on the observation dates on which the reference rate is in the range)
+#[derive(Serialize, Deserialize)]
+pub struct RangeAccrual
+{
+    pub name: String,
+    pub pay_or_receive:String,
+    pub notional:f64,
+    pub coupon_rate:f64,
+    pub lower_bound:f64,
+    pub upper_bound:f64,
+    pub reference_model_name:String,
+    pub reference_term:f64,
+    #[serde(default="default_observation_frequency")]
+    pub observation_frequency:f64,
+    pub payment_dates:Vec<f64>,
+    pub discount_model_name:String,
+    pub fx_model_name:String,
+    #[serde(default)]
+    pub final_redemption:bool
+}
+
+impl RangeAccrual
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.pay_or_receive.as_str()
+        {
+            "receive"   =>  1.0,
+            "pay"       =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'pay' or 'receive'!",&self.name,&self.pay_or_receive)
+        };
+    }
+
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payment_dates.len()<2
+        {
+            return Err(format!("Range accrual {} must have a start date and at least one payment date!",&self.name));
+        }
+        if self.observation_frequency<=0.0
+        {
+            return Err(format!("Range accrual {} has an invalid observation frequency ({})!",&self.name,self.observation_frequency));
+        }
+        if self.lower_bound>self.upper_bound
+        {
+            return Err(format!("Range accrual {} has a lower bound ({}) above its upper bound ({})!",&self.name,self.lower_bound,self.upper_bound));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Observation dates of a period (from the start date, every 'observation_frequency', excluding the end date)
+    fn get_observation_dates(&self,period_start:f64,period_end:f64) -> Vec<f64>
+    {
+        if self.observation_frequency<=0.0
+        {
+            panic!("Instrument {} has an invalid observation frequency ({})!",&self.name,self.observation_frequency);
+        }
+        let tolerance:f64=0.0000001;
+        let mut dates:Vec<f64>=Vec::new();
+        let mut date=period_start;
+        while date<period_end-tolerance
+        {
+            dates.push(date);
+            date+=self.observation_frequency;
+        }
+        return dates;
+    }
+
+    /// Coupons of a scenario; the state is the share of the observations of the current period that were in the range
+    fn get_path(&self,scenario:usize,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> PathData
+    {
+        let sign=self.get_sign();
+        let reference_model=self.get_live_model(live_models,&self.reference_model_name);
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let mut cashflows:Vec<(f64,f64)>=Vec::new();
+        let mut states:Vec<(f64,Vec<f64>)>=Vec::new();
+        for t in 1..self.payment_dates.len()
+        {
+            let period_start=self.payment_dates[t-1];
+            let period_end=self.payment_dates[t];
+            let observation_dates=self.get_observation_dates(period_start,period_end);
+            let mut num_in_range:usize=0;
+            for observation_date in observation_dates.iter()
+            {
+                let rate=reference_model.get_value(scenario,*observation_date,self.reference_term,&logger).unwrap();
+                if rate>=self.lower_bound && rate<=self.upper_bound
+                {
+                    num_in_range+=1;
+                }
+                states.push((*observation_date,vec![(num_in_range as f64)/(observation_dates.len() as f64)]));
+            }
+            let accrual=if observation_dates.is_empty() { 0.0 } else { (num_in_range as f64)/(observation_dates.len() as f64) };
+            let mut amount=self.notional*self.coupon_rate*(period_end-period_start)*accrual;
+            if self.final_redemption && t==self.payment_dates.len()-1
+            {
+                amount+=self.notional;
+            }
+            let fx=fx_model.get_value(scenario,period_end,0.0,&logger).unwrap();
+            cashflows.push((period_end,sign*amount*fx));
+        }
+        return PathData { cashflows:cashflows, states:states, exercise_values:Vec::new(), end_date:*self.payment_dates.last().unwrap() };
+    }
+}
+
+impl Instrument for RangeAccrual
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        logger.log(format!("range-accrual|compute_values -> name: {}, starting regression...",self.name),"instrument");
+        let f_path=|scenario:usize,live_models:&HashMap<String,LiveModel>| self.get_path(scenario,&live_models,&logger);
+        let f_variables=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| self.get_live_model(live_models,&self.reference_model_name).get_variable_values(scenario,date,&logger);
+        return compute_path_dependent_values(start,result_cube,*self.payment_dates.last().unwrap(),&Vec::new(),1.0,&live_models,training_live_models,&self.discount_model_name,&f_path,&f_variables,&logger);
+    }
+}
@@ -0,0 +1,644 @@
+use model::live_model::LiveModel;
+use std::collections::HashMap;
+use logger::Logger;
+
+/// Token of a payoff script
+#[derive(Clone, Debug, PartialEq)]
+enum Token
+{
+    Number(f64),
+    Text(String),
+    Identifier(String),
+    Symbol(String)
+}
+
+/// Expression of a payoff script (booleans are numbers: 0 is false, anything else is true)
+#[derive(Clone, Debug)]
+pub enum Expression
+{
+    Number(f64),
+    Text(String),
+    Variable(String),
+    Unary(String,Box<Expression>),
+    Binary(String,Box<Expression>,Box<Expression>),
+    Call(String,Vec<Expression>)
+}
+
+/// Statement of a payoff script
+#[derive(Clone, Debug)]
+pub enum Statement
+{
+    /// Sets a path variable
+    Assign(String,Expression),
+    /// Pays an amount on the event date
+    Pay(Expression),
+    /// Makes the event date an exercise date, with the given exercise value
+    Exercise(Expression),
+    /// Terminates the instrument after the event (e.g. an automatic early redemption)
+    Terminate,
+    If(Expression,Vec<Statement>,Vec<Statement>)
+}
+
+const KEYWORDS:[&str;10]=["if","then","else","end","pay","exercise","terminate","and","or","not"];
+
+fn tokenize(source:&str) -> Result<Vec<Token>,String>
+{
+    let chars:Vec<char>=source.chars().collect();
+    let mut tokens:Vec<Token>=Vec::new();
+    let mut i:usize=0;
+    while i<chars.len()
+    {
+        let c=chars[i];
+        if c.is_whitespace() || c==';'
+        {
+            i+=1;
+        }
+        else if c.is_ascii_digit() || (c=='.' && i+1<chars.len() && chars[i+1].is_ascii_digit())
+        {
+            let start=i;
+            while i<chars.len() && (chars[i].is_ascii_digit() || chars[i]=='.' || chars[i]=='e' || chars[i]=='E' || ((chars[i]=='-' || chars[i]=='+') && (chars[i-1]=='e' || chars[i-1]=='E')))
+            {
+                i+=1;
+            }
+            let text:String=chars[start..i].iter().collect();
+            match text.parse::<f64>()
+            {
+                Ok(v)   =>  tokens.push(Token::Number(v)),
+                Err(_)  =>  return Err(format!("invalid number ({})",text))
+            };
+        }
+        else if c.is_alphabetic() || c=='_'
+        {
+            let start=i;
+            while i<chars.len() && (chars[i].is_alphanumeric() || chars[i]=='_')
+            {
+                i+=1;
+            }
+            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+        }
+        else if c=='"'
+        {
+            let start=i+1;
+            i+=1;
+            while i<chars.len() && chars[i]!='"'
+            {
+                i+=1;
+            }
+            if i>=chars.len()
+            {
+                return Err("unterminated string".to_string());
+            }
+            tokens.push(Token::Text(chars[start..i].iter().collect()));
+            i+=1;
+        }
+        else
+        {
+            let two:String=chars[i..(i+2).min(chars.len())].iter().collect();
+            if ["<=",">=","==","!="].contains(&two.as_str())
+            {
+                tokens.push(Token::Symbol(two));
+                i+=2;
+            }
+            else if "+-*/^()<>=,".contains(c)
+            {
+                tokens.push(Token::Symbol(c.to_string()));
+                i+=1;
+            }
+            else
+            {
+                return Err(format!("unexpected character ({})",c));
+            }
+        }
+    }
+    return Ok(tokens);
+}
+
+/// Recursive descent parser of payoff scripts
+struct Parser
+{
+    tokens:Vec<Token>,
+    position:usize
+}
+
+impl Parser
+{
+    fn peek(&self) -> Option<&Token>
+    {
+        return self.tokens.get(self.position);
+    }
+
+    fn is_symbol(&self,symbol:&str) -> bool
+    {
+        return matches!(self.peek(),Some(Token::Symbol(s)) if s==symbol);
+    }
+
+    fn is_keyword(&self,keyword:&str) -> bool
+    {
+        return matches!(self.peek(),Some(Token::Identifier(s)) if s==keyword);
+    }
+
+    fn expect_symbol(&mut self,symbol:&str) -> Result<(),String>
+    {
+        if !self.is_symbol(symbol)
+        {
+            return Err(format!("expected '{}' at token {} ({:?})",symbol,self.position,self.peek()));
+        }
+        self.position+=1;
+        return Ok(());
+    }
+
+    fn expect_keyword(&mut self,keyword:&str) -> Result<(),String>
+    {
+        if !self.is_keyword(keyword)
+        {
+            return Err(format!("expected '{}' at token {} ({:?})",keyword,self.position,self.peek()));
+        }
+        self.position+=1;
+        return Ok(());
+    }
+
+    /// Parses statements up to the end of the script or to one of the 'terminators' keywords
+    fn parse_statements(&mut self,terminators:&[&str]) -> Result<Vec<Statement>,String>
+    {
+        let mut statements:Vec<Statement>=Vec::new();
+        while let Some(token)=self.peek()
+        {
+            if terminators.iter().any(|t| self.is_keyword(t))
+            {
+                break;
+            }
+            let statement=match token.clone()
+            {
+                Token::Identifier(id) if id=="if"           =>  {
+                                                                    self.position+=1;
+                                                                    let condition=self.parse_expression()?;
+                                                                    self.expect_keyword("then")?;
+                                                                    let then_statements=self.parse_statements(&["else","end"])?;
+                                                                    let else_statements=if self.is_keyword("else")
+                                                                    {
+                                                                        self.position+=1;
+                                                                        self.parse_statements(&["end"])?
+                                                                    } else { Vec::new() };
+                                                                    self.expect_keyword("end")?;
+                                                                    Statement::If(condition,then_statements,else_statements)
+                                                                },
+                Token::Identifier(id) if id=="pay"          =>  {
+                                                                    self.position+=1;
+                                                                    self.expect_symbol("(")?;
+                                                                    let amount=self.parse_expression()?;
+                                                                    self.expect_symbol(")")?;
+                                                                    Statement::Pay(amount)
+                                                                },
+                Token::Identifier(id) if id=="exercise"     =>  {
+                                                                    self.position+=1;
+                                                                    self.expect_symbol("(")?;
+                                                                    let amount=self.parse_expression()?;
+                                                                    self.expect_symbol(")")?;
+                                                                    Statement::Exercise(amount)
+                                                                },
+                Token::Identifier(id) if id=="terminate"    =>  {
+                                                                    self.position+=1;
+                                                                    Statement::Terminate
+                                                                },
+                Token::Identifier(id) if !KEYWORDS.contains(&id.as_str())   =>  {
+                                                                    self.position+=1;
+                                                                    self.expect_symbol("=")?;
+                                                                    Statement::Assign(id,self.parse_expression()?)
+                                                                },
+                t                                           =>  return Err(format!("unexpected token {} ({:?})",self.position,t))
+            };
+            statements.push(statement);
+        }
+        return Ok(statements);
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression,String>
+    {
+        let mut left=self.parse_and()?;
+        while self.is_keyword("or")
+        {
+            self.position+=1;
+            left=Expression::Binary("or".to_string(),Box::new(left),Box::new(self.parse_and()?));
+        }
+        return Ok(left);
+    }
+
+    fn parse_and(&mut self) -> Result<Expression,String>
+    {
+        let mut left=self.parse_not()?;
+        while self.is_keyword("and")
+        {
+            self.position+=1;
+            left=Expression::Binary("and".to_string(),Box::new(left),Box::new(self.parse_not()?));
+        }
+        return Ok(left);
+    }
+
+    fn parse_not(&mut self) -> Result<Expression,String>
+    {
+        if self.is_keyword("not")
+        {
+            self.position+=1;
+            return Ok(Expression::Unary("not".to_string(),Box::new(self.parse_not()?)));
+        }
+        return self.parse_comparison();
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression,String>
+    {
+        let left=self.parse_sum()?;
+        for op in ["<","<=",">",">=","==","!="].iter()
+        {
+            if self.is_symbol(op)
+            {
+                self.position+=1;
+                return Ok(Expression::Binary(op.to_string(),Box::new(left),Box::new(self.parse_sum()?)));
+            }
+        }
+        return Ok(left);
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression,String>
+    {
+        let mut left=self.parse_product()?;
+        while self.is_symbol("+") || self.is_symbol("-")
+        {
+            let op=if self.is_symbol("+") { "+" } else { "-" };
+            self.position+=1;
+            left=Expression::Binary(op.to_string(),Box::new(left),Box::new(self.parse_product()?));
+        }
+        return Ok(left);
+    }
+
+    fn parse_product(&mut self) -> Result<Expression,String>
+    {
+        let mut left=self.parse_unary()?;
+        while self.is_symbol("*") || self.is_symbol("/")
+        {
+            let op=if self.is_symbol("*") { "*" } else { "/" };
+            self.position+=1;
+            left=Expression::Binary(op.to_string(),Box::new(left),Box::new(self.parse_unary()?));
+        }
+        return Ok(left);
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression,String>
+    {
+        if self.is_symbol("-")
+        {
+            self.position+=1;
+            return Ok(Expression::Unary("-".to_string(),Box::new(self.parse_unary()?)));
+        }
+        return self.parse_power();
+    }
+
+    fn parse_power(&mut self) -> Result<Expression,String>
+    {
+        let base=self.parse_primary()?;
+        if self.is_symbol("^")
+        {
+            self.position+=1;
+            return Ok(Expression::Binary("^".to_string(),Box::new(base),Box::new(self.parse_unary()?)));
+        }
+        return Ok(base);
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression,String>
+    {
+        let token=match self.peek()
+        {
+            Some(t)     =>  t.clone(),
+            None        =>  return Err("unexpected end of script".to_string())
+        };
+        self.position+=1;
+        return match token
+        {
+            Token::Number(v)                =>  Ok(Expression::Number(v)),
+            Token::Text(t)                  =>  Ok(Expression::Text(t)),
+            Token::Symbol(s) if s=="("      =>  {
+                                                    let e=self.parse_expression()?;
+                                                    self.expect_symbol(")")?;
+                                                    Ok(e)
+                                                },
+            //'if' is also the conditional function if(condition,value if true,value if false)
+            Token::Identifier(id) if !KEYWORDS.contains(&id.as_str()) || (id=="if" && self.is_symbol("("))   =>  {
+                                                    if !self.is_symbol("(")
+                                                    {
+                                                        return Ok(Expression::Variable(id));
+                                                    }
+                                                    self.position+=1;
+                                                    let mut arguments:Vec<Expression>=Vec::new();
+                                                    if !self.is_symbol(")")
+                                                    {
+                                                        arguments.push(self.parse_expression()?);
+                                                        while self.is_symbol(",")
+                                                        {
+                                                            self.position+=1;
+                                                            arguments.push(self.parse_expression()?);
+                                                        }
+                                                    }
+                                                    self.expect_symbol(")")?;
+                                                    Ok(Expression::Call(id,arguments))
+                                                },
+            t                               =>  Err(format!("unexpected token {} ({:?})",self.position-1,t))
+        };
+    }
+}
+
+/// Parses a payoff script
+///
+/// # Remarks
+///
+/// Statements (optionally separated by ';'): `x = expression`, `pay(expression)`, `exercise(expression)`, `terminate`, `if condition then statements [else statements] end`.
+/// Expressions: numbers, "model names", variables, + - * / ^, comparisons (< <= > >= == !=), and, or, not, and function calls.
+pub fn parse_script(source:&str) -> Result<Vec<Statement>,String>
+{
+    let mut parser=Parser { tokens:tokenize(source)?, position:0 };
+    let statements=parser.parse_statements(&[])?;
+    return Ok(statements);
+}
+
+/// Whether a script contains an 'exercise' statement
+pub fn has_exercise(statements:&Vec<Statement>) -> bool
+{
+    return statements.iter().any(|s| match s
+    {
+        Statement::Exercise(_)      =>  true,
+        Statement::If(_,t,e)        =>  has_exercise(t) || has_exercise(e),
+        _                           =>  false
+    });
+}
+
+/// Names of the variables set by a script
+pub fn get_assigned_variables(statements:&Vec<Statement>,names:&mut Vec<String>) -> ()
+{
+    for statement in statements.iter()
+    {
+        match statement
+        {
+            Statement::Assign(name,_)   =>  if !names.contains(name) { names.push(name.clone()); },
+            Statement::If(_,t,e)        =>  {
+                                                get_assigned_variables(t,names);
+                                                get_assigned_variables(e,names);
+                                            },
+            _                           =>  ()
+        }
+    }
+}
+
+/// Minimum and maximum number of arguments of a function (None if the function does not exist)
+fn get_arity(function:&str) -> Option<(usize,usize)>
+{
+    return match function
+    {
+        "spot"|"abs"|"exp"|"ln"|"sqrt"  =>  Some((1,1)),
+        "rate"|"df"                     =>  Some((2,2)),
+        "fixing"|"if"                   =>  Some((3,3)),
+        "min"|"max"                     =>  Some((1,usize::MAX)),
+        _                               =>  None
+    };
+}
+
+fn check_arguments(function:&String,arguments:&Vec<Expression>) -> Result<(),String>
+{
+    return match get_arity(function)
+    {
+        None                                                        =>  Err(format!("unknown function ({})",function)),
+        Some((min,max)) if min==max && arguments.len()!=min         =>  Err(format!("{} expects {} arguments",function,min)),
+        Some((min,_)) if arguments.len()<min                        =>  Err(format!("{} expects at least {} arguments",function,min)),
+        _                                                           =>  Ok(())
+    };
+}
+
+/// Whether the first argument of a function is a model name
+fn has_model_argument(function:&str) -> bool
+{
+    return ["spot","rate","df","fixing"].contains(&function);
+}
+
+fn check_expression(expression:&Expression,names:&Vec<String>,model_names:&Vec<String>) -> Result<(),String>
+{
+    return match expression
+    {
+        Expression::Number(_)           =>  Ok(()),
+        Expression::Text(t)             =>  Err(format!("unexpected text (\"{}\"): model names in quotes are only the first argument of spot, rate, df and fixing",t)),
+        Expression::Variable(name)      =>  if name=="date" || name=="period" || names.contains(name) { Ok(()) } else { Err(format!("undefined variable ({})",name)) },
+        Expression::Unary(_,e)          =>  check_expression(e,names,model_names),
+        Expression::Binary(_,l,r)       =>  {
+                                                check_expression(l,names,model_names)?;
+                                                check_expression(r,names,model_names)
+                                            },
+        Expression::Call(f,arguments)   =>  {
+                                                check_arguments(f,arguments)?;
+                                                let mut values=arguments.iter();
+                                                if has_model_argument(f)
+                                                {
+                                                    match values.next()
+                                                    {
+                                                        Some(Expression::Text(t)) if !model_names.contains(t)   =>  return Err(format!("non-existent model ({})",t)),
+                                                        Some(Expression::Text(_))                               =>  (),
+                                                        _                                                       =>  return Err(format!("{} expects a model name in quotes as first argument",f))
+                                                    };
+                                                }
+                                                values.try_for_each(|a| check_expression(a,names,model_names))
+                                            }
+    };
+}
+
+/// Checks a parsed script before it is evaluated: the functions exist and have the right number of arguments, the model names are in quotes and are loaded ('model_names'),
+/// the variables are parameters or are set by one of the scripts ('variable_names'), and the parameters are not assigned
+///
+/// # Remarks
+///
+/// Whether a variable is set before it is used on a path is only known when the events are evaluated.
+pub fn check_script(statements:&Vec<Statement>,parameter_names:&Vec<String>,variable_names:&Vec<String>,model_names:&Vec<String>) -> Result<(),String>
+{
+    let names:Vec<String>=parameter_names.iter().chain(variable_names.iter()).cloned().collect();
+    for statement in statements.iter()
+    {
+        match statement
+        {
+            Statement::Assign(name,e)   =>  {
+                                                if parameter_names.contains(name) || name=="date" || name=="period"
+                                                {
+                                                    return Err(format!("{} can not be assigned",name));
+                                                }
+                                                check_expression(e,&names,model_names)?;
+                                            },
+            Statement::Pay(e)           =>  check_expression(e,&names,model_names)?,
+            Statement::Exercise(e)      =>  check_expression(e,&names,model_names)?,
+            Statement::Terminate        =>  (),
+            Statement::If(c,t,e)        =>  {
+                                                check_expression(c,&names,model_names)?;
+                                                check_script(t,parameter_names,variable_names,model_names)?;
+                                                check_script(e,parameter_names,variable_names,model_names)?;
+                                            }
+        };
+    }
+    return Ok(());
+}
+
+/// State of a path while its events are evaluated
+pub struct ScriptContext<'a>
+{
+    pub scenario:usize,
+    /// Date of the event
+    pub date:f64,
+    /// Time from the previous date of the event schedule (0 on the first date)
+    pub period:f64,
+    pub live_models:&'a HashMap<String,LiveModel<'a>>,
+    pub parameters:&'a HashMap<String,f64>,
+    pub variables:HashMap<String,f64>,
+    /// Amounts paid on the event date
+    pub payments:Vec<f64>,
+    /// Exercise value on the event date (if an 'exercise' statement was reached)
+    pub exercise_value:Option<f64>,
+    pub terminated:bool,
+    pub logger:&'a Logger
+}
+
+impl<'a> ScriptContext<'a>
+{
+    fn get_model(&self,name:&String) -> Result<&'a LiveModel<'a>,String>
+    {
+        return match self.live_models.get(name)
+        {
+            Some(m)     =>  Ok(m),
+            None        =>  Err(format!("non-existent model ({})",name))
+        };
+    }
+
+    fn get_text(&self,expression:&Expression) -> Result<String,String>
+    {
+        return match expression
+        {
+            Expression::Text(t)     =>  Ok(t.clone()),
+            _                       =>  Err("expected a model name in quotes".to_string())
+        };
+    }
+
+    /// Value of 'model' for 'term' on 'date' (fixings can not be taken after the event date)
+    fn get_model_value(&self,model:&String,date:f64,term:f64) -> Result<f64,String>
+    {
+        if date>self.date+0.0000001
+        {
+            return Err(format!("fixing of {} on {} after the event date ({})",model,date,self.date));
+        }
+        return self.get_model(model)?.get_value(self.scenario,date,term,&self.logger);
+    }
+
+    fn call(&self,function:&String,arguments:&Vec<Expression>) -> Result<f64,String>
+    {
+        check_arguments(function,arguments)?;
+        return match function.as_str()
+        {
+            "spot"      =>  self.get_model_value(&self.get_text(&arguments[0])?,self.date,0.0),
+            "rate"      =>  self.get_model_value(&self.get_text(&arguments[0])?,self.date,self.evaluate(&arguments[1])?),
+            "df"        =>  {
+                                let term=self.evaluate(&arguments[1])?;
+                                Ok((-self.get_model_value(&self.get_text(&arguments[0])?,self.date,term)?*term).exp())
+                            },
+            "fixing"    =>  self.get_model_value(&self.get_text(&arguments[0])?,self.evaluate(&arguments[1])?,self.evaluate(&arguments[2])?),
+            "min"|"max" =>  {
+                                let mut result=self.evaluate(&arguments[0])?;
+                                for a in arguments.iter().skip(1)
+                                {
+                                    let v=self.evaluate(a)?;
+                                    result=if function=="min" { result.min(v) } else { result.max(v) };
+                                }
+                                Ok(result)
+                            },
+            "abs"       =>  Ok(self.evaluate(&arguments[0])?.abs()),
+            "exp"       =>  Ok(self.evaluate(&arguments[0])?.exp()),
+            "ln"        =>  Ok(self.evaluate(&arguments[0])?.ln()),
+            "sqrt"      =>  Ok(self.evaluate(&arguments[0])?.sqrt()),
+            "if"        =>  {
+                                if self.evaluate(&arguments[0])?!=0.0 { self.evaluate(&arguments[1]) } else { self.evaluate(&arguments[2]) }
+                            },
+            _           =>  Err(format!("unknown function ({})",function))
+        };
+    }
+
+    /// Evaluates an expression
+    pub fn evaluate(&self,expression:&Expression) -> Result<f64,String>
+    {
+        let to_number=|b:bool| if b { 1.0 } else { 0.0 };
+        return match expression
+        {
+            Expression::Number(v)           =>  Ok(*v),
+            Expression::Text(t)             =>  Err(format!("unexpected text (\"{}\")",t)),
+            Expression::Variable(name)      =>  {
+                                                    if name=="date" { return Ok(self.date); }
+                                                    if name=="period" { return Ok(self.period); }
+                                                    match self.variables.get(name).or(self.parameters.get(name))
+                                                    {
+                                                        Some(v)     =>  Ok(*v),
+                                                        None        =>  Err(format!("undefined variable ({})",name))
+                                                    }
+                                                },
+            Expression::Unary(op,e)         =>  {
+                                                    let v=self.evaluate(e)?;
+                                                    if op=="-" { Ok(-v) } else { Ok(to_number(v==0.0)) }
+                                                },
+            Expression::Binary(op,l,r)      =>  {
+                                                    let a=self.evaluate(l)?;
+                                                    //'and' and 'or' do not evaluate the right side when the left side decides
+                                                    if op=="and" && a==0.0 { return Ok(0.0); }
+                                                    if op=="or" && a!=0.0 { return Ok(1.0); }
+                                                    let b=self.evaluate(r)?;
+                                                    match op.as_str()
+                                                    {
+                                                        "+"     =>  Ok(a+b),
+                                                        "-"     =>  Ok(a-b),
+                                                        "*"     =>  Ok(a*b),
+                                                        "/"     =>  Ok(a/b),
+                                                        "^"     =>  Ok(a.powf(b)),
+                                                        "<"     =>  Ok(to_number(a<b)),
+                                                        "<="    =>  Ok(to_number(a<=b)),
+                                                        ">"     =>  Ok(to_number(a>b)),
+                                                        ">="    =>  Ok(to_number(a>=b)),
+                                                        "=="    =>  Ok(to_number(a==b)),
+                                                        "!="    =>  Ok(to_number(a!=b)),
+                                                        _       =>  Ok(to_number(b!=0.0))
+                                                    }
+                                                },
+            Expression::Call(f,arguments)   =>  self.call(f,arguments)
+        };
+    }
+
+    /// Executes the statements of an event
+    pub fn execute(&mut self,statements:&Vec<Statement>) -> Result<(),String>
+    {
+        for statement in statements.iter()
+        {
+            if self.terminated
+            {
+                break;
+            }
+            match statement
+            {
+                Statement::Assign(name,e)   =>  {
+                                                    if self.parameters.contains_key(name) || name=="date" || name=="period"
+                                                    {
+                                                        return Err(format!("{} can not be assigned",name));
+                                                    }
+                                                    let v=self.evaluate(e)?;
+                                                    self.variables.insert(name.clone(),v);
+                                                },
+                Statement::Pay(e)           =>  {
+                                                    let v=self.evaluate(e)?;
+                                                    self.payments.push(v);
+                                                },
+                Statement::Exercise(e)      =>  {
+                                                    let v=self.evaluate(e)?;
+                                                    self.exercise_value=Some(v);
+                                                },
+                Statement::Terminate        =>  self.terminated=true,
+                Statement::If(c,t,e)        =>  {
+                                                    if self.evaluate(c)?!=0.0 { self.execute(t)?; } else { self.execute(e)?; }
+                                                }
+            };
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::path_dependent::{PathData,compute_path_dependent_values};
+use crate::script::{Statement,ScriptContext,parse_script,check_script,has_exercise,get_assigned_variables};
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_exercise_side() -> String
+{
+    return "holder".to_string();
+}
+
+/// Event of a scripted instrument: a script evaluated on each of its dates
+#[derive(Serialize, Deserialize)]
+pub struct ScriptEvent
+{
+    /// Dates of the event (used if 'schedule' is not set)
+    #[serde(default)]
+    pub dates:Vec<f64>,
+    /// Name of the schedule with the dates of the event
+    #[serde(default)]
+    pub schedule:String,
+    pub script:String
+}
+
+/// Instrument defined by a payoff script
+/// (the events are evaluated in date order along each path: they set path variables, pay amounts, define exercise values, and terminate the instrument)
+#[derive(Serialize, Deserialize)]
+pub struct ScriptedInstrument
+{
+    pub name: String,
+    #[serde(default)]
+    pub parameters:HashMap<String,f64>,
+    #[serde(default)]
+    pub schedules:HashMap<String,Vec<f64>>,
+    pub events:Vec<ScriptEvent>,
+    #[serde(default)]
+    pub regression_model_names:Vec<String>,
+    #[serde(default)]
+    pub state_variables:Option<Vec<String>>,
+    #[serde(default="default_exercise_side")]
+    pub exercise_side:String,
+    pub discount_model_name:String,
+    pub fx_model_name:String
+}
+
+/// Event date of a scripted instrument
+struct TimelineEntry
+{
+    date:f64,
+    /// Time from the previous date of the event (0 on the first date)
+    period:f64,
+    event_idx:usize
+}
+
+impl ScriptedInstrument
+{
+    /// Returns 1 if the holder of the position owns the exercise right, -1 if the counterparty does
+    fn get_side_sign(&self) -> f64
+    {
+        return match self.exercise_side.as_str()
+        {
+            "holder"    =>  1.0,
+            "issuer"    =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid exercise side ({}): it must be 'holder' or 'issuer'!",&self.name,&self.exercise_side)
+        };
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    fn get_event_dates(&self,event:&ScriptEvent) -> Result<Vec<f64>,String>
+    {
+        if event.schedule.is_empty()
+        {
+            return Ok(event.dates.clone());
+        }
+        return match self.schedules.get(&event.schedule)
+        {
+            Some(dates) =>  Ok(dates.clone()),
+            None        =>  Err(format!("Scripted instrument {} refers to non-existent schedule ({})!",&self.name,&event.schedule))
+        };
+    }
+
+    fn parse_events(&self) -> Result<Vec<Vec<Statement>>,String>
+    {
+        let mut programs:Vec<Vec<Statement>>=Vec::new();
+        for (e,event) in self.events.iter().enumerate()
+        {
+            match parse_script(&event.script)
+            {
+                Ok(p)   =>  programs.push(p),
+                Err(m)  =>  return Err(format!("Scripted instrument {} has an invalid script in event {}: {}!",&self.name,e,m))
+            };
+        }
+        return Ok(programs);
+    }
+
+    /// Checks the consistency of the definition (the scripts are parsed and checked, and the models they use must be in 'model_names', the loaded models)
+    pub fn validate(&self,model_names:&Vec<String>) -> Result<(),String>
+    {
+        if self.exercise_side!="holder" && self.exercise_side!="issuer"
+        {
+            return Err(format!("Scripted instrument {} has an invalid exercise side ({}): it must be 'holder' or 'issuer'!",&self.name,&self.exercise_side));
+        }
+        if self.events.is_empty()
+        {
+            return Err(format!("Scripted instrument {} must have at least one event!",&self.name));
+        }
+        for event in self.events.iter()
+        {
+            if self.get_event_dates(event)?.is_empty()
+            {
+                return Err(format!("Scripted instrument {} has an event without dates!",&self.name));
+            }
+        }
+        let programs=self.parse_events()?;
+        let parameter_names:Vec<String>=self.parameters.keys().cloned().collect();
+        let mut variable_names:Vec<String>=Vec::new();
+        for program in programs.iter()
+        {
+            get_assigned_variables(program,&mut variable_names);
+        }
+        for (e,program) in programs.iter().enumerate()
+        {
+            if let Err(m)=check_script(program,&parameter_names,&variable_names,model_names)
+            {
+                return Err(format!("Scripted instrument {} has an invalid script in event {}: {}!",&self.name,e,m));
+            }
+        }
+        if let Some(name)=self.regression_model_names.iter().find(|n| !model_names.contains(n))
+        {
+            return Err(format!("Scripted instrument {} has a regression model ({}) that does not exist!",&self.name,name));
+        }
+        if let Some(names)=&self.state_variables
+        {
+            if let Some(name)=names.iter().find(|n| !variable_names.contains(n))
+            {
+                return Err(format!("Scripted instrument {} has a state variable ({}) that is not set by the scripts!",&self.name,name));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Dates of all the events in date order (events on the same date are evaluated in definition order)
+    fn get_timeline(&self) -> Vec<TimelineEntry>
+    {
+        let mut timeline:Vec<TimelineEntry>=Vec::new();
+        for (e,event) in self.events.iter().enumerate()
+        {
+            let dates=match self.get_event_dates(event)
+            {
+                Ok(d)   =>  d,
+                Err(m)  =>  panic!("{}",m)
+            };
+            for i in 0..dates.len()
+            {
+                timeline.push(TimelineEntry { date:dates[i], period:if i==0 { 0.0 } else { dates[i]-dates[i-1] }, event_idx:e });
+            }
+        }
+        timeline.sort_by(|a, b| a.date.partial_cmp(&b.date).unwrap().then(a.event_idx.cmp(&b.event_idx)));
+        return timeline;
+    }
+
+    /// Names of the path variables used as regression state ('state_variables' or all the variables set by the scripts)
+    fn get_state_variables(&self,programs:&Vec<Vec<Statement>>) -> Vec<String>
+    {
+        if let Some(names)=&self.state_variables
+        {
+            return names.clone();
+        }
+        let mut names:Vec<String>=Vec::new();
+        for program in programs.iter()
+        {
+            get_assigned_variables(program,&mut names);
+        }
+        return names;
+    }
+
+    /// Evaluates the events along a scenario
+    fn get_path(&self,scenario:usize,timeline:&Vec<TimelineEntry>,programs:&Vec<Vec<Statement>>,state_variables:&Vec<String>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> PathData
+    {
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let mut context=ScriptContext { scenario:scenario, date:0.0, period:0.0, live_models:live_models, parameters:&self.parameters, variables:HashMap::new(),
+                                        payments:Vec::new(), exercise_value:None, terminated:false, logger:logger };
+        let mut cashflows:Vec<(f64,f64)>=Vec::new();
+        let mut states:Vec<(f64,Vec<f64>)>=Vec::new();
+        let mut exercise_values:Vec<(f64,f64)>=Vec::new();
+        let mut end_date=timeline.last().unwrap().date;
+        for entry in timeline.iter()
+        {
+            context.date=entry.date;
+            context.period=entry.period;
+            context.payments.clear();
+            context.exercise_value=None;
+            if let Err(m)=context.execute(&programs[entry.event_idx])
+            {
+                panic!("Instrument {}: {} (event {}, date {}, scenario {})!",&self.name,m,entry.event_idx,entry.date,scenario);
+            }
+
+            let fx=fx_model.get_value(scenario,entry.date,0.0,&logger).unwrap();
+            for payment in context.payments.iter()
+            {
+                cashflows.push((entry.date,payment*fx));
+            }
+            if let Some(v)=context.exercise_value
+            {
+                exercise_values.retain(|e| e.0!=entry.date);
+                exercise_values.push((entry.date,v*fx));
+            }
+            states.push((entry.date,state_variables.iter().map(|n| *context.variables.get(n).unwrap_or(&0.0)).collect()));
+            if context.terminated
+            {
+                end_date=entry.date;
+                break;
+            }
+        }
+        return PathData { cashflows:cashflows, states:states, exercise_values:exercise_values, end_date:end_date };
+    }
+}
+
+impl Instrument for ScriptedInstrument
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let programs=match self.parse_events()
+        {
+            Ok(p)   =>  p,
+            Err(m)  =>  panic!("{}",m)
+        };
+        let timeline=self.get_timeline();
+        let state_variables=self.get_state_variables(&programs);
+        let maturity_date=timeline.last().unwrap().date;
+        let exercise_dates:Vec<f64>=timeline.iter().filter(|e| has_exercise(&programs[e.event_idx])).map(|e| e.date).collect();
+
+        logger.log(format!("scripted|compute_values -> name: {}, events: {}, exercise dates: {}, state variables: {:?}",self.name,timeline.len(),exercise_dates.len(),state_variables),"instrument");
+        let f_path=|scenario:usize,live_models:&HashMap<String,LiveModel>| self.get_path(scenario,&timeline,&programs,&state_variables,&live_models,&logger);
+        let f_variables=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| {
+            let mut values:Vec<f64>=Vec::new();
+            for model_name in self.regression_model_names.iter()
+            {
+                values.append(&mut self.get_live_model(live_models,model_name).get_variable_values(scenario,date,&logger));
+            }
+            values
+        };
+        return compute_path_dependent_values(start,result_cube,maturity_date,&exercise_dates,self.get_side_sign(),&live_models,training_live_models,&self.discount_model_name,&f_path,&f_variables,&logger);
+    }
+}
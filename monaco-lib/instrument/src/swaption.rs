@@ -0,0 +1,406 @@
+use crate::instrument::Instrument;
+use crate::instrument::Exercisable;
+use crate::vanilla_swap::VanillaSwap;
+use crate::lsm::*;
+use model::live_model::LiveModel;
+use model::hw1f::Hw1f;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+fn default_settlement() -> String
+{
+    return "physical".to_string();
+}
+
+/// European swaption implementation
+/// (right to enter a vanilla swap on the expiry date)
+#[derive(Serialize, Deserialize)]
+pub struct Swaption
+{
+    pub name: String,
+    pub exposure_discount_model_name:String,
+    pub payer_or_receiver:String,
+    #[serde(default="default_settlement")]
+    pub settlement:String,
+    pub expiry_date:f64,
+    pub underlying:VanillaSwap
+}
+
+impl Swaption
+{
+    /// Returns 1 if the swap is entered as defined in 'underlying', -1 if it is entered in the opposite direction
+    ///
+    /// A payer swaption pays the fixed leg(s), a receiver swaption receives them
+    fn get_direction(&self) -> f64
+    {
+        let fixed_legs:Vec<&String>=self.underlying.legs.iter().filter(|leg| leg.is_fixed).map(|leg| &leg.pay_or_receive).collect();
+        if fixed_legs.is_empty()
+        {
+            panic!("Swaption {}: the underlying swap has no fixed leg!",&self.name);
+        }
+        if fixed_legs.iter().any(|p| *p!=fixed_legs[0])
+        {
+            panic!("Swaption {}: the fixed legs of the underlying swap must be either all paid or all received!",&self.name);
+        }
+        let pays_fixed=fixed_legs[0]=="pay";
+        return match self.payer_or_receiver.as_str()
+        {
+            "payer"     =>  if pays_fixed { 1.0 } else { -1.0 },
+            "receiver"  =>  if pays_fixed { -1.0 } else { 1.0 },
+            _           =>  panic!("Swaption {} has an invalid type ({}): it must be 'payer' or 'receiver'!",&self.name,&self.payer_or_receiver)
+        };
+    }
+
+    /// Checks the consistency of the definition (the underlying swap is checked separately)
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payer_or_receiver!="payer" && self.payer_or_receiver!="receiver"
+        {
+            return Err(format!("Swaption {} has an invalid type ({}): it must be 'payer' or 'receiver'!",&self.name,&self.payer_or_receiver));
+        }
+        if self.settlement!="physical" && self.settlement!="cash"
+        {
+            return Err(format!("Swaption {} has an invalid settlement ({}): it must be 'physical' or 'cash'!",&self.name,&self.settlement));
+        }
+        let fixed_legs:Vec<&String>=self.underlying.legs.iter().filter(|leg| leg.is_fixed).map(|leg| &leg.pay_or_receive).collect();
+        if fixed_legs.is_empty()
+        {
+            return Err(format!("Swaption {}: the underlying swap has no fixed leg!",&self.name));
+        }
+        if fixed_legs.iter().any(|p| *p!=fixed_legs[0])
+        {
+            return Err(format!("Swaption {}: the fixed legs of the underlying swap must be either all paid or all received!",&self.name));
+        }
+        if self.settlement=="cash" && !self.underlying.legs.iter().filter(|leg| leg.is_fixed).any(|leg| (1..leg.payment_dates.len()).any(|t| leg.get_coupon_payment_date(t)>self.expiry_date))
+        {
+            return Err(format!("Swaption {}: the underlying swap has no fixed coupon after the expiry date!",&self.name));
+        }
+        return Ok(());
+    }
+
+    fn is_cash_settled(&self) -> bool
+    {
+        return match self.settlement.as_str()
+        {
+            "physical"  =>  false,
+            "cash"      =>  true,
+            _           =>  panic!("Swaption {} has an invalid settlement ({}): it must be 'physical' or 'cash'!",&self.name,&self.settlement)
+        };
+    }
+
+    /// Cash settlement amount on the expiry date of the swap entered, whose value is 'swap_value' (par-yield settlement)
+    ///
+    /// # Remarks
+    ///
+    /// The amount is N*max(S-K,0)*A_cash(S) for a payer swaption (N*max(K-S,0)*A_cash(S) for a receiver), where the cash annuity discounts the fixed periods with the swap rate: A_cash(S)=sum(tau_i/((1+tau_1*S)*..*(1+tau_i*S))).
+    /// With A the annuity of the fixed leg(s) on the discount curves (including the notionals) and K their annuity-weighted fixed rate, the swap rate is S=K+X/A for a payer swaption (K-X/A for a receiver), where X is the value of the swap entered: the amount is then X*A_cash(S)/A (including the notionals).
+    fn get_cash_settlement_amount(&self,scenario:usize,swap_value:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let mut annuity:f64=0.0;
+        let mut fixed_value:f64=0.0;
+        let mut periods:Vec<(f64,f64,f64)>=Vec::new();
+        for leg in self.underlying.legs.iter().filter(|leg| leg.is_fixed)
+        {
+            let dsc_model=match live_models.get(&leg.discount_model_name)
+            {
+                Some(m)     =>  m,
+                None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&leg.discount_model_name)
+            };
+            let fx=match live_models.get(&leg.fx_model_name)
+            {
+                Some(m)     =>  m.get_value(scenario,self.expiry_date,0.0,&logger).unwrap(),
+                None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&leg.fx_model_name)
+            };
+            for t in 1..leg.payment_dates.len()
+            {
+                let payment_date=leg.get_coupon_payment_date(t);
+                if payment_date>self.expiry_date
+                {
+                    let term=payment_date-self.expiry_date;
+                    let df=(-dsc_model.get_value(scenario,self.expiry_date,term,&logger).unwrap()*term).exp();
+                    let accrual_notional=leg.get_accrual_period(t)*leg.get_notional(t)*fx;
+                    annuity+=accrual_notional*df;
+                    fixed_value+=leg.get_fixed_amount(t)*leg.get_notional(t)*fx*df;
+                    periods.push((payment_date,leg.get_accrual_period(t),accrual_notional));
+                }
+            }
+        }
+        if annuity<=0.0
+        {
+            panic!("Swaption {}: the underlying swap has no fixed coupon after the expiry date!",&self.name);
+        }
+        let fixed_rate=fixed_value/annuity;
+        let swap_rate=if self.payer_or_receiver=="payer" { fixed_rate+swap_value/annuity } else { fixed_rate-swap_value/annuity };
+
+        periods.sort_by(|a,b| a.0.partial_cmp(&b.0).unwrap());
+        let mut cash_annuity:f64=0.0;
+        let mut compounding:f64=1.0;
+        for (_,accrual,accrual_notional) in periods.iter()
+        {
+            compounding*=1.0+accrual*swap_rate;
+            cash_annuity+=accrual_notional/compounding;
+        }
+        return swap_value*cash_annuity/annuity;
+    }
+
+    /// Value of exercising on the expiry date (the value of the swap entered, or its cash settlement amount)
+    fn get_exercise_value(&self,scenario:usize,direction:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let swap_value=direction*self.underlying.get_value(scenario,self.expiry_date,&live_models,&logger);
+        if self.is_cash_settled()
+        {
+            return self.get_cash_settlement_amount(scenario,swap_value,&live_models,&logger);
+        }
+        return swap_value;
+    }
+
+    /// Returns the Hull-White model used for the analytic valuation, if the swaption is eligible for it
+    ///
+    /// The swaption is eligible if it is physically settled, the exposure discount model is a Hw1f model used by every leg for discounting and projection, the fx models are fixed,
+    /// no floating coupon resets before the expiry date, and no leg has mark-to-market resets, payment lags, overnight lookback/lockout, or CMS coupons
+    fn get_analytic_model<'a>(&self,live_models:&'a HashMap<String,LiveModel>) -> Option<&'a Hw1f>
+    {
+        let tolerance:f64=0.00001;
+        if self.is_cash_settled()
+        {
+            return None;
+        }
+        let hw1f=match live_models.get(&self.exposure_discount_model_name)
+        {
+            Some(m)     =>  m.model.as_any().downcast_ref::<Hw1f>()?,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.exposure_discount_model_name)
+        };
+        for leg in self.underlying.legs.iter()
+        {
+            if leg.discount_model_name!=self.exposure_discount_model_name || leg.projection_model_name!=self.exposure_discount_model_name || leg.mtm_reset
+                || leg.payment_lag!=0.0 || (leg.is_overnight && (leg.lookback!=0.0 || leg.lockout!=0.0)) || leg.is_cms
+            {
+                return None;
+            }
+            match live_models.get(&leg.fx_model_name)
+            {
+                Some(m)     =>  if m.model.get_type()!="fixed" { return None; },
+                None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&leg.fx_model_name)
+            };
+            if !leg.is_fixed
+            {
+                for t in 1..leg.payment_dates.len()
+                {
+                    if leg.payment_dates[t]>self.expiry_date && leg.payment_dates[t-1]<self.expiry_date-tolerance
+                    {
+                        return None;
+                    }
+                }
+            }
+        }
+        return Some(hw1f);
+    }
+
+    /// Represents the value of the underlying swap on the expiry date as a portfolio of zero bonds: returns the (maturity,amount) tuples
+    ///
+    /// # Remarks
+    ///
+    /// Floating coupons are replicated with zero bonds (notional at the reset date minus notional at the payment date)
+    fn get_zero_bond_portfolio(&self,scenario:usize,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>
+    {
+        let mut portfolio:Vec<(f64,f64)>=Vec::new();
+        for leg in self.underlying.legs.iter()
+        {
+            let fx=live_models.get(&leg.fx_model_name).unwrap().get_value(scenario,self.expiry_date,0.0,&logger).unwrap();
+            let unit=if leg.pay_or_receive=="pay" { -fx } else { fx };
+            for t in 0..leg.payment_dates.len()
+            {
+                let sign=unit*leg.get_notional(t);
+                if leg.payment_dates[t]>self.expiry_date
+                {
+                    portfolio.push((leg.payment_dates[t],sign*leg.get_fixed_amount(t)));
+                    if t>0 && !leg.is_fixed
+                    {
+                        portfolio.push((leg.payment_dates[t-1].max(self.expiry_date),sign));
+                        portfolio.push((leg.payment_dates[t],-sign));
+                    }
+                }
+            }
+            let num_payments=leg.payment_dates.len();
+            if leg.initial_exchange && leg.payment_dates[0]>self.expiry_date
+            {
+                portfolio.push((leg.payment_dates[0],-unit*leg.get_notional(num_payments.min(2)-1)));
+            }
+            if leg.final_exchange
+            {
+                for t in 2..num_payments
+                {
+                    if leg.payment_dates[t-1]>self.expiry_date
+                    {
+                        portfolio.push((leg.payment_dates[t-1],-unit*(leg.get_notional(t)-leg.get_notional(t-1))));
+                    }
+                }
+                if leg.payment_dates[num_payments-1]>self.expiry_date
+                {
+                    portfolio.push((leg.payment_dates[num_payments-1],unit*leg.get_notional(num_payments-1)));
+                }
+            }
+        }
+        return portfolio;
+    }
+
+    /// Jamshidian's formula: value at 'date' of the option to enter the zero bond portfolio on the expiry date, given the short rate 'r'
+    fn get_jamshidian_value(&self,model:&Hw1f,portfolio:&Vec<(f64,f64)>,direction:f64,date:f64,r:f64) -> f64
+    {
+        let expiry=self.expiry_date;
+        let portfolio_value=|r_expiry:f64| direction*portfolio.iter().map(|p| p.1*model.zero_bond(expiry,p.0,r_expiry)).sum::<f64>();
+
+        //Find the critical short rate (where the portfolio is worth 0 on the expiry date) by bisection
+        let (mut low,mut high)=(-1.0,1.0);
+        let (v_low,v_high)=(portfolio_value(low),portfolio_value(high));
+        if v_low.signum()==v_high.signum()
+        {
+            //The option is either always or never exercised
+            return if v_low>0.0 { direction*portfolio.iter().map(|p| p.1*model.zero_bond(date,p.0,r)).sum::<f64>() } else { 0.0 };
+        }
+        for _i in 0..100
+        {
+            let mid=0.5*(low+high);
+            if portfolio_value(mid).signum()==v_low.signum() { low=mid; } else { high=mid; }
+        }
+        let r_star=0.5*(low+high);
+
+        //Exercise happens for low rates (zero bond calls) or for high rates (zero bond puts)
+        let exercise_for_low_rates=v_low>0.0;
+        let mut v:f64=0.0;
+        for p in portfolio.iter()
+        {
+            let strike=model.zero_bond(expiry,p.0,r_star);
+            if exercise_for_low_rates
+            {
+                v+=direction*p.1*model.zero_bond_option(true,date,expiry,p.0,strike,r);
+            }
+            else
+            {
+                v-=direction*p.1*model.zero_bond_option(false,date,expiry,p.0,strike,r);
+            }
+        }
+        return v;
+    }
+}
+
+impl Instrument for Swaption
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        let date_threshold=0.001;
+        let direction=self.get_direction();
+        let cash_settled=self.is_cash_settled();
+        let exp_dsc_model=match live_models.get(&self.exposure_discount_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,&self.exposure_discount_model_name)
+        };
+
+        //Option values on the dates before expiry
+        let mut regressions:Vec<LsmRegression>=Vec::new();
+        let mut option_values_cube:Cube=match self.get_analytic_model(&live_models)
+        {
+            Some(hw1f)  =>  {
+                                logger.log(format!("swaption|compute_values -> name: {}, analytic valuation (Jamshidian)",self.name),"instrument");
+                                let mut dates:Vec<f64>=result_cube.dates.iter().filter(|d| **d<self.expiry_date).map(|d| *d).collect();
+                                dates.push(self.expiry_date);
+                                let mut values_cube=Cube::make_empty_cube(dates.clone(), result_cube.num_scenarios, 1);
+                                for s in 0..result_cube.num_scenarios
+                                {
+                                    let portfolio=self.get_zero_bond_portfolio(s,&live_models,&logger);
+                                    for dt_idx in 0..dates.len()-1
+                                    {
+                                        let r=exp_dsc_model.get_variable_values(s,dates[dt_idx],&logger)[0];
+                                        let _=values_cube.set_item(s, 0, dt_idx, self.get_jamshidian_value(hw1f,&portfolio,direction,dates[dt_idx],r));
+                                    }
+                                }
+                                values_cube
+                            },
+            None        =>  {
+                                logger.log(format!("swaption|compute_values -> name: {}, lsm valuation",self.name),"instrument");
+                                //LSM dates: the dates before expiry, the expiry date (exercise) and a final date where the option value is 0
+                                let mut rich_dates:Vec<(bool,f64)>=result_cube.dates.iter().filter(|d| **d<self.expiry_date-date_threshold).map(|d| (false,*d)).collect();
+                                rich_dates.push((true,self.expiry_date));
+                                rich_dates.push((false,self.expiry_date+date_threshold));
+                                let dates:Vec<f64>=rich_dates.iter().map(|d| d.1).collect();
+                                let exercise_flags:Vec<bool>=rich_dates.iter().map(|d| d.0).collect();
+
+                                let mut f_models_variables_values=|date:f64,live_models:&HashMap<String,LiveModel>| self.underlying.get_models_variables_values(date, &live_models,&logger);
+                                let mut f_exercise_value=|scenario:usize,_date:f64,live_models:&HashMap<String,LiveModel>| self.get_exercise_value(scenario,direction,&live_models,&logger);
+                                let mut f_cashflows=|_scenario:usize,_min_date:f64,_max_date:f64,_live_models:&HashMap<String,LiveModel>| Vec::new();
+
+                                let training_regressions=match training_live_models
+                                {
+                                    Some(training_models)   =>  {
+                                                                    let training_dsc_model=match training_models.get(&self.exposure_discount_model_name)
+                                                                    {
+                                                                        Some(m)     =>  m,
+                                                                        None        =>  panic!("Instrument {} refers to non-existent training model ({})!",&self.name,&self.exposure_discount_model_name)
+                                                                    };
+                                                                    let mut training_values_cube:Cube=Cube::make_empty_cube(dates.clone(), training_dsc_model.cube.num_scenarios, 1);
+                                                                    let (_,_,r)=compute_lsm_values(&mut training_values_cube,&training_models,&exercise_flags,&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,&training_dsc_model,None,&logger);
+                                                                    Some(r)
+                                                                },
+                                    None                    =>  None
+                                };
+                                let mut values_cube=Cube::make_empty_cube(dates, result_cube.num_scenarios, 1);
+                                let (_,_,r)=compute_lsm_values(&mut values_cube,&live_models,&exercise_flags,&mut f_models_variables_values,&mut f_exercise_value,&mut f_cashflows,&exp_dsc_model,training_regressions.as_ref(),&logger);
+                                regressions=r;
+                                values_cube
+                            }
+        };
+
+        //Exercise on the expiry date (the option is exercised if the swap has a positive value, the cash settlement amount has the same sign)
+        let mut exercise_cube:Cube=Cube::make_empty_cube(vec![self.expiry_date], result_cube.num_scenarios, 1);
+        let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
+        for s in 0..result_cube.num_scenarios
+        {
+            let exercise_value=self.get_exercise_value(s,direction,&live_models,&logger);
+            let exercised=exercise_value>0.0;
+            let expiry_idx=option_values_cube.dates.iter().position(|d| *d==self.expiry_date).unwrap();
+            let _=option_values_cube.set_item(s, 0, expiry_idx, exercise_value.max(0.0));
+            let _=exercise_cube.set_item(s, 0, 0, if exercised { 1.0 } else { 0.0 });
+
+            let scenario_cashflows:Vec<(f64,f64)>=if !exercised
+            {
+                Vec::new()
+            }
+            else if cash_settled
+            {
+                vec![(self.expiry_date,exercise_value)]
+            }
+            else
+            {
+                self.underlying.get_cashflows(s,self.expiry_date,self.underlying.get_maturity_date(),&live_models,&logger).iter().map(|cf| (cf.0,direction*cf.1)).collect()
+            };
+            cashflows.push(scenario_cashflows);
+
+            for dt_idx in 0..result_cube.dates.len()
+            {
+                let date=result_cube.dates[dt_idx];
+                let v=if date<=self.expiry_date
+                {
+                    option_values_cube.get_item_interp(s, 0, date, true).unwrap().2
+                }
+                else if exercised && !cash_settled
+                {
+                    direction*self.underlying.get_value(s,date,&live_models,&logger)
+                }
+                else
+                {
+                    0.0
+                };
+                let _=result_cube.set_item(s, start, dt_idx, v);
+            }
+        }
+        return (cashflows,exercise_cube,regressions);
+    }
+}
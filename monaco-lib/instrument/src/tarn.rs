@@ -0,0 +1,116 @@
+use crate::instrument::Instrument;
+use crate::lsm::LsmRegression;
+use crate::path_dependent::{PathData,compute_path_dependent_values};
+use model::live_model::LiveModel;
+use data_cube::data_cube::Cube;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use logger::Logger;
+
+/// Target redemption note implementation
+/// (the note redeems early as soon as the running total of the coupons reaches the target)
+#[derive(Serialize, Deserialize)]
+pub struct Tarn
+{
+    pub name: String,
+    pub pay_or_receive:String,
+    pub notional:f64,
+    pub fixed_rate:f64,
+    #[serde(default)]
+    pub leverage:f64,
+    pub target:f64,
+    pub payment_dates:Vec<f64>,
+    pub projection_model_name:String,
+    pub discount_model_name:String,
+    pub fx_model_name:String
+}
+
+impl Tarn
+{
+    fn get_sign(&self) -> f64
+    {
+        return match self.pay_or_receive.as_str()
+        {
+            "receive"   =>  1.0,
+            "pay"       =>  -1.0,
+            _           =>  panic!("Instrument {} has an invalid side ({}): it must be 'pay' or 'receive'!",&self.name,&self.pay_or_receive)
+        };
+    }
+
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.payment_dates.len()<2
+        {
+            return Err(format!("Tarn {} must have a start date and at least one payment date!",&self.name));
+        }
+        if self.target<=0.0
+        {
+            return Err(format!("Tarn {} has an invalid target ({})!",&self.name,self.target));
+        }
+        return Ok(());
+    }
+
+    fn get_live_model<'a>(&self,live_models:&'a HashMap<String,LiveModel<'a>>,model_name:&String) -> &'a LiveModel<'a>
+    {
+        return match live_models.get(model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} refers to non-existent model ({})!",&self.name,model_name)
+        };
+    }
+
+    /// Coupons and redemption of a scenario; the state is the running total of the coupons (per unit of notional)
+    fn get_path(&self,scenario:usize,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> PathData
+    {
+        let sign=self.get_sign();
+        let projection_model=self.get_live_model(live_models,&self.projection_model_name);
+        let fx_model=self.get_live_model(live_models,&self.fx_model_name);
+        let mut cashflows:Vec<(f64,f64)>=Vec::new();
+        let mut states:Vec<(f64,Vec<f64>)>=Vec::new();
+        let mut total:f64=0.0;
+        let mut end_date=*self.payment_dates.last().unwrap();
+        for t in 1..self.payment_dates.len()
+        {
+            let period_start=self.payment_dates[t-1];
+            let period_end=self.payment_dates[t];
+            let period=period_end-period_start;
+            let rate=projection_model.get_value(scenario,period_start,period,&logger).unwrap();
+            let mut coupon=(self.fixed_rate+self.leverage*rate).max(0.0)*period;
+
+            //The coupon that reaches the target is capped and the note is redeemed with it
+            let redeemed=total+coupon>=self.target || t==self.payment_dates.len()-1;
+            if total+coupon>=self.target
+            {
+                coupon=self.target-total;
+            }
+            total+=coupon;
+            states.push((period_end,vec![total]));
+
+            let amount=if redeemed { (coupon+1.0)*self.notional } else { coupon*self.notional };
+            let fx=fx_model.get_value(scenario,period_end,0.0,&logger).unwrap();
+            cashflows.push((period_end,sign*amount*fx));
+            if redeemed
+            {
+                end_date=period_end;
+                break;
+            }
+        }
+        return PathData { cashflows:cashflows, states:states, exercise_values:Vec::new(), end_date:end_date };
+    }
+}
+
+impl Instrument for Tarn
+{
+    fn get_name(&self) -> String
+    {
+        return self.name.clone();
+    }
+
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
+    {
+        logger.log(format!("tarn|compute_values -> name: {}, starting regression...",self.name),"instrument");
+        let f_path=|scenario:usize,live_models:&HashMap<String,LiveModel>| self.get_path(scenario,&live_models,&logger);
+        let f_variables=|scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>| self.get_live_model(live_models,&self.projection_model_name).get_variable_values(scenario,date,&logger);
+        return compute_path_dependent_values(start,result_cube,*self.payment_dates.last().unwrap(),&Vec::new(),1.0,&live_models,training_live_models,&self.discount_model_name,&f_path,&f_variables,&logger);
+    }
+}
@@ -1,23 +1,292 @@
 //use std::any::Any;
 use crate::instrument::Instrument;
+use crate::instrument::Exercisable;
+use crate::lsm::LsmRegression;
 use model::live_model::LiveModel;
 use data_cube::data_cube::Cube;
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 //use macros::debug;
 use logger::Logger;
+use calendar::date::Date;
+use calendar::day_count::{validate_day_count,get_accrual_periods};
+use calendar::holiday_calendar::{HolidayCalendar,get_calendar,validate_business_day_convention};
+use calendar::schedule::{ScheduleDefinition,SchedulePeriod};
 
 #[derive(Serialize, Deserialize)]
 pub struct SwapLeg
 {
-    pub notional:f64,
+    #[serde(default)]
+    pub notional:Option<f64>,
+    #[serde(default)]
+    pub notionals:Vec<f64>,
     pub pay_or_receive:String,
     pub discount_model_name:String,
     pub projection_model_name:String,
     pub fx_model_name:String,
+    #[serde(default)]
     pub payment_dates:Vec<f64>,
     pub is_fixed:bool,
-    pub fixed_values:Vec<f64>
+    pub fixed_values:Vec<f64>,
+    #[serde(default)]
+    pub initial_exchange:bool,
+    #[serde(default)]
+    pub final_exchange:bool,
+    #[serde(default)]
+    pub mtm_reset:bool,
+    #[serde(default)]
+    pub mtm_reference_fx_model_name:String,
+    #[serde(default)]
+    pub mtm_reference_discount_model_name:String,
+    #[serde(default)]
+    pub is_overnight:bool,
+    #[serde(default="default_compounding_step")]
+    pub compounding_step:f64,
+    #[serde(default)]
+    pub lookback:f64,
+    #[serde(default)]
+    pub lockout:f64,
+    #[serde(default)]
+    pub payment_lag:f64,
+    #[serde(default)]
+    pub is_cms:bool,
+    #[serde(default)]
+    pub cms_tenor:f64,
+    #[serde(default="default_cms_frequency")]
+    pub cms_frequency:f64,
+    #[serde(default)]
+    pub cms_cap:Option<f64>,
+    #[serde(default)]
+    pub cms_floor:Option<f64>,
+    #[serde(default)]
+    pub cms_volatility:f64,
+    #[serde(default)]
+    pub index_name:String,
+    #[serde(default)]
+    pub fixings:Vec<(f64,f64)>,
+    #[serde(default)]
+    pub day_count:String,
+    #[serde(default)]
+    pub calendar:String,
+    #[serde(default="default_business_day_convention")]
+    pub business_day_convention:String,
+    #[serde(default)]
+    pub schedule:Option<ScheduleDefinition>,
+    #[serde(skip)]
+    accrual_periods:Vec<f64>,
+    #[serde(skip)]
+    fixing_dates:Vec<f64>,
+    #[serde(skip)]
+    coupon_payment_dates:Vec<f64>,
+    #[serde(skip)]
+    schedule_periods:Vec<SchedulePeriod>
+}
+
+fn default_compounding_step() -> f64
+{
+    return 1.0/365.0;
+}
+
+fn default_cms_frequency() -> f64
+{
+    return 1.0;
+}
+
+fn default_business_day_convention() -> String
+{
+    return "modified-following".to_string();
+}
+
+/// Annuity and value of the fixed leg (with coupon 'rate') of a swap with 'num_periods' periods of length 'tau', as functions of its yield
+fn get_swap_bond_price(rate:f64,tau:f64,num_periods:usize,yield_rate:f64) -> f64
+{
+    let discount=1.0/(1.0+tau*yield_rate);
+    let mut v:f64=0.0;
+    let mut df:f64=1.0;
+    for _k in 0..num_periods
+    {
+        df*=discount;
+        v+=tau*rate*df;
+    }
+    return v+df;
+}
+
+impl SwapLeg
+{
+    /// Date on which the coupon of 'payment_dates[t]' is paid
+    pub fn get_coupon_payment_date(&self,t:usize) -> f64
+    {
+        if !self.coupon_payment_dates.is_empty()
+        {
+            return self.coupon_payment_dates[t];
+        }
+        return self.payment_dates[t]+self.payment_lag;
+    }
+
+    /// Fixing date of the floating rate of the coupon paid on 'payment_dates[t]' (the start of the period, unless the schedule has a fixing lag)
+    pub fn get_fixing_date(&self,t:usize) -> f64
+    {
+        if !self.fixing_dates.is_empty()
+        {
+            return self.fixing_dates[t];
+        }
+        return self.payment_dates[t-1];
+    }
+
+    /// Periods generated from 'schedule' (empty if the payment dates are explicit)
+    pub fn get_schedule_periods(&self) -> &Vec<SchedulePeriod>
+    {
+        return &self.schedule_periods;
+    }
+
+    /// Notional of the coupon paid on 'payment_dates[t]' (before mark-to-market resets)
+    pub fn get_notional(&self,t:usize) -> f64
+    {
+        if self.notionals.is_empty()
+        {
+            return match self.notional
+            {
+                Some(n)     =>  n,
+                None        =>  panic!("Swap leg has neither a notional nor notionals!")
+            };
+        }
+        return self.notionals[t];
+    }
+
+    /// Accrual period of the coupon paid on 'payment_dates[t]' ('day_count' fraction, or the difference of the payment dates if no convention is set)
+    pub fn get_accrual_period(&self,t:usize) -> f64
+    {
+        if !self.accrual_periods.is_empty()
+        {
+            return self.accrual_periods[t];
+        }
+        return if t==0 { 0.0 } else { self.payment_dates[t]-self.payment_dates[t-1] };
+    }
+
+    /// Fixed amount (fixed coupon or spread) of the coupon paid on 'payment_dates[t]', per unit of notional
+    ///
+    /// # Remarks
+    ///
+    /// With a day count convention the fixed values are annual rates, accrued over the period; otherwise they are amounts.
+    pub fn get_fixed_amount(&self,t:usize) -> f64
+    {
+        if self.day_count.is_empty()
+        {
+            return self.fixed_values[t];
+        }
+        return self.fixed_values[t]*self.get_accrual_period(t);
+    }
+
+    /// Generates the payment dates from 'schedule', or adjusts them to the business days of 'calendar', and computes the accrual periods with 'day_count' (when they are set)
+    ///
+    /// # Remarks
+    ///
+    /// With a schedule, a single fixed value is applied to all the periods.
+    pub fn apply_conventions(&mut self,valuation_date:&Option<Date>,calendars:&HashMap<String,HolidayCalendar>) -> Result<(),String>
+    {
+        if self.calendar.is_empty() && self.day_count.is_empty() && self.schedule.is_none()
+        {
+            return Ok(());
+        }
+        let valuation_date=match valuation_date
+        {
+            Some(d)     =>  d,
+            None        =>  return Err("a valuation date is required for schedules, calendars and day count conventions".to_string())
+        };
+        let calendar=match self.calendar.is_empty()
+        {
+            true    =>  None,
+            false   =>  {
+                            validate_business_day_convention(&self.business_day_convention)?;
+                            Some(get_calendar(&self.calendar,calendars)?)
+                        }
+        };
+        if let Some(schedule)=&self.schedule
+        {
+            if !self.payment_dates.is_empty() || self.payment_lag!=0.0
+            {
+                return Err("a leg with a schedule can not have explicit payment dates or a payment lag (the schedule has its own payment lag)".to_string());
+            }
+            let periods=schedule.generate(valuation_date,calendar.as_ref(),&self.business_day_convention)?;
+            let start_date=periods[0].start_date.to_time(valuation_date);
+            self.payment_dates=vec![start_date];
+            self.payment_dates.extend(periods.iter().map(|p| p.end_date.to_time(valuation_date)));
+            self.fixing_dates=vec![start_date];
+            self.fixing_dates.extend(periods.iter().map(|p| p.fixing_date.to_time(valuation_date)));
+            self.coupon_payment_dates=vec![start_date];
+            self.coupon_payment_dates.extend(periods.iter().map(|p| p.payment_date.to_time(valuation_date)));
+            if self.fixed_values.len()==1
+            {
+                let fixed_value=self.fixed_values[0];
+                self.fixed_values=vec![0.0];
+                self.fixed_values.extend(vec![fixed_value;periods.len()]);
+            }
+            self.schedule_periods=periods;
+        }
+        else if let Some(c)=&calendar
+        {
+            self.payment_dates=c.adjust_times(&self.payment_dates,valuation_date,&self.business_day_convention);
+        }
+        if !self.day_count.is_empty()
+        {
+            validate_day_count(&self.day_count)?;
+            self.accrual_periods=get_accrual_periods(&self.payment_dates,valuation_date,&self.day_count);
+        }
+        for p in 0..self.schedule_periods.len()
+        {
+            self.schedule_periods[p].accrual_period=self.get_accrual_period(p+1);
+        }
+        return Ok(());
+    }
+
+    /// Name of the index of the floating rate ('index_name' or, if not set, the projection model name)
+    pub fn get_index_name(&self) -> &String
+    {
+        if self.index_name.is_empty()
+        {
+            return &self.projection_model_name;
+        }
+        return &self.index_name;
+    }
+
+    /// Known fixing of the floating rate for 'fixing_date' (see 'find_fixing')
+    pub fn get_fixing(&self,fixing_date:f64) -> Result<Option<f64>,String>
+    {
+        return find_fixing(&self.fixings,self.get_index_name(),fixing_date);
+    }
+}
+
+/// Fixing of 'index_name' dated on 'fixing_date' (within half a day), if 'fixing_date' is on or before the evaluation date 0
+///
+/// # Remarks
+///
+/// Returns None when the rate is projected from the model: the fixing date is after 0, or it is 0 and its fixing is not known yet. A fixing date before 0 without a fixing is an error.
+pub fn find_fixing(fixings:&Vec<(f64,f64)>,index_name:&String,fixing_date:f64) -> Result<Option<f64>,String>
+{
+    let tolerance:f64=0.5/365.0;
+    if fixing_date>tolerance
+    {
+        return Ok(None);
+    }
+    return match fixings.iter().find(|f| (f.0-fixing_date).abs()<tolerance)
+    {
+        Some(f)                             =>  Ok(Some(f.1)),
+        None if fixing_date< -tolerance     =>  Err(format!("no fixing of {} on {}",index_name,fixing_date)),
+        None                                =>  Ok(None)
+    };
+}
+
+/// Adds the fixings of 'history' that are not already in 'fixings' (the fixings already there take precedence)
+pub fn add_fixings(fixings:&mut Vec<(f64,f64)>,history:&Vec<(f64,f64)>) -> ()
+{
+    let tolerance:f64=0.0000001;
+    for (date,rate) in history.iter()
+    {
+        if !fixings.iter().any(|f| (f.0-date).abs()<tolerance)
+        {
+            fixings.push((*date,*rate));
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,6 +298,83 @@ pub struct VanillaSwap
 
 impl VanillaSwap
 {
+    /// Checks the consistency of the legs (to be called when the swap is loaded)
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.legs.is_empty()
+        {
+            return Err(format!("Swap {} has no legs!",&self.name));
+        }
+        for l in 0..self.legs.len()
+        {
+            let leg=&self.legs[l];
+            if leg.payment_dates.is_empty()
+            {
+                return Err(format!("Swap {} (leg {}) has no payment dates!",&self.name,l));
+            }
+            if leg.fixed_values.len()!=leg.payment_dates.len()
+            {
+                return Err(format!("Swap {} (leg {}) has {} fixed values for {} payment dates!",&self.name,l,leg.fixed_values.len(),leg.payment_dates.len()));
+            }
+            if leg.notionals.is_empty() && leg.notional.is_none()
+            {
+                return Err(format!("Swap {} (leg {}) has neither a notional nor notionals!",&self.name,l));
+            }
+            if !leg.notionals.is_empty() && leg.notionals.len()!=leg.payment_dates.len()
+            {
+                return Err(format!("Swap {} (leg {}) has {} notionals for {} payment dates!",&self.name,l,leg.notionals.len(),leg.payment_dates.len()));
+            }
+            if leg.mtm_reset && (leg.mtm_reference_fx_model_name.is_empty() || leg.mtm_reference_discount_model_name.is_empty())
+            {
+                return Err(format!("Swap {} (leg {}) is a mark-to-market leg without a reference fx model or discount model!",&self.name,l));
+            }
+            if leg.is_overnight && (leg.is_fixed || leg.compounding_step<=0.0 || leg.lookback<0.0 || leg.lockout<0.0)
+            {
+                return Err(format!("Swap {} (leg {}) is an invalid overnight leg (it must be floating, with a positive compounding step and non-negative lookback/lockout)!",&self.name,l));
+            }
+            if leg.is_cms && (leg.is_fixed || leg.is_overnight || leg.cms_tenor<=0.0 || leg.cms_frequency<=0.0 || leg.cms_volatility<0.0)
+            {
+                return Err(format!("Swap {} (leg {}) is an invalid CMS leg (it must be floating and not overnight, with a positive tenor and frequency and a non-negative volatility)!",&self.name,l));
+            }
+            if !leg.is_fixed && !leg.is_overnight
+            {
+                for t in (1..leg.payment_dates.len()).filter(|t| leg.get_coupon_payment_date(*t)>0.0)
+                {
+                    if let Err(m)=leg.get_fixing(leg.get_fixing_date(t))
+                    {
+                        return Err(format!("Swap {} (leg {}): {}!",&self.name,l,m));
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Applies the calendars and day count conventions of the legs (to be called when the swap is loaded)
+    pub fn apply_conventions(&mut self,valuation_date:&Option<Date>,calendars:&HashMap<String,HolidayCalendar>) -> Result<(),String>
+    {
+        for l in 0..self.legs.len()
+        {
+            if let Err(m)=self.legs[l].apply_conventions(valuation_date,calendars)
+            {
+                return Err(format!("Swap {} (leg {}): {}!",&self.name,l,m));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Adds the fixings of 'fixing_history' (index name -> list of (date,rate)) to the floating legs (the fixings defined in the legs take precedence)
+    pub fn apply_fixing_history(&mut self,fixing_history:&HashMap<String,Vec<(f64,f64)>>) -> ()
+    {
+        for leg in self.legs.iter_mut().filter(|l| !l.is_fixed)
+        {
+            if let Some(history)=fixing_history.get(leg.get_index_name())
+            {
+                add_fixings(&mut leg.fixings,history);
+            }
+        }
+    }
+
     //To be removed
     // fn test(&self,ex_fl:&Vec<bool>,test_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,discount_model:&LiveModel) -> ()
     // {
@@ -75,21 +421,242 @@ impl VanillaSwap
         return variables_values;
     }
 
-    fn get_direct_value_for_leg(&self,leg_no:usize,scenario:usize,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,logger:&Logger) -> f64
+    /// Returns the (fx,discount) live models of the reference ccy of a mark-to-market leg
+    fn get_mtm_reference_models<'a>(&self,leg_no:usize,live_models:&'a HashMap<String,LiveModel<'a>>) -> (&'a LiveModel<'a>,&'a LiveModel<'a>)
+    {
+        let leg=&self.legs[leg_no];
+        let fx_model=match live_models.get(&leg.mtm_reference_fx_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} (leg {}) refers to non-existent mtm reference fx model ({})!",&self.name,leg_no,&leg.mtm_reference_fx_model_name)
+        };
+        let discount_model=match live_models.get(&leg.mtm_reference_discount_model_name)
+        {
+            Some(m)     =>  m,
+            None        =>  panic!("Instrument {} (leg {}) refers to non-existent mtm reference discount model ({})!",&self.name,leg_no,&leg.mtm_reference_discount_model_name)
+        };
+        return (fx_model,discount_model);
+    }
+
+    /// Value of one unit of the leg ccy in the reference ccy on 'fixing_date', as known on 'date' (forward value if 'fixing_date' is after 'date')
+    fn get_mtm_fx(&self,leg_no:usize,scenario:usize,fixing_date:f64,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let (ref_fx_model,ref_discount_model)=self.get_mtm_reference_models(leg_no,&live_models);
+        let observation_date=fixing_date.min(date);
+        let spot=models[leg_no].2.get_value(scenario,observation_date,0.0,&logger).unwrap()/ref_fx_model.get_value(scenario,observation_date,0.0,&logger).unwrap();
+        if fixing_date<=date
+        {
+            return spot;
+        }
+        let term=fixing_date-date;
+        let leg_df=(-term*models[leg_no].0.get_value(scenario,date,term,&logger).unwrap()).exp();
+        let ref_df=(-term*ref_discount_model.get_value(scenario,date,term,&logger).unwrap()).exp();
+        return spot*leg_df/ref_df;
+    }
+
+    /// Notional of the coupon paid on 'payment_dates[t]' as known on 'date'
+    /// 
+    /// # Remarks
+    /// 
+    /// The notional of a mark-to-market leg is reset at the start of every period (after the first) so that its value in the reference ccy stays constant
+    fn get_period_notional(&self,leg_no:usize,t:usize,scenario:usize,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        let leg=&self.legs[leg_no];
+        if !leg.mtm_reset || t<2
+        {
+            return leg.get_notional(t);
+        }
+        let start_fx=self.get_mtm_fx(leg_no,scenario,leg.payment_dates[0],date,&models,&live_models,&logger);
+        let reset_fx=self.get_mtm_fx(leg_no,scenario,leg.payment_dates[t-1],date,&models,&live_models,&logger);
+        return leg.get_notional(t)*start_fx/reset_fx;
+    }
+
+    /// Returns the notional exchanges of a leg as (date,amount) tuples (amounts are in the leg ccy, as known on 'date')
+    /// 
+    /// # Remarks
+    /// 
+    /// A receive leg pays the notional on the first payment date and receives it on the last one (a pay leg does the opposite).
+    /// Mark-to-market legs, and legs with a final exchange, also exchange the notional changes at the start of every period.
+    fn get_notional_exchanges(&self,leg_no:usize,scenario:usize,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>
+    {
+        let leg=&self.legs[leg_no];
+        let sign=if leg.pay_or_receive=="pay" { -1.0 } else { 1.0 };
+        let num_payments=leg.payment_dates.len();
+        let mut exchanges:Vec<(f64,f64)>=Vec::new();
+        if leg.initial_exchange
+        {
+            exchanges.push((leg.payment_dates[0],-sign*leg.get_notional(num_payments.min(2)-1)));
+        }
+        if leg.mtm_reset || leg.final_exchange
+        {
+            for t in 2..num_payments
+            {
+                let notional_change=self.get_period_notional(leg_no,t,scenario,date,&models,&live_models,&logger)-self.get_period_notional(leg_no,t-1,scenario,date,&models,&live_models,&logger);
+                if notional_change!=0.0
+                {
+                    exchanges.push((leg.payment_dates[t-1],-sign*notional_change));
+                }
+            }
+        }
+        if leg.final_exchange
+        {
+            exchanges.push((leg.payment_dates[num_payments-1],sign*self.get_period_notional(leg_no,num_payments-1,scenario,date,&models,&live_models,&logger)));
+        }
+        return exchanges;
+    }
+
+    /// Growth factor of the overnight-compounded coupon paid on 'payment_dates[t]' as known on 'date'
+    /// 
+    /// # Remarks
+    /// 
+    /// The rate of every compounding step is the rate of the projection model for the step term, observed 'lookback' before the start of the step
+    /// (during the last 'lockout' of the period the rate is frozen). The steps observed up to 'date' use the simulated path (or the known fixings if observed on or before date 0), the others the forward discount factors on 'date'.
+    fn get_overnight_growth(&self,leg_no:usize,t:usize,scenario:usize,date:f64,projection_model:&LiveModel,logger:&Logger) -> f64
+    {
+        let leg=&self.legs[leg_no];
+        let period_start=leg.payment_dates[t-1];
+        let period_end=leg.payment_dates[t];
+        let lockout_start=period_end-leg.lockout;
+        let tolerance:f64=0.0000001;
+
+        let mut growth:f64=1.0;
+        let mut step_start=period_start;
+        while step_start<period_end-tolerance
+        {
+            let step=leg.compounding_step.min(period_end-step_start);
+            let fixing_date=step_start.min(lockout_start)-leg.lookback;
+            let observation_date=fixing_date.max(0.0);
+            if observation_date>date
+            {
+                //Forward growth on the remaining (shifted) observation period
+                let forward_start=(step_start-leg.lookback).max(date);
+                let forward_end=(period_end-leg.lookback).max(forward_start);
+                let df=|x:f64| if x<=date { 1.0 } else { (-(x-date)*projection_model.get_value(scenario,date,x-date,&logger).unwrap()).exp() };
+                growth*=df(forward_start)/df(forward_end);
+                break;
+            }
+            let rate=match leg.get_fixing(fixing_date)
+            {
+                Ok(Some(r)) =>  r,
+                Ok(None)    =>  projection_model.get_value(scenario,observation_date,step,&logger).unwrap(),
+                Err(m)      =>  panic!("Swap {} (leg {}): {}!",&self.name,leg_no,m)
+            };
+            growth*=(step*rate).exp();
+            step_start+=step;
+        }
+        return growth;
+    }
+
+    /// Rate of the floating period of leg 'leg_no' fixed on 'fixing_date' (past fixings are known, the others are taken from the path of the projection model)
+    fn get_floating_rate(&self,leg_no:usize,scenario:usize,fixing_date:f64,term:f64,projection_model:&LiveModel,logger:&Logger) -> f64
+    {
+        let leg=&self.legs[leg_no];
+        return match leg.get_fixing(fixing_date)
+        {
+            Ok(Some(r)) =>  r,
+            Ok(None)    =>  projection_model.get_value(scenario,fixing_date.max(0.0),term,&logger).unwrap(),
+            Err(m)      =>  panic!("Swap {} (leg {}): {}!",&self.name,leg_no,m)
+        };
+    }
+
+    /// Par swap rate of tenor 'cms_tenor' starting on 'fixing_date', from the discount factors of the projection model on 'date'
+    fn get_cms_swap_rate(&self,leg_no:usize,scenario:usize,date:f64,fixing_date:f64,projection_model:&LiveModel,logger:&Logger) -> f64
+    {
+        let leg=&self.legs[leg_no];
+        let num_periods=((leg.cms_tenor/leg.cms_frequency).round() as usize).max(1);
+        let df=|x:f64| if x<=date { 1.0 } else { (-(x-date)*projection_model.get_value(scenario,date,x-date,&logger).unwrap()).exp() };
+        let start_df=df(fixing_date);
+        let mut annuity:f64=0.0;
+        let mut end_df:f64=start_df;
+        for k in 1..num_periods+1
+        {
+            end_df=df(fixing_date+(k as f64)*leg.cms_frequency);
+            annuity+=leg.cms_frequency*end_df;
+        }
+        return (start_df-end_df)/annuity;
+    }
+
+    /// Coupon rate of the CMS period paid on 'payment_dates[t]' as known on 'date' (capped/floored)
+    ///
+    /// # Remarks
+    ///
+    /// Before the fixing date the forward swap rate is convexity and timing adjusted (Hull), assuming a lognormal swap rate with volatility 'cms_volatility'
+    /// perfectly correlated with the rate of the period; the cap and the floor are valued with the Black formula on the adjusted rate.
+    fn get_cms_rate(&self,leg_no:usize,t:usize,scenario:usize,date:f64,projection_model:&LiveModel,logger:&Logger) -> f64
+    {
+        let leg=&self.legs[leg_no];
+        let fixing_date=leg.get_fixing_date(t);
+        let apply_bounds=|r:f64| r.max(leg.cms_floor.unwrap_or(f64::NEG_INFINITY)).min(leg.cms_cap.unwrap_or(f64::INFINITY));
+        if fixing_date<=date
+        {
+            return match leg.get_fixing(fixing_date)
+            {
+                Ok(Some(r)) =>  apply_bounds(r),
+                Ok(None)    =>  apply_bounds(self.get_cms_swap_rate(leg_no,scenario,fixing_date.max(0.0),fixing_date.max(0.0),projection_model,&logger)),
+                Err(m)      =>  panic!("Swap {} (leg {}): {}!",&self.name,leg_no,m)
+            };
+        }
+
+        let swap_rate=self.get_cms_swap_rate(leg_no,scenario,date,fixing_date,projection_model,&logger);
+        let variance=leg.cms_volatility*leg.cms_volatility*(fixing_date-date);
+        if variance<=0.0 || swap_rate<=0.0
+        {
+            return apply_bounds(swap_rate);
+        }
+
+        //Convexity adjustment: -0.5*S^2*sigma^2*T*G''(S)/G'(S), with G the price of the fixed leg as a function of its yield
+        let num_periods=((leg.cms_tenor/leg.cms_frequency).round() as usize).max(1);
+        let h=0.0001;
+        let g=|y:f64| get_swap_bond_price(swap_rate,leg.cms_frequency,num_periods,y);
+        let g1=(g(swap_rate+h)-g(swap_rate-h))/(2.0*h);
+        let g2=(g(swap_rate+h)-2.0*g(swap_rate)+g(swap_rate-h))/(h*h);
+        let convexity_adjustment=-0.5*swap_rate*swap_rate*variance*g2/g1;
+
+        //Timing adjustment (the rate is paid at the end of the period instead of the fixing date)
+        let period=leg.payment_dates[t]-fixing_date;
+        let period_rate=model::live_model::get_fwd_rate(projection_model, scenario, date, fixing_date, leg.payment_dates[t],&logger);
+        let timing_adjustment=-swap_rate*variance*period_rate*period/(1.0+period_rate*period);
+
+        let adjusted_rate=swap_rate+convexity_adjustment+timing_adjustment;
+        if adjusted_rate<=0.0
+        {
+            return apply_bounds(adjusted_rate);
+        }
+        let mut rate=adjusted_rate;
+        if let Some(floor)=leg.cms_floor
+        {
+            rate+=math::math::black_formula(false,adjusted_rate,floor,variance);
+        }
+        if let Some(cap)=leg.cms_cap
+        {
+            rate-=math::math::black_formula(true,adjusted_rate,cap,variance);
+        }
+        return rate;
+    }
+
+    fn get_direct_value_for_leg(&self,leg_no:usize,scenario:usize,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
     {
         let mut v:f64=0.0;
         for t in 0..self.legs[leg_no].payment_dates.len()
         {
-            if self.legs[leg_no].payment_dates[t]>date
+            if self.legs[leg_no].get_coupon_payment_date(t)>date
             {
                 let cpn_end=self.legs[leg_no].payment_dates[t];
                 let cpn_start=if t==0 { 0.0 } else { self.legs[leg_no].payment_dates[t-1] };
-                let cpn_period=if t==0 { 0.0 } else { self.legs[leg_no].payment_dates[t]-self.legs[leg_no].payment_dates[t-1] };
-                let cpn_time=self.legs[leg_no].payment_dates[t]-date;
+                let accrual_period=self.legs[leg_no].get_accrual_period(t);
+                let cpn_time=self.legs[leg_no].get_coupon_payment_date(t)-date;
 
                 let mut leg_payment=if t==0||self.legs[leg_no].is_fixed
                 {
-                    self.legs[leg_no].fixed_values[t]
+                    self.legs[leg_no].get_fixed_amount(t)
+                }
+                else if self.legs[leg_no].is_overnight
+                {
+                    self.get_overnight_growth(leg_no,t,scenario,date,&models[leg_no].1,&logger)-1.0+self.legs[leg_no].get_fixed_amount(t)
+                }
+                else if self.legs[leg_no].is_cms
+                {
+                    self.get_cms_rate(leg_no,t,scenario,date,&models[leg_no].1,&logger)*accrual_period+self.legs[leg_no].get_fixed_amount(t)
                 }
                 else
                 {
@@ -98,18 +665,19 @@ impl VanillaSwap
                     //let fwd_test1=model::live_model::get_fwd_rate(&models[leg_no].1, scenario, date, cpn_start, cpn_end);
                     //let fwd_test2=models[leg_no].1.get_value(scenario,cpn_start,cpn_end-cpn_start).unwrap();
                     //End debug
-                    if cpn_start>date
+                    let fixing_date=self.legs[leg_no].get_fixing_date(t);
+                    if fixing_date>date
                     {
-                        fwd=model::live_model::get_fwd_rate(&models[leg_no].1, scenario, date, cpn_start, cpn_end,&logger);
+                        fwd=model::live_model::get_fwd_rate(&models[leg_no].1, scenario, date, fixing_date, fixing_date+cpn_end-cpn_start,&logger);
                     }
                     else
                     {
-                        fwd=models[leg_no].1.get_value(scenario,cpn_start,cpn_end-cpn_start,&logger).unwrap()
+                        fwd=self.get_floating_rate(leg_no,scenario,fixing_date,cpn_end-cpn_start,&models[leg_no].1,&logger);
                     }                   
-                    fwd*cpn_period+self.legs[leg_no].fixed_values[t]
+                    fwd*accrual_period+self.legs[leg_no].get_fixed_amount(t)
                     //fwd+self.legs[leg_no].fixed_values[t]
                 };
-                leg_payment*=self.legs[leg_no].notional;
+                leg_payment*=self.get_period_notional(leg_no,t,scenario,date,&models,&live_models,&logger);
                 let leg_df=(-cpn_time*models[leg_no].0.get_value(scenario, date, cpn_time,&logger).unwrap()).exp();
                 v=if self.legs[leg_no].pay_or_receive=="pay" { v-leg_payment*leg_df } else { v+leg_payment*leg_df };
                 // debug!(format!("vanilla-swap|get_direct_value_for_leg -> name: {}, date: {}, scenario: {}, t: {}, is_fixed: {}, cpn_start: {}, cpn_end: {}, cpn_period: {}, cpn_time: {}, leg_payment: {}, leg_df: {}",
@@ -126,18 +694,27 @@ impl VanillaSwap
                 // leg_df));
             }
         }
+        for (exchange_date,amount) in self.get_notional_exchanges(leg_no,scenario,date,&models,&live_models,&logger)
+        {
+            if exchange_date>date
+            {
+                let exchange_time=exchange_date-date;
+                let exchange_df=(-exchange_time*models[leg_no].0.get_value(scenario, date, exchange_time,&logger).unwrap()).exp();
+                v+=amount*exchange_df;
+            }
+        }
         let leg_fx=models[leg_no].2.get_value(scenario, date,0.0,&logger).unwrap();
 
         let v=v*leg_fx;
         return v;
     }
 
-    fn get_direct_value(&self,scenario:usize,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,logger:&Logger) -> f64
+    fn get_direct_value(&self,scenario:usize,date:f64,models:&Vec<(&model::live_model::LiveModel,&model::live_model::LiveModel,&model::live_model::LiveModel)>,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
     {
         let mut v:f64=0.0;
         for l in 0..self.legs.len()
         {
-            let leg_value=self.get_direct_value_for_leg(l,scenario,date,models,&logger);
+            let leg_value=self.get_direct_value_for_leg(l,scenario,date,models,&live_models,&logger);
             v+=leg_value;
             // debug!(format!("vanilla-swap|get_direct_value -> name: {}, date: {}, leg: {}, leg_value: {}, v: {}",
             // self.name,
@@ -153,7 +730,7 @@ impl VanillaSwap
     pub fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
     {
         let models=self.get_live_models(&live_models);
-        return self.get_direct_value(scenario,date,&models,&logger);
+        return self.get_direct_value(scenario,date,&models,&live_models,&logger);
     }
 
     fn get_cashflows_for_leg(&self,leg_no:usize,scenario:usize,min_date:f64,max_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>
@@ -163,24 +740,41 @@ impl VanillaSwap
         
         for t in 0..self.legs[leg_no].payment_dates.len()
         {
-            if self.legs[leg_no].payment_dates[t]>min_date&&self.legs[leg_no].payment_dates[t]<=max_date
+            let payment_date=self.legs[leg_no].get_coupon_payment_date(t);
+            if payment_date>min_date&&payment_date<=max_date
             {
-                let cpn_start=if t==0 { 0.0 } else { self.legs[leg_no].payment_dates[t-1] };
                 let cpn_period=if t==0 { 0.0 } else { self.legs[leg_no].payment_dates[t]-self.legs[leg_no].payment_dates[t-1] };
+                let accrual_period=self.legs[leg_no].get_accrual_period(t);
 
                 let mut leg_payment=if t==0||self.legs[leg_no].is_fixed
                 {
-                    self.legs[leg_no].fixed_values[t]
+                    self.legs[leg_no].get_fixed_amount(t)
+                }
+                else if self.legs[leg_no].is_overnight
+                {
+                    self.get_overnight_growth(leg_no,t,scenario,f64::INFINITY,&models[leg_no].1,&logger)-1.0+self.legs[leg_no].get_fixed_amount(t)
+                }
+                else if self.legs[leg_no].is_cms
+                {
+                    self.get_cms_rate(leg_no,t,scenario,f64::INFINITY,&models[leg_no].1,&logger)*accrual_period+self.legs[leg_no].get_fixed_amount(t)
                 }
                 else
                 {
-                    models[leg_no].1.get_value(scenario, cpn_start, cpn_period,&logger).unwrap()*cpn_period+self.legs[leg_no].fixed_values[t]
+                    self.get_floating_rate(leg_no,scenario,self.legs[leg_no].get_fixing_date(t),cpn_period,&models[leg_no].1,&logger)*accrual_period+self.legs[leg_no].get_fixed_amount(t)
                 };
-                leg_payment*=self.legs[leg_no].notional;
+                leg_payment*=self.get_period_notional(leg_no,t,scenario,f64::INFINITY,&models,&live_models,&logger);
                 if self.legs[leg_no].pay_or_receive=="pay" { leg_payment=-leg_payment; } 
-                let leg_fx=models[leg_no].2.get_value(scenario,self.legs[leg_no].payment_dates[t],0.0,&logger).unwrap();
+                let leg_fx=models[leg_no].2.get_value(scenario,payment_date,0.0,&logger).unwrap();
                 let global_ccy_leg_payment=leg_payment*leg_fx;
-                cashflows.push((self.legs[leg_no].payment_dates[t],global_ccy_leg_payment));
+                cashflows.push((payment_date,global_ccy_leg_payment));
+            }
+        }
+        for (exchange_date,amount) in self.get_notional_exchanges(leg_no,scenario,f64::INFINITY,&models,&live_models,&logger)
+        {
+            if exchange_date>min_date&&exchange_date<=max_date
+            {
+                let leg_fx=models[leg_no].2.get_value(scenario,exchange_date,0.0,&logger).unwrap();
+                cashflows.push((exchange_date,amount*leg_fx));
             }
         }
         
@@ -221,6 +815,12 @@ impl VanillaSwap
             models.insert(leg_discount_model.model.get_name(),leg_discount_model);
             models.insert(leg_projection_model.model.get_name(),leg_projection_model);
             models.insert(leg_fx_model.model.get_name(),leg_fx_model);
+            if self.legs[l].mtm_reset
+            {
+                let (ref_fx_model,ref_discount_model)=self.get_mtm_reference_models(l,&live_models);
+                models.insert(ref_fx_model.model.get_name(),ref_fx_model);
+                models.insert(ref_discount_model.model.get_name(),ref_discount_model);
+            }
         }
 
         return models;
@@ -260,7 +860,7 @@ impl Instrument for VanillaSwap
         return self.name.clone();
     }
 
-    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube)
+    fn compute_values(&self,start:usize,result_cube:&mut Cube,live_models:&HashMap<String,LiveModel>,_training_live_models:Option<&HashMap<String,LiveModel>>,logger:&Logger) -> (Vec<Vec<(f64,f64)>>,Cube,Vec<LsmRegression>)
     {
         let models=self.get_live_models(&live_models);
         let mut cashflows:Vec<Vec<(f64,f64)>>=Vec::new();
@@ -273,13 +873,37 @@ impl Instrument for VanillaSwap
             for dt_idx in 0..result_cube.dates.len()
             {
                 let date=result_cube.dates[dt_idx];
-                let v=self.get_direct_value(s,date,&models,&logger);
+                let v=self.get_direct_value(s,date,&models,&live_models,&logger);
                 logger.log(format!("vanilla-swap|compute_values -> name: {}, s: {}, dt_idx: {}, date: {}, v: {}",self.name,s,dt_idx,date,v),"instrument");
                 let _=result_cube.set_item(s, start, dt_idx, v);
             }
         }
 
         let exercise_cube=Cube::make_empty_cube(vec![0.0], 1, 1);
-        return (cashflows,exercise_cube);
+        return (cashflows,exercise_cube,Vec::new());
+    }
+}
+
+impl Exercisable for VanillaSwap
+{
+    fn get_maturity_date(&self) -> f64
+    {
+        //Remember: swaps must have at least one leg
+        return self.legs.iter().map(|leg| leg.get_coupon_payment_date(leg.payment_dates.len()-1)).fold(f64::NEG_INFINITY, f64::max);
+    }
+
+    fn get_models_variables_values(&self,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<f64>
+    {
+        return VanillaSwap::get_models_variables_values(self,date,&live_models,&logger);
     }
-}
\ No newline at end of file
+
+    fn get_value(&self,scenario:usize,date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> f64
+    {
+        return VanillaSwap::get_value(self,scenario,date,&live_models,&logger);
+    }
+
+    fn get_cashflows(&self,scenario:usize,min_date:f64,max_date:f64,live_models:&HashMap<String,LiveModel>,logger:&Logger) -> Vec<(f64,f64)>
+    {
+        return VanillaSwap::get_cashflows(self,scenario,min_date,max_date,&live_models,&logger);
+    }
+}
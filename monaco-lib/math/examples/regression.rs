@@ -16,7 +16,7 @@ fn main() {
         cols:1
     };
     println!("Creating regressor...");
-    let r=LinearRegressor::create_regressor(&x.data,&y.data);
+    let r=LinearRegressor::create_regressor(&x.data,&y.data).unwrap();
     println!("Regressor created.");
 
     let num_params=x.cols+1;
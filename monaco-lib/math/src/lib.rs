@@ -44,6 +44,40 @@ mod tests
 
     }
 
+    #[test]
+    fn normal_cdf()
+    {
+        assert!((math::normal_cdf(0.0)-0.5).abs()<1e-7);
+        assert!((math::normal_cdf(1.959963985)-0.975).abs()<1e-6);
+        assert!((math::normal_cdf(-1.0)+math::normal_cdf(1.0)-1.0).abs()<1e-12);
+    }
+
+    #[test]
+    fn black_formula()
+    {
+        //Put-call parity, intrinsic value without variance, and the at-the-money approximation forward*std_dev/sqrt(2*pi)
+        let (forward,strike,variance)=(0.03,0.025,0.04);
+        let call=math::black_formula(true,forward,strike,variance);
+        let put=math::black_formula(false,forward,strike,variance);
+        assert!((call-put-(forward-strike)).abs()<1e-12);
+        assert!(call>forward-strike && put>0.0);
+        assert_eq!(math::black_formula(true,forward,strike,0.0),forward-strike);
+        assert_eq!(math::black_formula(false,forward,strike,0.0),0.0);
+        let atm=math::black_formula(true,forward,forward,0.0001);
+        assert!((atm-forward*0.01/(2.0*std::f64::consts::PI).sqrt()).abs()<1e-7);
+    }
+
+    #[test]
+    fn seeded_variates()
+    {
+        let corr=vec![1.0,0.5,0.5,1.0];
+        let x1=math::simulate_normal_variates_with_seed(2, 10, &corr, 7);
+        let x2=math::simulate_normal_variates_with_seed(2, 10, &corr, 7);
+        let x3=math::simulate_normal_variates_with_seed(2, 10, &corr, 8);
+        assert!(x1==x2);
+        assert!(x1!=x3);
+    }
+
     #[test]
     fn inv() 
     {
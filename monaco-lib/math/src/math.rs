@@ -4,7 +4,8 @@ use crate::matrix::*;
 //extern crate lapack;
 //use lapack::*;
 //use macros::debug;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::distributions::Open01;
 
 /// Compute the parameters for linear regression from two vectors
@@ -51,6 +52,55 @@ pub fn linear_regression_one_var(data:&Vec<(f64,f64)>) -> (f64,f64)
     return (alpha,beta)
 }
 
+/// Compute the sample mean and the standard error of the mean
+///
+///  # Arguments
+/// 
+/// * `data` - Sample values
+pub fn mean_and_standard_error(data:&Vec<f64>) -> (f64,f64)
+{
+    let n:f64=data.len() as f64;
+    if data.len()==0
+    {
+        return (0.0,0.0);
+    }
+    let mean:f64=data.iter().sum::<f64>()/n;
+    if data.len()==1
+    {
+        return (mean,0.0);
+    }
+    let variance:f64=data.iter().map(|x| (x-mean)*(x-mean)).sum::<f64>()/(n-1.0);
+
+    return (mean,(variance/n).sqrt());
+}
+
+/// Compute the coefficient of determination (R squared) of fitted values
+///
+///  # Arguments
+/// 
+/// * `y` - Observed values
+/// * `y_fitted` - Fitted values
+pub fn r_squared(y:&Vec<f64>,y_fitted:&Vec<f64>) -> f64
+{
+    if y.len()==0
+    {
+        return 0.0;
+    }
+    let mean:f64=y.iter().sum::<f64>()/(y.len() as f64);
+    let mut ss_res:f64=0.0;
+    let mut ss_tot:f64=0.0;
+    for i in 0..y.len()
+    {
+        ss_res+=(y[i]-y_fitted[i])*(y[i]-y_fitted[i]);
+        ss_tot+=(y[i]-mean)*(y[i]-mean);
+    }
+    if ss_tot==0.0
+    {
+        return if ss_res==0.0 { 1.0 } else { 0.0 };
+    }
+    return 1.0-ss_res/ss_tot;
+}
+
 /// Perform linear interpolation
 /// 
 /// # Arguments
@@ -105,8 +155,31 @@ pub fn interpolate(data:&Vec<(f64,f64)>, x:f64) -> f64
 /// To retrieve variable j for sample i  in the matrix: matrix[i*num_var+j]
 pub fn simulate_normal_variates(num_var:usize, sample_size:usize, correlation_matrix: &Vec<f64>) -> Vec<f64>
 {
-    let mut result = vec![0.0; sample_size*num_var];
     let mut rng = thread_rng();
+    return simulate_normal_variates_with_rng(&mut rng, num_var, sample_size, &correlation_matrix);
+}
+
+/// Simulate normally distributed vectors of correlated variates from a seeded generator
+///
+/// # Arguments
+/// 
+/// * `num_var` - Number of random variables to simulate
+/// * `sample_size` - Number of correlated vector to produce
+/// * `correlation_matrix` - Correlation matrix (dimensions: num_var x num_var)
+/// * `seed` - Seed of the random number generator
+/// 
+/// # Remarks
+/// 
+/// The same seed always produces the same variates (see 'simulate_normal_variates' for the layout)
+pub fn simulate_normal_variates_with_seed(num_var:usize, sample_size:usize, correlation_matrix: &Vec<f64>, seed:u64) -> Vec<f64>
+{
+    let mut rng = StdRng::seed_from_u64(seed);
+    return simulate_normal_variates_with_rng(&mut rng, num_var, sample_size, &correlation_matrix);
+}
+
+fn simulate_normal_variates_with_rng<R:Rng>(rng:&mut R, num_var:usize, sample_size:usize, correlation_matrix: &Vec<f64>) -> Vec<f64>
+{
+    let mut result = vec![0.0; sample_size*num_var];
 
     for i in 0..sample_size
     {
@@ -126,6 +199,52 @@ pub fn simulate_normal_variates(num_var:usize, sample_size:usize, correlation_ma
     return y;
 }
 
+/// Compute the CDF of the standard normal distribution
+/// 
+/// # Arguments
+/// 
+/// *`x` - Value
+/// 
+/// # Remarks
+/// 
+/// Uses the Chebyshev approximation of the complementary error function (fractional error below 1.2e-7)
+pub fn normal_cdf(x: f64) -> f64
+{
+    let z=(x/f64::sqrt(2.0)).abs();
+    let t=1.0/(1.0+0.5*z);
+    let poly=-z*z-1.26551223+t*(1.00002368+t*(0.37409196+t*(0.09678418+t*(-0.18628806+t*(0.27886807+t*(-1.13520398+t*(1.48851587+t*(-0.82215223+t*0.17087277))))))));
+    let erfc=t*poly.exp();
+    if x>=0.0
+    {
+        return 1.0-0.5*erfc;
+    }
+    else
+    {
+        return 0.5*erfc;
+    }
+}
+
+/// Compute the Black price (undiscounted) of a European option on a lognormal forward
+/// 
+/// # Arguments
+/// 
+/// *`is_call` - True for a call, false for a put
+/// *`forward` - Forward value of the underlying
+/// *`strike` - Strike
+/// *`variance` - Integrated variance of the log forward to the expiry date
+pub fn black_formula(is_call:bool,forward:f64,strike:f64,variance:f64) -> f64
+{
+    let omega:f64=if is_call { 1.0 } else { -1.0 };
+    if variance<=0.0
+    {
+        return (omega*(forward-strike)).max(0.0);
+    }
+    let std_dev=variance.sqrt();
+    let d1=((forward/strike).ln()+0.5*variance)/std_dev;
+    let d2=d1-std_dev;
+    return omega*(forward*normal_cdf(omega*d1)-strike*normal_cdf(omega*d2));
+}
+
 /// Compute the inverse CDF from a normal distribution
 /// 
 /// # Arguments
@@ -16,6 +16,55 @@ pub struct Black
     pub initial_value: f64
 }
 
+impl Black
+{
+    /// Integrated variance of the log value between 'start' and 'end'
+    /// (trapezoidal rule on the squared volatility, using the volatility dates as nodes)
+    pub fn get_variance(&self,start:f64,end:f64) -> f64
+    {
+        if end<=start
+        {
+            return 0.0;
+        }
+        let mut nodes:Vec<f64>=vec![start];
+        for (t,_) in self.sigmas.iter()
+        {
+            if *t>start && *t<end
+            {
+                nodes.push(*t);
+            }
+        }
+        nodes.push(end);
+
+        let mut variance:f64=0.0;
+        for i in 1..nodes.len()
+        {
+            let prev_sigma=math::math::interpolate(&self.sigmas, nodes[i-1]);
+            let sigma=math::math::interpolate(&self.sigmas, nodes[i]);
+            variance+=0.5*(prev_sigma*prev_sigma+sigma*sigma)*(nodes[i]-nodes[i-1]);
+        }
+        return variance;
+    }
+
+    /// Sets the value for a date from the previous date (or from the initial value for the first date)
+    #[allow(non_snake_case)]
+    fn evolve_factor(&self,s:usize,dt_idx:usize,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ()
+    {
+        let t:f64=factors.dates[dt_idx];
+        let prev_t:f64=if dt_idx==0 {0.0} else {factors.dates[dt_idx - 1]};  
+        let delta_t:f64 = t - prev_t;
+
+        let sigma:f64=math::math::interpolate(&self.sigmas, t);
+        let dW=raw_factors.get_item(s, start_raw, dt_idx).unwrap();
+        let prev:f64=if dt_idx==0 { self.initial_value } else { factors.get_item(s, start, dt_idx-1).unwrap() };
+        let v:f64=prev+prev*(self.r*delta_t+sigma*dW*f64::sqrt(delta_t));
+
+        logger.log(format!("black|populate_factors -> name: {0}, s: {1}, dt_idx: {2}, delta_t: {3}, sigma:{4}, dW: {5}, prev: {6}, v: {7}, r: {8}",self.name,s,dt_idx,delta_t,sigma,dW,prev,v,self.r),"model");
+
+        factors.set_item(s, start, dt_idx, v).unwrap();
+    }
+}
+
 impl Model for Black
 {
     fn as_any(&self) -> &dyn Any { self }
@@ -38,7 +87,6 @@ impl Model for Black
         return 1;
     }
 
-    #[allow(non_snake_case)]
     fn populate_factors(&self,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ()
     {
         for s in 0..factors.num_scenarios
@@ -52,21 +100,21 @@ impl Model for Black
                 }
                 else
                 {
-                    let prev_t:f64=if dt_idx==0 {0.0} else {factors.dates[dt_idx - 1]};  
-                    let delta_t:f64 = t - prev_t;
-
-                    let sigma:f64=math::math::interpolate(&self.sigmas, t);
-                    let dW=raw_factors.get_item(s, start_raw, dt_idx).unwrap();
-                    let prev:f64=if dt_idx==0 { self.initial_value } else { factors.get_item(s, start, dt_idx-1).unwrap() };
-                    let v:f64=prev+prev*(self.r*delta_t+sigma*dW*f64::sqrt(delta_t));
-
-                    logger.log(format!("black|populate_factors -> name: {0}, s: {1}, dt_idx: {2}, delta_t: {3}, sigma:{4}, dW: {5}, prev: {6}, v: {7}, r: {8}",self.name,s,dt_idx,delta_t,sigma,dW,prev,v,self.r),"model");
-
-                    factors.set_item(s, start, dt_idx, v).unwrap();
+                    self.evolve_factor(s, dt_idx, start_raw, raw_factors, start, factors, &logger);
                 }
             }
         }
     }
+    fn populate_factors_from(&self,first_dt_idx:usize,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ()
+    {
+        for s in 0..factors.num_scenarios
+        {
+            for dt_idx in first_dt_idx+1..factors.dates.len()
+            {
+                self.evolve_factor(s, dt_idx, start_raw, raw_factors, start, factors, &logger);
+            }
+        }
+    }
     #[allow(non_snake_case)]
     fn get_output_values(&self,start_pos:usize, cube:&Cube, raw_start_pos:usize, raw_cube:&Cube, scenario:usize, date:f64,logger:&Logger) -> Result<Vec<f64>,String>
     {
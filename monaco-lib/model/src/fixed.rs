@@ -22,6 +22,7 @@ impl Model for Fixed
     fn get_number_of_outputs(&self) -> usize {return 0;}
 
     fn populate_factors(&self,_start_raw: usize,_raw_factors:&Cube,_start:usize,_factors:&mut Cube,_logger:&Logger) -> () {}
+    fn populate_factors_from(&self,_first_dt_idx:usize,_start_raw: usize,_raw_factors:&Cube,_start:usize,_factors:&mut Cube,_logger:&Logger) -> () {}
     
     fn get_output_values(&self,_start_pos:usize,_cube:&Cube,_raw_start_pos:usize,_raw_cube:&Cube,_scenario:usize,_date:f64,_logger:&Logger) -> Result<Vec<f64>,String>
     {
@@ -27,6 +27,24 @@ impl Hw1f
 
 impl Hw1f
 {    
+    /// Sets the short rate for a date from the previous date (or from the initial rate for the first date)
+    fn evolve_factor(&self,s:usize,dt_idx:usize,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ()
+    {
+        let t:f64=factors.dates[dt_idx];
+        let prev_t:f64= if dt_idx==0 {0.0} else {factors.dates[dt_idx - 1]};  
+        let delta_t:f64 = t - prev_t;
+        let phi_t:f64=math::math::interpolate(&self.thetas, t);
+        
+        let prev_r:f64=if dt_idx==0 { self.initial_rate } else { factors.get_item(s, start, dt_idx-1).unwrap() };
+
+        let r1:f64=phi_t*delta_t;
+        let r2:f64=-math::math::interpolate(&self.a, t)*prev_r*delta_t;
+        let r3:f64=math::math::interpolate(&self.sigmas, t)*f64::sqrt(delta_t)*raw_factors.get_item(s, start_raw, dt_idx).unwrap();
+        let r=prev_r+r1+r2+r3;
+        logger.log(format!("hw1f|populate_factors -> name: {0}, s: {1}, dt_idx: {2}, delat_t: {3}, phi_t: {4}, r1: {5}, r2: {6}, r3: {7}, r: {8}, prev_r: {9}",self.name,s,dt_idx,delta_t,phi_t,r1,r2,r3,r,prev_r),"model");
+        factors.set_item(s, start, dt_idx, r).unwrap();
+    }
+
     pub fn thetas_from_term_structure(&self) -> Vec<(f64,f64)>
     {
         let mut thetas:Vec<(f64,f64)>=vec![(0.0,0.0);self.term_structure.len()];
@@ -66,6 +84,40 @@ impl Hw1f
 
         return f.ln()/(t2-t1);
     }
+    /// Price at 't' of the zero bond maturing at 'maturity' given the short rate 'r' at 't'
+    pub fn zero_bond(&self, t:f64, maturity:f64, r:f64) -> f64
+    {
+        return self.A(t,maturity)*(-r*self.B(t,maturity)).exp();
+    }
+
+    /// Price at 't' of a European option expiring at 'expiry' on the zero bond maturing at 'maturity' given the short rate 'r' at 't'
+    /// 
+    /// # Remarks
+    /// 
+    /// The mean reversion and the volatility are taken at 't' (the closed form assumes constant parameters)
+    pub fn zero_bond_option(&self, is_call:bool, t:f64, expiry:f64, maturity:f64, strike:f64, r:f64) -> f64
+    {
+        let p_expiry=self.zero_bond(t,expiry,r);
+        let p_maturity=self.zero_bond(t,maturity,r);
+        let a=math::math::interpolate(&self.a, t);
+        let sigma=math::math::interpolate(&self.sigmas, t);
+        let sigma_p=sigma*((1.0-(-2.0*a*(expiry-t)).exp())/(2.0*a)).sqrt()*self.B(expiry,maturity);
+        if sigma_p<=0.0
+        {
+            let intrinsic=p_maturity-strike*p_expiry;
+            return if is_call { intrinsic.max(0.0) } else { (-intrinsic).max(0.0) };
+        }
+        let h=(p_maturity/(p_expiry*strike)).ln()/sigma_p+0.5*sigma_p;
+        if is_call
+        {
+            return p_maturity*math::math::normal_cdf(h)-strike*p_expiry*math::math::normal_cdf(h-sigma_p);
+        }
+        else
+        {
+            return strike*p_expiry*math::math::normal_cdf(-h+sigma_p)-p_maturity*math::math::normal_cdf(-h);
+        }
+    }
+
     #[allow(non_snake_case)]
     fn A(&self, t:f64, T:f64) -> f64
     {
@@ -142,23 +194,21 @@ impl Model for Hw1f
                 }
                 else
                 {
-                    let t:f64=factors.dates[dt_idx];
-                    let prev_t:f64= if dt_idx==0 {0.0} else {factors.dates[dt_idx - 1]};  
-                    let delta_t:f64 = t - prev_t;
-                    let phi_t:f64=math::math::interpolate(&self.thetas, t);
-                    
-                    let prev_r:f64=if dt_idx==0 { self.initial_rate } else { factors.get_item(s, start, dt_idx-1).unwrap() };
-
-                    let r1:f64=phi_t*delta_t;
-                    let r2:f64=-math::math::interpolate(&self.a, t)*prev_r*delta_t;
-                    let r3:f64=math::math::interpolate(&self.sigmas, t)*f64::sqrt(delta_t)*raw_factors.get_item(s, start_raw, dt_idx).unwrap();
-                    let r=prev_r+r1+r2+r3;
-                    logger.log(format!("hw1f|populate_factors -> name: {0}, s: {1}, dt_idx: {2}, delat_t: {3}, phi_t: {4}, r1: {5}, r2: {6}, r3: {7}, r: {8}, prev_r: {9}",self.name,s,dt_idx,delta_t,phi_t,r1,r2,r3,r,prev_r),"model");
-                    factors.set_item(s, start, dt_idx, r).unwrap();
+                    self.evolve_factor(s, dt_idx, start_raw, raw_factors, start, factors, &logger);
                 }
             }
         }
     }
+    fn populate_factors_from(&self,first_dt_idx:usize,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ()
+    {
+        for s in 0..factors.num_scenarios
+        {
+            for dt_idx in first_dt_idx+1..factors.dates.len()
+            {
+                self.evolve_factor(s, dt_idx, start_raw, raw_factors, start, factors, &logger);
+            }
+        }
+    }
     #[allow(non_snake_case)]
     fn get_output_values(&self,start_pos:usize, cube:&Cube, raw_start_pos:usize, raw_cube:&Cube, scenario:usize, date:f64,logger:&Logger) -> Result<Vec<f64>,String>
     {
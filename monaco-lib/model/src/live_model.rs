@@ -2,6 +2,7 @@
 use data_cube::data_cube::Cube;
 use crate::model::Model;
 use logger::Logger;
+use std::collections::HashMap;
 
 //Utility function
 pub fn get_fwd_rate(model:&LiveModel,scenario:usize,date:f64,start:f64,end:f64,logger:&Logger) -> f64
@@ -49,4 +50,19 @@ impl LiveModel<'_>
     {
         return self.model.get_value(self.start, &self.cube, self.raw_start, &self.raw_cube, scenario, date, term,&logger);
     }
-}
\ No newline at end of file
+}
+/// Provider of nested simulations (inner paths branching off a path of the outer simulation)
+pub trait InnerSimulator
+{
+    /// Simulates inner paths starting from the state of an outer path and passes their live models to a function
+    /// 
+    /// # Arguments
+    /// 
+    /// * `live_models` - Live models of the outer simulation
+    /// * `scenario` - Outer scenario from which the inner paths branch off
+    /// * `dates` - Dates of the inner simulation (the first date is the branching date)
+    /// * `num_paths` - Number of inner paths
+    /// * `logger` - Logger
+    /// * `f` - Function that receives the live models of the inner simulation
+    fn with_inner_paths(&self,live_models:&HashMap<String,LiveModel>,scenario:usize,dates:&Vec<f64>,num_paths:usize,logger:&Logger,f:&mut dyn FnMut(&HashMap<String,LiveModel>) -> ()) -> ();
+}
@@ -17,6 +17,8 @@ pub trait Model
     fn get_number_of_variables(&self) -> usize;
     fn get_number_of_outputs(&self) -> usize;
     fn populate_factors(&self,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ();
+    /// Populates the factors after a date index, starting from the values already set up to that index (used for nested simulations)
+    fn populate_factors_from(&self,first_dt_idx:usize,start_raw: usize, raw_factors:&Cube, start:usize, factors:&mut Cube,logger:&Logger) -> ();
     
     fn get_name(&self) -> String;
     fn get_type(&self) -> &str;
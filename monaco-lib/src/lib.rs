@@ -3,6 +3,7 @@ pub use instrument;
 pub use exposure_simulation;
 pub use data_cube;
 pub use curve;
+pub use calendar;
 pub use logger;
 
 pub mod testmod
@@ -17,10 +18,12 @@ pub mod testmod
 pub mod tests
 {
     use model::hw1f::Hw1f;
+    use model::model::OutputInterpolation;
     #[test]
     fn create_hw1f() {
         Hw1f{
             name:"Test_Hw1f".to_string(),
+            interpolation:OutputInterpolation::Martingale,
             initial_rate:0.02,
             term_structure: vec![(0.1,0.1)],
             thetas: vec![(0.1,0.1)],
@@ -29,4 +32,25 @@ pub mod tests
          };
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn hw1f_zero_bond_option_parity() {
+        let mut model=Hw1f{
+            name:"Test_Hw1f".to_string(),
+            interpolation:OutputInterpolation::Martingale,
+            initial_rate:0.02,
+            term_structure: vec![(0.0,0.02),(10.0,0.03)],
+            thetas: Vec::new(),
+            a: vec![(0.0,0.1)],
+            sigmas: vec![(0.0,0.01)],
+         };
+        use model::model::Model;
+        model.init();
+        let (t,expiry,maturity,strike,r)=(0.5,1.0,3.0,0.95,0.025);
+        let call=model.zero_bond_option(true,t,expiry,maturity,strike,r);
+        let put=model.zero_bond_option(false,t,expiry,maturity,strike,r);
+        let forward=model.zero_bond(t,maturity,r)-strike*model.zero_bond(t,expiry,r);
+        assert!(call>0.0 && put>0.0);
+        assert!((call-put-forward).abs()<1e-9);
+    }
 }
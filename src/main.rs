@@ -2,6 +2,8 @@ mod parameters;
 
 //use std::any::Any;
 use std::{env, fs};
+use std::collections::HashMap;
+use serde::de::DeserializeOwned;
 //use std::ffi::OsStr;
 //use serde::{Serialize, Deserialize};
 
@@ -16,11 +18,149 @@ use monaco_lib::model::model::Model;
 use monaco_lib::instrument::instrument::Instrument;
 use monaco_lib::instrument::vanilla_swap::VanillaSwap;
 use monaco_lib::instrument::callable_swap::CallableSwap;
+use monaco_lib::instrument::swaption::Swaption;
+use monaco_lib::instrument::cap_floor::CapFloor;
+use monaco_lib::instrument::fx_forward::FxForward;
+use monaco_lib::instrument::fx_option::FxOption;
+use monaco_lib::instrument::equity_forward::EquityForward;
+use monaco_lib::instrument::equity_option::EquityOption;
+use monaco_lib::instrument::bond::Bond;
+use monaco_lib::instrument::cds::Cds;
+use monaco_lib::instrument::inflation_swap::InflationSwap;
+use monaco_lib::instrument::range_accrual::RangeAccrual;
+use monaco_lib::instrument::tarn::Tarn;
+use monaco_lib::instrument::barrier_option::BarrierOption;
+use monaco_lib::instrument::asian_option::AsianOption;
+use monaco_lib::instrument::scripted::ScriptedInstrument;
+use monaco_lib::instrument::lsm::LsmRegression;
 use monaco_lib::exposure_simulation::controller;
+use monaco_lib::exposure_simulation::analytics::{self,ExposureProfile};
+use monaco_lib::exposure_simulation::netting::NettingDefinition;
+use monaco_lib::calendar::date::Date;
+use monaco_lib::calendar::holiday_calendar::HolidayCalendar;
+use monaco_lib::calendar::schedule::SchedulePeriod;
+
+/// Applies the schedules, calendars and day count conventions and the fixings of a swap and checks its definition (stopping the execution if it is not consistent)
+/// (the generated schedules are added to 'schedules')
+fn prepare_swap(swap:&mut VanillaSwap,file_name:&str,valuation_date:&Option<Date>,calendars:&HashMap<String,HolidayCalendar>,fixing_history:&HashMap<String,Vec<(f64,f64)>>,schedules:&mut HashMap<String,Vec<Vec<SchedulePeriod>>>) -> ()
+{
+    swap.apply_fixing_history(&fixing_history);
+    if let Err(e)=swap.apply_conventions(valuation_date,calendars).and_then(|_| swap.validate())
+    {
+        panic!("Invalid instrument definition ({}): {}",file_name,e);
+    }
+    if swap.legs.iter().any(|l| !l.get_schedule_periods().is_empty())
+    {
+        schedules.insert(file_name.to_string(),swap.legs.iter().map(|l| l.get_schedule_periods().clone()).collect());
+    }
+}
+
+/// Fields of the input files whose values are dates or lists of dates (besides the fields named '*_date' and '*_dates')
+const DATE_FIELDS:[&str;3]=["dates","time_steps","schedules"];
+
+/// Fields of the input files whose values are lists of [date,value] pairs
+const DATED_VALUE_FIELDS:[&str;3]=["fixings","redemptions","dividends"];
+
+/// Replaces the ISO dates (YYYY-MM-DD strings) of a date (or of a list or an object of dates) with their times on the simulation time axis of 'valuation_date'
+fn convert_iso_dates(value:&mut serde_json::Value,valuation_date:&Option<Date>) -> Result<(),String>
+{
+    match value
+    {
+        serde_json::Value::String(text)     =>  {
+                                                    let bytes=text.as_bytes();
+                                                    if bytes.len()==10 && bytes[4]==b'-' && bytes[7]==b'-'
+                                                    {
+                                                        let date=Date::parse(text)?;
+                                                        let time=match valuation_date
+                                                        {
+                                                            Some(d) =>  date.to_time(d),
+                                                            None    =>  return Err(format!("ISO date {} found, but no valuation date is set",text))
+                                                        };
+                                                        *value=serde_json::json!(time);
+                                                    }
+                                                },
+        serde_json::Value::Array(items)     =>  for item in items.iter_mut() { convert_iso_dates(item,valuation_date)?; },
+        serde_json::Value::Object(fields)   =>  for (_,field) in fields.iter_mut() { convert_iso_dates(field,valuation_date)?; },
+        _                                   =>  ()
+    };
+    return Ok(());
+}
+
+/// Replaces the ISO dates of a list of [date,value] pairs (the values are left as they are)
+fn convert_dated_values(value:&mut serde_json::Value,valuation_date:&Option<Date>) -> Result<(),String>
+{
+    if let serde_json::Value::Array(pairs)=value
+    {
+        for pair in pairs.iter_mut()
+        {
+            if let Some(date)=pair.get_mut(0)
+            {
+                convert_iso_dates(date,valuation_date)?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Replaces the ISO dates of the date fields of a json value (see 'DATE_FIELDS' and 'DATED_VALUE_FIELDS'): the other string values are left as they are
+fn convert_date_fields(value:&mut serde_json::Value,valuation_date:&Option<Date>) -> Result<(),String>
+{
+    match value
+    {
+        serde_json::Value::Array(items)     =>  for item in items.iter_mut() { convert_date_fields(item,valuation_date)?; },
+        serde_json::Value::Object(fields)   =>  for (name,field) in fields.iter_mut()
+                                                {
+                                                    if name.ends_with("_date") || name.ends_with("_dates") || DATE_FIELDS.contains(&name.as_str())
+                                                    {
+                                                        convert_iso_dates(field,valuation_date)?;
+                                                    }
+                                                    else if DATED_VALUE_FIELDS.contains(&name.as_str())
+                                                    {
+                                                        convert_dated_values(field,valuation_date)?;
+                                                    }
+                                                    else
+                                                    {
+                                                        convert_date_fields(field,valuation_date)?;
+                                                    }
+                                                },
+        _                                   =>  ()
+    };
+    return Ok(());
+}
+
+/// Reads a json input file (the ISO dates of the date fields are converted to times)
+fn read_with_iso_dates<T:DeserializeOwned>(contents:&str,file_name:&str,valuation_date:&Option<Date>) -> T
+{
+    let mut value:serde_json::Value=serde_json::from_str(contents).unwrap();
+    if let Err(e)=convert_date_fields(&mut value,valuation_date)
+    {
+        panic!("Invalid input file ({}): {}!",file_name,e);
+    }
+    return serde_json::from_value(value).unwrap();
+}
+
+/// Reads the fixing history (index name -> list of [date,rate] pairs, the ISO dates are converted to times)
+fn read_fixing_history(contents:&str,valuation_date:&Option<Date>) -> HashMap<String,Vec<(f64,f64)>>
+{
+    let mut value:serde_json::Value=serde_json::from_str(contents).unwrap();
+    if let serde_json::Value::Object(indices)=&mut value
+    {
+        for (_,fixings) in indices.iter_mut()
+        {
+            if let Err(e)=convert_dated_values(fixings,valuation_date)
+            {
+                panic!("Invalid input file (fixings.json): {}!",e);
+            }
+        }
+    }
+    return serde_json::from_value(value).unwrap();
+}
 
-fn read_instruments(dir_name:&str,logger:&Logger) -> Vec<Box<dyn Instrument>>
+/// Reads the instrument definitions: returns the instruments and the schedules generated for them (by file name and leg)
+fn read_instruments(dir_name:&str,valuation_date:&Option<Date>,calendars:&HashMap<String,HolidayCalendar>,fixing_history:&HashMap<String,Vec<(f64,f64)>>,model_names:&Vec<String>,logger:&Logger) -> (Vec<Box<dyn Instrument>>,HashMap<String,Vec<Vec<SchedulePeriod>>>)
 {
     let mut instruments: Vec<Box<dyn Instrument>> = Vec::new();
+    let mut schedules:HashMap<String,Vec<Vec<SchedulePeriod>>>=HashMap::new();
     for entry in fs::read_dir(dir_name).unwrap()
     {
         let file_entry=entry.unwrap().path();
@@ -35,19 +175,205 @@ fn read_instruments(dir_name:&str,logger:&Logger) -> Vec<Box<dyn Instrument>>
             {
                 "vanilla-swap"  =>  {
                                         //println!("Reading vanilla swap...");
-                                        let deserialized:VanillaSwap=serde_json::from_str(&contents).unwrap();
+                                        let mut deserialized:VanillaSwap=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        prepare_swap(&mut deserialized,file_name,valuation_date,calendars,fixing_history,&mut schedules);
                                         instruments.push(Box::new(deserialized));
                                         //println!("Vanilla swap created.");
                                     },
                 "callable-swap"  =>  {
-                                        let deserialized:CallableSwap=serde_json::from_str(&contents).unwrap();
+                                        let mut deserialized:CallableSwap=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        prepare_swap(&mut deserialized.underlying,file_name,valuation_date,calendars,fixing_history,&mut schedules);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "swaption"  =>  {
+                                        let mut deserialized:Swaption=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        prepare_swap(&mut deserialized.underlying,file_name,valuation_date,calendars,fixing_history,&mut schedules);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "cap-floor"  =>  {
+                                        let mut deserialized:CapFloor=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        deserialized.apply_fixing_history(&fixing_history);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "fx-forward"  =>  {
+                                        let deserialized:FxForward=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "fx-option"  =>  {
+                                        let deserialized:FxOption=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "equity-forward"  =>  {
+                                        let deserialized:EquityForward=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "equity-option"  =>  {
+                                        let deserialized:EquityOption=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "inflation-swap"  =>  {
+                                        let mut deserialized:InflationSwap=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        deserialized.apply_fixing_history(&fixing_history);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "range-accrual"  =>  {
+                                        let deserialized:RangeAccrual=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "tarn"  =>  {
+                                        let deserialized:Tarn=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "barrier-option"  =>  {
+                                        let deserialized:BarrierOption=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "asian-option"  =>  {
+                                        let deserialized:AsianOption=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "scripted"  =>  {
+                                        let deserialized:ScriptedInstrument=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate(model_names)
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "cds"  =>  {
+                                        let deserialized:Cds=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        if let Err(e)=deserialized.validate()
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        instruments.push(Box::new(deserialized));
+                                    },
+                "bond"  =>  {
+                                        let mut deserialized:Bond=read_with_iso_dates(&contents,file_name,valuation_date);
+                                        deserialized.apply_fixing_history(&fixing_history);
+                                        if let Err(e)=deserialized.apply_conventions(valuation_date,calendars).and_then(|_| deserialized.validate())
+                                        {
+                                            panic!("Invalid instrument definition ({}): {}",file_name,e);
+                                        }
+                                        if !deserialized.get_schedule_periods().is_empty()
+                                        {
+                                            schedules.insert(file_name.to_string(),vec![deserialized.get_schedule_periods().clone()]);
+                                        }
                                         instruments.push(Box::new(deserialized));
                                     },
                 &_      =>      ()
             };
         }                           
     }
-    return instruments;
+    return (instruments,schedules);
+}
+
+/// Reads the control file (the time steps can be ISO dates, converted with the valuation date)
+fn read_parameters(contents:&str) -> RunParameters
+{
+    let mut value:serde_json::Value=serde_json::from_str(contents).unwrap();
+    let valuation_date=match value.get("valuation_date").and_then(|d| d.as_str())
+    {
+        Some(d) if !d.is_empty()    =>  Some(Date::parse(d).unwrap()),
+        _                           =>  None
+    };
+    if let Some(time_steps)=value.get_mut("time_steps")
+    {
+        if let Err(e)=convert_iso_dates(time_steps,&valuation_date)
+        {
+            panic!("Invalid control file: {}!",e);
+        }
+    }
+    return serde_json::from_value(value).unwrap();
+}
+
+/// Formats the LSM diagnostics for the exercise dates as CSV (vectors are space-separated)
+fn lsm_diagnostics_to_csv(regressions:&Vec<LsmRegression>) -> String
+{
+    let join=|v:&Vec<f64>| v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ");
+    let mut csv=String::from("date,num_paths_itm,exercise_probability,exercise_r_squared,pricing_r_squared,exercise_parameters,pricing_parameters,exercise_boundary\n");
+    for r in regressions.iter().filter(|r| r.is_exercise_date)
+    {
+        csv.push_str(format!("{},{},{},{},{},{},{},{}\n",r.date,r.num_paths_itm,r.exercise_probability,r.exercise_r_squared,r.pricing_r_squared,join(&r.exercise_parameters),join(&r.pricing_parameters),join(&r.exercise_boundary)).as_str());
+    }
+    return csv;
+}
+
+/// Formats the exposure profiles as CSV (one row per cube, time series and date)
+fn exposure_profiles_to_csv(profiles:&HashMap<String,Vec<ExposureProfile>>,quantiles:&Vec<f64>) -> String
+{
+    let mut csv=String::from("cube,name,date,ee,ee_std_error,ene,ene_std_error,effective_ee");
+    for q in quantiles.iter()
+    {
+        csv.push_str(format!(",pfe_{},pfe_{}_std_error",q,q).as_str());
+    }
+    csv.push('\n');
+    let mut cubes:Vec<&String>=profiles.keys().collect();
+    cubes.sort();
+    for cube in cubes
+    {
+        for p in profiles[cube].iter()
+        {
+            for d in 0..p.dates.len()
+            {
+                csv.push_str(format!("{},{},{},{},{},{},{},{}",cube,p.name,p.dates[d],p.ee[d],p.ee_std_error[d],p.ene[d],p.ene_std_error[d],p.effective_ee[d]).as_str());
+                for pfe in p.pfe.iter()
+                {
+                    csv.push_str(format!(",{},{}",pfe.pfe[d],pfe.pfe_std_error[d]).as_str());
+                }
+                csv.push('\n');
+            }
+        }
+    }
+    return csv;
 }
 
 const VERSION:&str="0.9";
@@ -64,6 +390,9 @@ fn main() {
     let mut models: Vec<Box<dyn Model>> = Vec::new();
     //let mut instruments: Vec<Box<dyn Instrument>> = Vec::new();
     let mut correlation_matrix:Vec<f64>=Vec::new();
+    let mut fixing_contents:String=String::new();
+    let mut calendars:HashMap<String,HolidayCalendar>=HashMap::new();
+    let mut netting:Option<NettingDefinition>=None;
     let mut parameters:RunParameters=RunParameters
     {
         log_tags:Vec::new(),
@@ -81,7 +410,25 @@ fn main() {
         output_file_cashflows:String::new(),
         exercise_output_dir:String::new(),
         recycle_randomness:false,
-        randomness_file:String::new()
+        randomness_file:String::new(),
+        lsm_training_paths:false,
+        lsm_training_num_paths:0,
+        lsm_training_seed:0,
+        compute_dual_bounds:false,
+        dual_bounds_outer_paths:0,
+        dual_bounds_inner_paths:0,
+        dual_bounds_seed:0,
+        output_file_bounds:String::new(),
+        valuation_date:String::new(),
+        output_file_trade_diagnostics:String::new(),
+        output_file_netting_set_exposures:String::new(),
+        output_file_counterparty_exposures:String::new(),
+        pfe_quantiles:Vec::new(),
+        exposure_horizon:1.0,
+        output_file_exposure_profiles:String::new(),
+        output_file_exposure_profiles_csv:String::new(),
+        output_file_collateral:String::new(),
+        output_file_collateralised_exposures:String::new()
     };
 
     let args: Vec<String> = env::args().collect();
@@ -95,8 +442,10 @@ fn main() {
         let contents = fs::read_to_string(&file_entry).expect("Error reading file!");
         match file_name
         {
-            "control.json"    => { logger.log("Reading control file...","app"); let deserialized:RunParameters=serde_json::from_str(&contents).unwrap(); parameters=deserialized; },
+            "control.json"    => { logger.log("Reading control file...","app"); parameters=read_parameters(&contents); },
             "correlations.json"     => { logger.log("Reading correlations...","app"); let deserialized:Vec<f64>=serde_json::from_str(&contents).unwrap(); correlation_matrix=deserialized; },
+            "fixings.json"          => { logger.log("Reading fixing history...","app"); fixing_contents=contents; },
+            "counterparties.json"   => { logger.log("Reading netting sets...","app"); let deserialized:NettingDefinition=serde_json::from_str(&contents).unwrap(); netting=Some(deserialized); },
             &_                      => {
                                             if x.len()>1
                                             {
@@ -115,6 +464,15 @@ fn main() {
                                                                             let deserialized:Fixed=serde_json::from_str(&contents).unwrap();
                                                                             models.push(Box::new(deserialized));
                                                                         },
+                                                        //Holiday calendars
+                                                        "calendar"  =>  {
+                                                                            let mut deserialized:HolidayCalendar=serde_json::from_str(&contents).unwrap();
+                                                                            if let Err(e)=deserialized.init()
+                                                                            {
+                                                                                panic!("Invalid calendar definition ({}): {}!",file_name,e);
+                                                                            }
+                                                                            calendars.insert(deserialized.name.clone(),deserialized);
+                                                                        },
                                                         &_      =>      ()
                                                 };
                                             }
@@ -122,7 +480,38 @@ fn main() {
         }
     }
 
+    if let Err(e)=parameters.validate()
+    {
+        panic!("Invalid control file: {}!",e);
+    }
     logger=Logger { log_tags:parameters.log_tags };
+    let valuation_date=match parameters.valuation_date.is_empty()
+    {
+        true    =>  None,
+        false   =>  Some(Date::parse(&parameters.valuation_date).unwrap())
+    };
+    let fixing_history:HashMap<String,Vec<(f64,f64)>>=match fixing_contents.is_empty()
+    {
+        true    =>  HashMap::new(),
+        false   =>  read_fixing_history(&fixing_contents,&valuation_date)
+    };
+
+    if let Some(netting)=&netting
+    {
+        if let Err(e)=netting.validate_csas()
+        {
+            panic!("Invalid netting set definition (counterparties.json): {}",e);
+        }
+        match parameters.recycle_randomness
+        {
+            true    =>  logger.log("Recycled randomness: the margin period of risk lookback dates must already be in the simulation dates (checked once the randomness is loaded)","app"),
+            false   =>  {
+                            let num_dates=parameters.time_steps.len();
+                            parameters.time_steps=netting.add_lookback_dates(&parameters.time_steps);
+                            logger.log(format!("Margin period of risk lookback dates added: {}",parameters.time_steps.len()-num_dates),"app");
+                        }
+        }
+    }
 
     logger.log_with_check("Sorting models...","app",0);
     models.sort_by(|a, b| a.get_name().partial_cmp(&b.get_name()).unwrap());
@@ -145,10 +534,45 @@ fn main() {
         false =>    controller::compute_paths(&models, &parameters.time_steps, parameters.num_paths, &correlation_matrix,&logger)
 
     };
+    if let (true,Some(netting))=(parameters.recycle_randomness,&netting)
+    {
+        if let Err(e)=netting.check_lookback_dates(&paths.dates)
+        {
+            panic!("Invalid netting set definition (counterparties.json): {} (recycled randomness: the lookback dates cannot be added)",e);
+        }
+    }
     let live_models=controller::create_live_models(&models, &paths, &raw_cube, &logger);
 
+    //Independent paths used only to estimate the LSM regressions
+    let training_num_paths=if parameters.lsm_training_num_paths>0 { parameters.lsm_training_num_paths } else { parameters.num_paths };
+    let (training_paths,training_raw_cube)=match parameters.lsm_training_paths
+    {
+        true    =>  {
+                        logger.log(format!("Computing LSM training paths (paths: {}, seed: {})...",training_num_paths,parameters.lsm_training_seed),"app");
+                        controller::compute_paths_with_seed(&models, &parameters.time_steps, training_num_paths, &correlation_matrix, parameters.lsm_training_seed, &logger)
+                    },
+        false   =>  (Cube::make_empty_cube(Vec::new(), 0, 0),Cube::make_empty_cube(Vec::new(), 0, 0))
+    };
+    let training_live_models=match parameters.lsm_training_paths
+    {
+        true    =>  Some(controller::create_live_models(&models, &training_paths, &training_raw_cube, &logger)),
+        false   =>  None
+    };
+
     logger.log("Reading instruments...","app");
-    let mut instruments=read_instruments(&args[1],&logger);
+    let model_names:Vec<String>=models.iter().map(|m| m.get_name()).collect();
+    let (mut instruments,schedules)=read_instruments(&args[1],&valuation_date,&calendars,&fixing_history,&model_names,&logger);
+    if !parameters.output_file_trade_diagnostics.is_empty()
+    {
+        logger.log("Writing trade diagnostics...","app");
+        let serialized_schedules=serde_json::to_string(&schedules).unwrap();
+        let write_res=fs::write(parameters.output_file_trade_diagnostics.as_str(),serialized_schedules);
+        match write_res
+        {
+            Ok(_) =>  logger.log(format!("Trade diagnostics written."),"app"),
+            Err(_)  =>   logger.log(format!("Error writing trade diagnostics!"),"app")
+        }
+    }
 
     logger.log(format!("Computing exposures..."),"app");
     logger.log(format!("Num models           : {0}",models.len()),"app");
@@ -164,10 +588,102 @@ fn main() {
     //controller::populate_live_models(&live_models, &mut instruments, log);
 
     let results_cube=Cube::make_empty_cube(parameters.time_steps.clone(),parameters.num_paths,instruments.len());
-    let (exposures,cashflows,exercise_cubes)=controller::compute_exposures(&mut instruments,results_cube,&live_models,&logger);
+    let (exposures,cashflows,exercise_cubes,regressions)=controller::compute_exposures(&mut instruments,results_cube,&live_models,training_live_models.as_ref(),&logger);
 
     println!("Results ready.");
 
+    let mut profile_cubes:Vec<(String,Cube)>=Vec::new();
+    if let Some(netting)=&netting
+    {
+        if let Err(e)=netting.validate(&exposures.time_series_names)
+        {
+            panic!("Invalid netting set definition (counterparties.json): {}",e);
+        }
+        logger.log(format!("Computing netting set exposures ({} netting sets)...",netting.netting_sets.len()),"app");
+        let netting_set_exposures=netting.compute_netting_set_cube(&exposures);
+        if !parameters.output_file_netting_set_exposures.is_empty()
+        {
+            logger.log(format!("Writing netting set exposures to: {}",parameters.output_file_netting_set_exposures),"app");
+            match fs::write(&parameters.output_file_netting_set_exposures, serde_json::to_string(&netting_set_exposures).unwrap())
+            {
+                Ok(_)   =>  logger.log(format!("Netting set exposures written."),"app"),
+                Err(_)  =>  logger.log(format!("Error writing netting set exposures!"),"app")
+            }
+        }
+        if !parameters.output_file_counterparty_exposures.is_empty()
+        {
+            let counterparty_exposures=netting.compute_counterparty_cube(&netting_set_exposures);
+            logger.log(format!("Writing counterparty exposures to: {}",parameters.output_file_counterparty_exposures),"app");
+            match fs::write(&parameters.output_file_counterparty_exposures, serde_json::to_string(&counterparty_exposures).unwrap())
+            {
+                Ok(_)   =>  logger.log(format!("Counterparty exposures written."),"app"),
+                Err(_)  =>  logger.log(format!("Error writing counterparty exposures!"),"app")
+            }
+        }
+        if netting.netting_sets.iter().any(|n| n.csa.is_some())
+        {
+            logger.log("Computing collateral...","app");
+            let (collateral,collateralised_exposures)=match netting.compute_collateral_cubes(&netting_set_exposures)
+            {
+                Ok(cubes)   =>  cubes,
+                Err(e)      =>  panic!("Invalid netting set definition (counterparties.json): {}",e)
+            };
+            if !parameters.output_file_collateral.is_empty()
+            {
+                logger.log(format!("Writing collateral to: {}",parameters.output_file_collateral),"app");
+                match fs::write(&parameters.output_file_collateral, serde_json::to_string(&collateral).unwrap())
+                {
+                    Ok(_)   =>  logger.log(format!("Collateral written."),"app"),
+                    Err(_)  =>  logger.log(format!("Error writing collateral!"),"app")
+                }
+            }
+            if !parameters.output_file_collateralised_exposures.is_empty()
+            {
+                logger.log(format!("Writing collateralised exposures to: {}",parameters.output_file_collateralised_exposures),"app");
+                match fs::write(&parameters.output_file_collateralised_exposures, serde_json::to_string(&collateralised_exposures).unwrap())
+                {
+                    Ok(_)   =>  logger.log(format!("Collateralised exposures written."),"app"),
+                    Err(_)  =>  logger.log(format!("Error writing collateralised exposures!"),"app")
+                }
+            }
+            profile_cubes.push(("collateralised_netting_sets".to_string(),collateralised_exposures));
+        }
+        profile_cubes.push(("netting_sets".to_string(),netting_set_exposures));
+    }
+
+    if !parameters.output_file_exposure_profiles.is_empty() || !parameters.output_file_exposure_profiles_csv.is_empty()
+    {
+        if let Err(e)=analytics::validate_profile_parameters(&parameters.pfe_quantiles,parameters.exposure_horizon)
+        {
+            panic!("Invalid control file: {}",e);
+        }
+        logger.log(format!("Computing exposure profiles (PFE quantiles: {:?}, horizon: {})...",parameters.pfe_quantiles,parameters.exposure_horizon),"app");
+        let mut profiles:HashMap<String,Vec<ExposureProfile>>=HashMap::new();
+        profiles.insert("trades".to_string(),analytics::compute_profiles(&exposures,&parameters.pfe_quantiles,parameters.exposure_horizon));
+        for (cube_name,cube) in profile_cubes.iter()
+        {
+            profiles.insert(cube_name.clone(),analytics::compute_profiles(cube,&parameters.pfe_quantiles,parameters.exposure_horizon));
+        }
+        if !parameters.output_file_exposure_profiles.is_empty()
+        {
+            logger.log(format!("Writing exposure profiles to: {}",parameters.output_file_exposure_profiles),"app");
+            match fs::write(&parameters.output_file_exposure_profiles, serde_json::to_string(&profiles).unwrap())
+            {
+                Ok(_)   =>  logger.log(format!("Exposure profiles written."),"app"),
+                Err(_)  =>  logger.log(format!("Error writing exposure profiles!"),"app")
+            }
+        }
+        if !parameters.output_file_exposure_profiles_csv.is_empty()
+        {
+            logger.log(format!("Writing exposure profiles to: {}",parameters.output_file_exposure_profiles_csv),"app");
+            match fs::write(&parameters.output_file_exposure_profiles_csv, exposure_profiles_to_csv(&profiles,&parameters.pfe_quantiles))
+            {
+                Ok(_)   =>  logger.log(format!("Exposure profiles written."),"app"),
+                Err(_)  =>  logger.log(format!("Error writing exposure profiles!"),"app")
+            }
+        }
+    }
+
 
     logger.log(format!("Writing exercise cubes to {}...",parameters.exercise_output_dir),"app");
 
@@ -183,6 +699,49 @@ fn main() {
             Ok(_) =>  logger.log(format!("Exercise cube for: {} written.",ins_name),"app"),
             Err(_)  =>   logger.log(format!("Error writing exercise cube for: {}!",ins_name),"app")
         }
+        if regressions[c].len()>0
+        {
+            let serialized_regressions = serde_json::to_string(&regressions[c]).unwrap();
+            let file_name=format!("{}/{} (regressions).json",parameters.exercise_output_dir.as_str(),ins_name);
+            logger.log(format!("Writing LSM regressions for: {} ({})",ins_name,file_name),"app");
+            let write_res=fs::write(file_name, serialized_regressions);
+            match write_res
+            {
+                Ok(_) =>  logger.log(format!("LSM regressions for: {} written.",ins_name),"app"),
+                Err(_)  =>   logger.log(format!("Error writing LSM regressions for: {}!",ins_name),"app")
+            }
+            let file_name=format!("{}/{} (lsm diagnostics).csv",parameters.exercise_output_dir.as_str(),ins_name);
+            logger.log(format!("Writing LSM diagnostics for: {} ({})",ins_name,file_name),"app");
+            let write_res=fs::write(file_name, lsm_diagnostics_to_csv(&regressions[c]));
+            match write_res
+            {
+                Ok(_) =>  logger.log(format!("LSM diagnostics for: {} written.",ins_name),"app"),
+                Err(_)  =>   logger.log(format!("Error writing LSM diagnostics for: {}!",ins_name),"app")
+            }
+        }
+    }
+
+    if parameters.compute_dual_bounds
+    {
+        logger.log(format!("Computing dual bounds (outer paths: {}, inner paths: {})...",parameters.dual_bounds_outer_paths,parameters.dual_bounds_inner_paths),"app");
+        if !parameters.lsm_training_paths
+        {
+            logger.log("Warning: the LSM regressions were estimated on the simulation paths, the lower bounds may be biased upwards (set lsm_training_paths to avoid it)","app");
+        }
+        let simulator=controller::NestedSimulator { models:&models, correlation_matrix:&correlation_matrix, seed:parameters.dual_bounds_seed };
+        let bounds=controller::compute_bounds(&instruments,&parameters.time_steps,&live_models,&regressions,&simulator,parameters.dual_bounds_outer_paths,parameters.dual_bounds_inner_paths,&logger);
+        for b in bounds.iter()
+        {
+            logger.log(format!("Bounds for {}: lower: {} ({}), upper: {} ({}), gap: {} ({})",b.name,b.lower_bound,b.lower_bound_std_error,b.upper_bound,b.upper_bound_std_error,b.duality_gap,b.duality_gap_std_error),"app");
+        }
+        logger.log(format!("Writing bounds to: {}",parameters.output_file_bounds),"app");
+        let serialized_bounds = serde_json::to_string(&bounds).unwrap();
+        let write_res=fs::write(&parameters.output_file_bounds, serialized_bounds);
+        match write_res
+        {
+            Ok(_) =>  logger.log(format!("Bounds written."),"app"),
+            Err(_)  =>   logger.log(format!("Error writing bounds!"),"app")
+        }
     }
 
     logger.log("Writing cashflows...","app");
@@ -194,6 +753,23 @@ fn main() {
         Err(_)  =>   logger.log(format!("Error writing cashflows!"),"app")
     }
 
+    for i in 0..instruments.len()
+    {
+        if let Some(components)=instruments[i].compute_cashflow_components(parameters.num_paths,&live_models,&logger)
+        {
+            let ins_name=instruments[i].get_name();
+            let serialized_components = serde_json::to_string(&components).unwrap();
+            let file_name=format!("{}/{} (cashflow components).json",parameters.exercise_output_dir.as_str(),ins_name);
+            logger.log(format!("Writing cashflow components for: {} ({})",ins_name,file_name),"app");
+            let write_res=fs::write(file_name, serialized_components);
+            match write_res
+            {
+                Ok(_) =>  logger.log(format!("Cashflow components for: {} written.",ins_name),"app"),
+                Err(_)  =>   logger.log(format!("Error writing cashflow components for: {}!",ins_name),"app")
+            }
+        }
+    }
+
     // println!("Results cube - Num paths    : {0}",results_cube.num_scenarios);
     // println!("Results cube - Num dates    : {0}",results_cube.dates.len());
     // println!("Results cube - Num series : {0}",results_cube.num_series);
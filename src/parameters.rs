@@ -1,5 +1,15 @@
 use serde::{Serialize, Deserialize};
 
+fn default_pfe_quantiles() -> Vec<f64>
+{
+    return vec![0.95];
+}
+
+fn default_exposure_horizon() -> f64
+{
+    return 1.0;
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RunParameters
 {
@@ -18,5 +28,56 @@ pub struct RunParameters
     pub output_file_cashflows:String,
     pub exercise_output_dir:String,
     pub recycle_randomness:bool,
-    pub randomness_file:String
-}
\ No newline at end of file
+    pub randomness_file:String,
+    #[serde(default)]
+    pub lsm_training_paths:bool,
+    #[serde(default)]
+    pub lsm_training_num_paths:usize,
+    #[serde(default)]
+    pub lsm_training_seed:u64,
+    #[serde(default)]
+    pub compute_dual_bounds:bool,
+    #[serde(default)]
+    pub dual_bounds_outer_paths:usize,
+    #[serde(default)]
+    pub dual_bounds_inner_paths:usize,
+    #[serde(default)]
+    pub dual_bounds_seed:u64,
+    #[serde(default)]
+    pub output_file_bounds:String,
+    #[serde(default)]
+    pub valuation_date:String,
+    #[serde(default)]
+    pub output_file_trade_diagnostics:String,
+    #[serde(default)]
+    pub output_file_netting_set_exposures:String,
+    #[serde(default)]
+    pub output_file_counterparty_exposures:String,
+    #[serde(default="default_pfe_quantiles")]
+    pub pfe_quantiles:Vec<f64>,
+    #[serde(default="default_exposure_horizon")]
+    pub exposure_horizon:f64,
+    #[serde(default)]
+    pub output_file_exposure_profiles:String,
+    #[serde(default)]
+    pub output_file_exposure_profiles_csv:String,
+    #[serde(default)]
+    pub output_file_collateral:String,
+    #[serde(default)]
+    pub output_file_collateralised_exposures:String
+}
+impl RunParameters
+{
+    pub fn validate(&self) -> Result<(),String>
+    {
+        if self.compute_dual_bounds && self.dual_bounds_outer_paths==0
+        {
+            return Err("dual_bounds_outer_paths must be positive when compute_dual_bounds is set".to_string());
+        }
+        if self.compute_dual_bounds && self.dual_bounds_inner_paths==0
+        {
+            return Err("dual_bounds_inner_paths must be positive when compute_dual_bounds is set".to_string());
+        }
+        return Ok(());
+    }
+}